@@ -1,6 +1,6 @@
 use board::Board;
 use merging_board_logic::board_repr::BoardRepr;
-use merging_board_logic::pieces::movement::find_any_legal_move;
+use merging_board_logic::game::Game;
 use merging_board_logic::pieces::{movement::Move, Color};
 use merging_engine::{AlphaBetaMinimax, Engine};
 use yew::prelude::*;
@@ -49,52 +49,43 @@ fn Home() -> Html {
 
 #[function_component]
 fn EngineDemo() -> Html {
-    let board_state = use_state(BoardRepr::default);
+    let game_state = use_state(Game::default);
     let engine = use_state(AlphaBetaMinimax::new);
-    let keep_playing = use_state(|| true);
     let onmove = {
-        let board_state = board_state.clone();
+        let game_state = game_state.clone();
         let engine = engine.clone();
-        let keep_playing = keep_playing.clone();
         Callback::from(move |move_: Move| {
             log::info!("Move: {move_:?}");
-            let mut state = *board_state;
-            state.play(move_).expect("Move from board was illegal");
-            board_state.set(state);
-
-            // Check if the engine has any legal moves
-            if find_any_legal_move(&state, Color::Black).is_none() {
-                keep_playing.set(false);
-                return;
+            let mut game = (*game_state).clone();
+            game.play(move_).expect("Move from board was illegal");
+
+            // Find a move from the engine and play it, unless the game is over
+            if game.outcome().is_none() {
+                let mut engine_val = (*engine).clone();
+                let engine_move = engine_val.think(&game);
+                game.play(engine_move)
+                    .expect("Move from engine was illegal");
+                engine.set(engine_val);
             }
 
-            // Find a move from the engine and play it
-
-            let mut engine_val = (*engine).clone();
-            let engine_move = engine_val.think(&state);
-            state
-                .play(engine_move)
-                .expect("Move from engine was illegal");
-
-            engine.set(engine_val);
-            board_state.set(state);
-
-            // Check if the player has any legal moves
-            if find_any_legal_move(&state, Color::White).is_none() {
-                keep_playing.set(false);
-            }
+            game_state.set(game);
         })
     };
+    let board_state = *game_state.board();
+    let outcome = game_state.outcome();
 
     html! {
         <>
             <div class="row">
-                <Board style="max-width: 33%;" class={"container"} onmove={onmove.clone()} as_black={false} board={*board_state} interactable={board_state.side_to_move == Color::White && *keep_playing}/>
+                <Board style="max-width: 33%;" class={"container"} onmove={onmove.clone()} as_black={false} board={board_state} interactable={board_state.side_to_move == Color::White && outcome.is_none()}/>
             </div>
+            if let Some(outcome) = outcome {
+                <p>{outcome.to_string()}</p>
+            }
             <p>{"White king in check: "}{board_state.king_in_check(merging_board_logic::pieces::Color::White)}</p>
             <p>{"Black king in check: "}{board_state.king_in_check(merging_board_logic::pieces::Color::Black)}</p>
             <code><pre>
-                {format!("{:#?}", *board_state)}
+                {format!("{:#?}", board_state)}
             </pre></code>
         </>
     }
@@ -102,46 +93,20 @@ fn EngineDemo() -> Html {
 
 #[function_component]
 fn ForScreenshot() -> Html {
-    use merging_board_logic::pieces;
+    use merging_board_logic::builder::BoardBuilder;
     use merging_board_logic::square::Square;
-    use merging_board_logic::square::Square::*;
-
-    use pieces::{ColorPiece, CombinationPiece, Piece, UnitaryPiece};
-    use UnitaryPiece::*;
-    // let mut board = BoardRepr::empty();
-    // board[Square::E4] = Some(ColorPiece::White(Piece::Combination(
-    //     CombinationPiece::new(Queen, Knight).unwrap(),
-    // )));
-    // board[Square::A1] = Some(ColorPiece::White(Piece::Unitary(King)));
-    // board[Square::H8] = Some(ColorPiece::Black(Piece::Unitary(King)));
 
     let combinations = [
-        CombinationPiece::new(Queen, Bishop).unwrap(),
-        CombinationPiece::new(Queen, Knight).unwrap(),
-        CombinationPiece::new(Queen, Rook).unwrap(),
-        CombinationPiece::new(Queen, Pawn).unwrap(),
-        CombinationPiece::new(Bishop, Knight).unwrap(),
-        CombinationPiece::new(Bishop, Rook).unwrap(),
-        CombinationPiece::new(Bishop, Pawn).unwrap(),
-        CombinationPiece::new(Knight, Rook).unwrap(),
-        CombinationPiece::new(Knight, Pawn).unwrap(),
-        CombinationPiece::new(Rook, Pawn).unwrap(),
-        CombinationPiece::new(Pawn, Pawn).unwrap(),
-        CombinationPiece::new(Rook, Rook).unwrap(),
-        CombinationPiece::new(Knight, Knight).unwrap(),
-        CombinationPiece::new(Bishop, Bishop).unwrap(),
-        CombinationPiece::new(Queen, Queen).unwrap(),
+        "QB", "QN", "QR", "QP", "BN", "BR", "BP", "NR", "NP", "RP", "PP", "RR", "NN", "BB", "QQ",
     ];
 
-    let mut board = BoardRepr::default();
-
-    for (idx, combination) in combinations.iter().enumerate() {
-        board[G4 as usize - idx] = Some(ColorPiece::White(Piece::Combination(combination.clone())));
-        board[B5 as usize + idx] = Some(ColorPiece::Black(Piece::Combination(combination.clone())));
+    let mut builder = BoardBuilder::from(BoardRepr::default());
+    for (idx, code) in combinations.into_iter().enumerate() {
+        builder = builder
+            .white(Square::ALL[Square::G4 as usize - idx], code)
+            .black(Square::ALL[Square::B5 as usize + idx], code);
     }
-
-    board[Square::E1] = Some(ColorPiece::White(Piece::Unitary(King)));
-    board[Square::E8] = Some(ColorPiece::Black(Piece::Unitary(King)));
+    let board = builder.build().expect("Screenshot position is invalid");
 
     html! {
         <>
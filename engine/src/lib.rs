@@ -1,11 +1,12 @@
+use game::Game;
 use merging_board_logic::*;
 use minimax_alpha_beta::strategy::alpha_beta_minimax::AlphaBetaMiniMaxStrategy;
-use pieces::movement::get_all_legal_moves;
 use strategy::MergingChessStrategy;
 pub trait Engine: Clone + Send + Sync {
     fn new() -> Self;
 
-    fn think(&mut self, board_repr: &board_repr::BoardRepr) -> pieces::movement::Move;
+    /// Picks a move in the current position of the game, by the rules of its variant.
+    fn think(&mut self, game: &Game) -> pieces::movement::Move;
 }
 
 #[derive(Clone)]
@@ -15,8 +16,8 @@ impl Engine for FirstMove {
     fn new() -> Self {
         Self {}
     }
-    fn think(&mut self, board_repr: &board_repr::BoardRepr) -> pieces::movement::Move {
-        let moves = get_all_legal_moves(board_repr, board_repr.side_to_move);
+    fn think(&mut self, game: &Game) -> pieces::movement::Move {
+        let moves = game.legal_moves();
 
         moves[0]
     }
@@ -31,8 +32,11 @@ impl Engine for AlphaBetaMinimax {
     fn new() -> Self {
         Self { depth: 2 }
     }
-    fn think(&mut self, board_repr: &board_repr::BoardRepr) -> pieces::movement::Move {
-        let mut strat = MergingChessStrategy::from(*board_repr);
-        strat.get_best_move(self.depth, board_repr.side_to_move == pieces::Color::White)
+    fn think(&mut self, game: &Game) -> pieces::movement::Move {
+        let mut strat = MergingChessStrategy::from(game.clone());
+        strat.get_best_move(
+            self.depth,
+            game.board().side_to_move == pieces::Color::White,
+        )
     }
 }
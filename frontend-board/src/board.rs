@@ -7,6 +7,7 @@ use merging_board_logic::board_repr::BoardRepr;
 use merging_board_logic::pieces::movement::{get_legal_moves_from_square, Move, MovesList};
 use merging_board_logic::pieces::{Color, Piece, PieceHalf};
 use merging_board_logic::square::Square;
+use merging_board_logic::variant::Variant;
 
 #[derive(Properties, PartialEq, Default)]
 pub struct BoardProps {
@@ -27,6 +28,10 @@ pub struct BoardProps {
 
     #[prop_or_default]
     pub as_black: bool,
+
+    /// The variant being played, which may forbid some of the legal moves.
+    #[prop_or_default]
+    pub variant: &'static dyn Variant,
 }
 
 #[function_component]
@@ -38,6 +43,7 @@ pub fn Board(props: &BoardProps) -> Html {
         onmove,
         as_black,
         interactable,
+        variant,
     } = props;
 
     let wrap_node = use_node_ref();
@@ -179,7 +185,10 @@ pub fn Board(props: &BoardProps) -> Html {
             square,
             *combo_selection,
         );
-        for move_ in moves {
+        for move_ in moves
+            .into_iter()
+            .filter(|move_| variant.allows_move(board, *move_))
+        {
             let onclick = {
                 shadow_clone!(onmove_wrapper);
                 Callback::from(move |ev: MouseEvent| {
@@ -200,17 +209,16 @@ pub fn Board(props: &BoardProps) -> Html {
 
     // If either side is in check,
     // display the check indicator
-    // on the king's square
-    if board.king_in_check(Color::White) {
-        pieces.push(html! {
-            <square class="check" style={square_to_transform(board.king_square(Color::White))}></square>
-        });
-    }
-
-    if board.king_in_check(Color::Black) {
-        pieces.push(html! {
-            <square class="check" style={square_to_transform(board.king_square(Color::Black))}></square>
-        });
+    // on the king's square.
+    // A board being set up may have no king at all
+    for color in [Color::White, Color::Black] {
+        if let Some(king_square) = board.try_king_square(color) {
+            if board.king_in_check(color) {
+                pieces.push(html! {
+                    <square class="check" style={square_to_transform(king_square)}></square>
+                });
+            }
+        }
     }
 
     // If there is a previous move,
@@ -0,0 +1,39 @@
+//! Runs the whole perft suite, printing how fast each position is searched.
+//! Run with `cargo bench -p merging-board-logic`.
+
+use std::time::Instant;
+
+use merging_board_logic::{
+    board_repr::BoardRepr,
+    perft::{parse_perft_suite, perft},
+};
+
+const SUITE: &str = include_str!("../tests/perft_suite.epd");
+
+fn main() {
+    let mut total_nodes = 0;
+    let start = Instant::now();
+
+    for position in parse_perft_suite(SUITE) {
+        let board = BoardRepr::from_fen(&position.fen).unwrap();
+        let (depth, expected) = *position.expected.last().unwrap();
+
+        let position_start = Instant::now();
+        let nodes = perft(&board, depth);
+        let elapsed = position_start.elapsed();
+        assert_eq!(nodes, expected, "{} at depth {depth}", position.fen);
+
+        println!(
+            "{:<70} D{depth} {nodes:>10} nodes {:>10.0} nodes/s",
+            position.fen,
+            nodes as f64 / elapsed.as_secs_f64()
+        );
+        total_nodes += nodes;
+    }
+
+    let elapsed = start.elapsed();
+    println!(
+        "total: {total_nodes} nodes in {elapsed:.2?}, {:.0} nodes/s",
+        total_nodes as f64 / elapsed.as_secs_f64()
+    );
+}
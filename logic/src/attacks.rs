@@ -0,0 +1,151 @@
+//! Which squares pieces attack, meaning the squares they could capture on.
+//!
+//! A combination attacks every square that either of its halves attacks,
+//! and a piece containing a pawn attacks the squares diagonally forward of it,
+//! even though the pawn moves straight.
+//! These are used for check detection and castling,
+//! and are much cheaper than generating the moves of every enemy piece.
+
+use crate::{
+    bitboard::{
+        bishop_attacks, king_attacks, knight_attacks, pawn_attacks, queen_attacks, rook_attacks,
+        Bitboard, Bitboards,
+    },
+    board_repr::BoardRepr,
+    pieces::{Color, Piece, UnitaryPiece},
+    square::Square,
+};
+
+/// The squares attacked by a unitary piece of the given color standing on a square.
+fn unitary_attacks(
+    piece: UnitaryPiece,
+    color: Color,
+    square: Square,
+    occupied: Bitboard,
+) -> Bitboard {
+    match piece {
+        UnitaryPiece::Pawn => pawn_attacks(color, square),
+        UnitaryPiece::Knight => knight_attacks(square),
+        UnitaryPiece::Bishop => bishop_attacks(square, occupied),
+        UnitaryPiece::Rook => rook_attacks(square, occupied),
+        UnitaryPiece::Queen => queen_attacks(square, occupied),
+        UnitaryPiece::King => king_attacks(square),
+    }
+}
+
+impl Bitboards {
+    /// The squares attacked by the piece on the given square, whatever is on them.
+    /// An empty square attacks nothing.
+    pub fn attacks_from(&self, square: Square) -> Bitboard {
+        let Some(piece) = self.piece_at(square) else {
+            return Bitboard::EMPTY;
+        };
+        let occupied = self.occupied();
+        let attacks = |unitary| unitary_attacks(unitary, piece.color(), square, occupied);
+        match piece.piece() {
+            Piece::Unitary(unitary) => attacks(unitary),
+            Piece::Combination(combo) => attacks(combo.first()) | attacks(combo.second()),
+        }
+    }
+
+    /// The squares of the pieces of the given color that attack the square.
+    pub fn attackers_of(&self, square: Square, color: Color) -> Bitboard {
+        let occupied = self.occupied();
+
+        // A piece attacks the square if a piece of the same kind on the square would attack it back,
+        // except for pawns, which attack in the direction they move in
+        let attackers = (knight_attacks(square) & self.by_component(UnitaryPiece::Knight))
+            | (king_attacks(square) & self.by_component(UnitaryPiece::King))
+            | (pawn_attacks(color.opposite(), square) & self.by_component(UnitaryPiece::Pawn))
+            | (rook_attacks(square, occupied) & self.rook_like(color))
+            | (bishop_attacks(square, occupied) & self.bishop_like(color));
+        attackers & self.by_color(color)
+    }
+
+    /// All the squares attacked by at least one piece of the given color.
+    pub fn attacked_squares(&self, color: Color) -> Bitboard {
+        self.by_color(color)
+            .fold(Bitboard::EMPTY, |attacked, square| {
+                attacked | self.attacks_from(square)
+            })
+    }
+}
+
+impl BoardRepr {
+    /// The squares attacked by the piece on the given square, see [`Bitboards::attacks_from`].
+    pub fn attacks_from(&self, square: Square) -> Bitboard {
+        self.bitboards().attacks_from(square)
+    }
+
+    /// The squares of the pieces of the given color that attack the square.
+    pub fn attackers_of(&self, square: Square, color: Color) -> Bitboard {
+        self.bitboards().attackers_of(square, color)
+    }
+
+    /// All the squares attacked by at least one piece of the given color.
+    pub fn attacked_squares(&self, color: Color) -> Bitboard {
+        self.bitboards().attacked_squares(color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combination_attacks() {
+        let board = BoardRepr::from_fen("4k3/8/8/8/3(NP)4/8/8/4K3 w - - 0 1").unwrap();
+
+        // The Knawn attacks like a knight and like a pawn, but not straight forward
+        let expected: Bitboard = [
+            Square::C6,
+            Square::E6,
+            Square::B5,
+            Square::F5,
+            Square::B3,
+            Square::F3,
+            Square::C2,
+            Square::E2,
+            Square::C5,
+            Square::E5,
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(board.attacks_from(Square::D4), expected);
+        assert!(!board.attacks_from(Square::D4).contains(Square::D5));
+        assert_eq!(board.attacks_from(Square::D5), Bitboard::EMPTY);
+    }
+
+    #[test]
+    fn test_attackers_of() {
+        let board = BoardRepr::from_fen("3k4/8/2(bp)5/8/4(RN)3/8/4(BP)3/r3K3 w - - 0 1").unwrap();
+
+        // The black bishop-pawn attacks d5 both ways
+        assert_eq!(
+            board.attackers_of(Square::D5, Color::Black),
+            Bitboard::from_square(Square::C6)
+        );
+        assert_eq!(
+            board.attackers_of(Square::D5, Color::White),
+            Bitboard::EMPTY
+        );
+        assert_eq!(
+            board.attackers_of(Square::F3, Color::White),
+            Bitboard::from_square(Square::E2)
+        );
+
+        // The rook on a1 is blocked by the king, so it only attacks up to e1
+        let attacked = board.attacked_squares(Color::Black);
+        assert!(attacked.contains(Square::E1));
+        assert!(!attacked.contains(Square::F1));
+
+        // Every attacked square has an attacker, and the other way round
+        for square in Square::ALL {
+            assert_eq!(
+                attacked.contains(square),
+                board.attackers_of(square, Color::Black).any(),
+                "{square}"
+            );
+        }
+    }
+}
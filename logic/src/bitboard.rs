@@ -0,0 +1,487 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not},
+};
+
+use crate::{
+    board_repr::BoardRepr,
+    pieces::{Color, ColorPiece, CombinationPiece, Piece, UnitaryPiece},
+    square::{File, Rank, Square},
+};
+
+/// A set of squares, stored as one bit per square.
+/// Bit 0 is A1, bit 7 is H1 and bit 63 is H8.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Bitboard(pub u64);
+
+impl Bitboard {
+    pub const EMPTY: Bitboard = Bitboard(0);
+    pub const FULL: Bitboard = Bitboard(!0);
+
+    pub const LIGHT_SQUARES: Bitboard = Bitboard(0x55aa_55aa_55aa_55aa);
+    pub const DARK_SQUARES: Bitboard = Bitboard(0xaa55_aa55_aa55_aa55);
+
+    pub const fn from_square(square: Square) -> Bitboard {
+        Bitboard(1 << square as u32)
+    }
+
+    pub const fn from_file(file: File) -> Bitboard {
+        Bitboard(0x0101_0101_0101_0101 << file as u32)
+    }
+
+    pub const fn from_rank(rank: Rank) -> Bitboard {
+        Bitboard(0xff << (8 * rank as u32))
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn any(self) -> bool {
+        self.0 != 0
+    }
+
+    pub const fn count(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    pub const fn contains(self, square: Square) -> bool {
+        self.0 & (1 << square as u32) != 0
+    }
+
+    pub fn add(&mut self, square: Square) {
+        self.0 |= 1 << square as u32;
+    }
+
+    pub fn remove(&mut self, square: Square) {
+        self.0 &= !(1 << square as u32);
+    }
+
+    pub fn set(&mut self, square: Square, value: bool) {
+        if value {
+            self.add(square);
+        } else {
+            self.remove(square);
+        }
+    }
+
+    /// The lowest square in the set, if any.
+    pub fn first(self) -> Option<Square> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(Square::new(self.0.trailing_zeros()))
+        }
+    }
+
+    /// Returns the only square in the set,
+    /// or `None` if the set is empty or has more than one square.
+    pub fn single_square(self) -> Option<Square> {
+        if self.count() == 1 {
+            self.first()
+        } else {
+            None
+        }
+    }
+}
+
+impl From<Square> for Bitboard {
+    fn from(square: Square) -> Self {
+        Bitboard::from_square(square)
+    }
+}
+
+impl FromIterator<Square> for Bitboard {
+    fn from_iter<T: IntoIterator<Item = Square>>(iter: T) -> Self {
+        let mut bb = Bitboard::EMPTY;
+        for square in iter {
+            bb.add(square);
+        }
+        bb
+    }
+}
+
+impl Iterator for Bitboard {
+    type Item = Square;
+
+    fn next(&mut self) -> Option<Square> {
+        let square = self.first()?;
+        // Clear the lowest set bit
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = self.count() as usize;
+        (count, Some(count))
+    }
+}
+
+impl Debug for Bitboard {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // Print the board from the perspective of the white player,
+        // with the eighth rank at the top
+        writeln!(f, "Bitboard({:#018x})", self.0)?;
+        for rank in Rank::ALL.into_iter().rev() {
+            for file in File::ALL {
+                let c = if self.contains(Square::from_coords(file, rank)) {
+                    '1'
+                } else {
+                    '.'
+                };
+                write!(f, "{c}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+macro_rules! bitboard_binop_impl {
+    ($trait:ident, $method:ident, $assign_trait:ident, $assign_method:ident, $op:tt) => {
+        impl $trait for Bitboard {
+            type Output = Bitboard;
+
+            #[inline]
+            fn $method(self, other: Bitboard) -> Bitboard {
+                Bitboard(self.0 $op other.0)
+            }
+        }
+
+        impl $assign_trait for Bitboard {
+            #[inline]
+            fn $assign_method(&mut self, other: Bitboard) {
+                self.0 = self.0 $op other.0;
+            }
+        }
+    };
+}
+
+bitboard_binop_impl!(BitAnd, bitand, BitAndAssign, bitand_assign, &);
+bitboard_binop_impl!(BitOr, bitor, BitOrAssign, bitor_assign, |);
+bitboard_binop_impl!(BitXor, bitxor, BitXorAssign, bitxor_assign, ^);
+
+impl Not for Bitboard {
+    type Output = Bitboard;
+
+    #[inline]
+    fn not(self) -> Bitboard {
+        Bitboard(!self.0)
+    }
+}
+
+const fn step_attacks(deltas: &[(i32, i32)]) -> [Bitboard; 64] {
+    let mut table = [Bitboard::EMPTY; 64];
+    let mut square = 0;
+    while square < 64 {
+        let file = (square % 8) as i32;
+        let rank = (square / 8) as i32;
+        let mut bits = 0u64;
+        let mut i = 0;
+        while i < deltas.len() {
+            let (df, dr) = deltas[i];
+            let (f, r) = (file + df, rank + dr);
+            if f >= 0 && f < 8 && r >= 0 && r < 8 {
+                bits |= 1 << (r * 8 + f);
+            }
+            i += 1;
+        }
+        table[square] = Bitboard(bits);
+        square += 1;
+    }
+    table
+}
+
+const KNIGHT_DELTAS: [(i32, i32); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+
+const KING_DELTAS: [(i32, i32); 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
+
+const ROOK_DIRECTIONS: [(i32, i32); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+const BISHOP_DIRECTIONS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+
+static KNIGHT_ATTACKS: [Bitboard; 64] = step_attacks(&KNIGHT_DELTAS);
+static KING_ATTACKS: [Bitboard; 64] = step_attacks(&KING_DELTAS);
+static WHITE_PAWN_ATTACKS: [Bitboard; 64] = step_attacks(&[(-1, 1), (1, 1)]);
+static BLACK_PAWN_ATTACKS: [Bitboard; 64] = step_attacks(&[(-1, -1), (1, -1)]);
+
+pub fn knight_attacks(square: Square) -> Bitboard {
+    KNIGHT_ATTACKS[square as usize]
+}
+
+pub fn king_attacks(square: Square) -> Bitboard {
+    KING_ATTACKS[square as usize]
+}
+
+/// The squares that a pawn of the given color attacks diagonally from the given square.
+pub fn pawn_attacks(color: Color, square: Square) -> Bitboard {
+    match color {
+        Color::White => WHITE_PAWN_ATTACKS[square as usize],
+        Color::Black => BLACK_PAWN_ATTACKS[square as usize],
+    }
+}
+
+fn slider_attacks(square: Square, occupied: Bitboard, directions: &[(i32, i32)]) -> Bitboard {
+    let mut attacks = Bitboard::EMPTY;
+    for &(df, dr) in directions {
+        let mut current = square;
+        // Walk in this direction until the edge of the board,
+        // or until (and including) the first occupied square
+        while let Some(next) = current
+            .file()
+            .offset(df)
+            .zip(current.rank().offset(dr))
+            .map(|(file, rank)| Square::from_coords(file, rank))
+        {
+            attacks.add(next);
+            if occupied.contains(next) {
+                break;
+            }
+            current = next;
+        }
+    }
+    attacks
+}
+
+pub fn rook_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    slider_attacks(square, occupied, &ROOK_DIRECTIONS)
+}
+
+pub fn bishop_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    slider_attacks(square, occupied, &BISHOP_DIRECTIONS)
+}
+
+pub fn queen_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    rook_attacks(square, occupied) | bishop_attacks(square, occupied)
+}
+
+/// The squares strictly between two squares on the same rank, file or diagonal,
+/// or no squares if they are not on a line.
+pub fn between(a: Square, b: Square) -> Bitboard {
+    let (files, ranks) = (a.file().distance(b.file()), a.rank().distance(b.rank()));
+    let slider_attacks = if files == 0 || ranks == 0 {
+        rook_attacks
+    } else if files == ranks {
+        bishop_attacks
+    } else {
+        return Bitboard::EMPTY;
+    };
+    slider_attacks(a, Bitboard::from_square(b)) & slider_attacks(b, Bitboard::from_square(a))
+}
+
+/// The position of all the pieces on the board, stored as a set of bitboards.
+///
+/// Every square is described by its color, by the set of unitary components it contains,
+/// and by whether it is a combination piece.
+/// A combination of two different pieces (like a Knawn) has two components set,
+/// while a combination of two identical pieces (like a double queen) has only one component set,
+/// and is told apart from the unitary piece by the `combined` mask.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Bitboards {
+    by_color: [Bitboard; 2],
+    by_component: [Bitboard; 6],
+    combined: Bitboard,
+}
+
+impl Bitboards {
+    pub const fn empty() -> Self {
+        Self {
+            by_color: [Bitboard::EMPTY; 2],
+            by_component: [Bitboard::EMPTY; 6],
+            combined: Bitboard::EMPTY,
+        }
+    }
+
+    /// All the squares that have a piece on them.
+    pub fn occupied(&self) -> Bitboard {
+        self.by_color[0] | self.by_color[1]
+    }
+
+    /// All the squares that have a piece of the given color on them.
+    pub fn by_color(&self, color: Color) -> Bitboard {
+        self.by_color[color as usize]
+    }
+
+    /// All the squares whose piece contains the given unitary piece,
+    /// either on its own or as one of the halves of a combination.
+    pub fn by_component(&self, piece: UnitaryPiece) -> Bitboard {
+        self.by_component[piece as usize]
+    }
+
+    /// All the squares that have a combination piece on them.
+    pub fn combined(&self) -> Bitboard {
+        self.combined
+    }
+
+    /// All the squares that have a unitary piece on them.
+    pub fn unitary(&self) -> Bitboard {
+        self.occupied() & !self.combined
+    }
+
+    /// All the squares with pieces of the given color that move like a rook in at least one of their halves.
+    pub fn rook_like(&self, color: Color) -> Bitboard {
+        (self.by_component(UnitaryPiece::Rook) | self.by_component(UnitaryPiece::Queen))
+            & self.by_color(color)
+    }
+
+    /// All the squares with pieces of the given color that move like a bishop in at least one of their halves.
+    pub fn bishop_like(&self, color: Color) -> Bitboard {
+        (self.by_component(UnitaryPiece::Bishop) | self.by_component(UnitaryPiece::Queen))
+            & self.by_color(color)
+    }
+
+    pub fn piece_at(&self, square: Square) -> Option<ColorPiece> {
+        let color = if self.by_color[Color::White as usize].contains(square) {
+            Color::White
+        } else if self.by_color[Color::Black as usize].contains(square) {
+            Color::Black
+        } else {
+            return None;
+        };
+
+        let mut components = UnitaryPiece::ALL
+            .into_iter()
+            .filter(|p| self.by_component(*p).contains(square));
+        let first = components.next()?;
+        let piece = if self.combined.contains(square) {
+            // If only one component is set, the combination is made of two identical halves
+            let second = components.next().unwrap_or(first);
+            Piece::Combination(CombinationPiece::new(first, second)?)
+        } else {
+            Piece::Unitary(first)
+        };
+
+        Some(match color {
+            Color::White => ColorPiece::White(piece),
+            Color::Black => ColorPiece::Black(piece),
+        })
+    }
+
+    pub fn set(&mut self, square: Square, piece: Option<ColorPiece>) {
+        for bb in self.by_color.iter_mut().chain(self.by_component.iter_mut()) {
+            bb.remove(square);
+        }
+        self.combined.remove(square);
+
+        if let Some(piece) = piece {
+            self.by_color[piece.color() as usize].add(square);
+            piece
+                .piece()
+                .for_components(|component| self.by_component[component as usize].add(square));
+            self.combined.set(square, piece.piece().is_combination());
+        }
+    }
+
+    pub fn to_pieces(&self) -> [Option<ColorPiece>; 64] {
+        let mut pieces = [None; 64];
+        for square in self.occupied() {
+            pieces[square as usize] = self.piece_at(square);
+        }
+        pieces
+    }
+}
+
+impl From<&[Option<ColorPiece>; 64]> for Bitboards {
+    fn from(pieces: &[Option<ColorPiece>; 64]) -> Self {
+        let mut bitboards = Bitboards::empty();
+        for (square, piece) in Square::ALL.into_iter().zip(pieces) {
+            if piece.is_some() {
+                bitboards.set(square, *piece);
+            }
+        }
+        bitboards
+    }
+}
+
+impl From<&BoardRepr> for Bitboards {
+    fn from(board: &BoardRepr) -> Self {
+        *board.bitboards()
+    }
+}
+
+impl Debug for Bitboards {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Bitboards")
+            .field("white", &self.by_color[Color::White as usize])
+            .field("black", &self.by_color[Color::Black as usize])
+            .field("combined", &self.combined)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitboards_round_trip() {
+        let mut pieces = BoardRepr::default().pieces();
+        pieces[Square::E4 as usize] = Some(
+            CombinationPiece::new(UnitaryPiece::Knight, UnitaryPiece::Pawn)
+                .unwrap()
+                .white(),
+        );
+        pieces[Square::D5 as usize] = Some(
+            CombinationPiece::new(UnitaryPiece::Queen, UnitaryPiece::Queen)
+                .unwrap()
+                .black(),
+        );
+
+        let bitboards = Bitboards::from(&pieces);
+        assert_eq!(bitboards.to_pieces(), pieces);
+        assert_eq!(bitboards.occupied().count(), 34);
+        assert!(bitboards
+            .by_component(UnitaryPiece::Pawn)
+            .contains(Square::E4));
+        assert!(bitboards
+            .by_component(UnitaryPiece::Queen)
+            .contains(Square::D5));
+        assert!(bitboards.combined().contains(Square::D5));
+    }
+
+    #[test]
+    fn test_attacks() {
+        assert_eq!(knight_attacks(Square::A1).count(), 2);
+        assert_eq!(king_attacks(Square::E4).count(), 8);
+        assert_eq!(
+            pawn_attacks(Color::White, Square::E4),
+            Bitboard::from(Square::D5) | Bitboard::from(Square::F5)
+        );
+
+        let occupied = Bitboard::from(Square::E6) | Bitboard::from(Square::B4);
+        let attacks = rook_attacks(Square::E4, occupied);
+        assert!(attacks.contains(Square::E6));
+        assert!(!attacks.contains(Square::E7));
+        assert!(attacks.contains(Square::B4));
+        assert!(!attacks.contains(Square::A4));
+        assert_eq!(attacks.count(), 2 + 3 + 3 + 3);
+    }
+
+    #[test]
+    fn test_between() {
+        assert_eq!(
+            between(Square::E1, Square::E4),
+            Bitboard::from(Square::E2) | Bitboard::from(Square::E3)
+        );
+        assert_eq!(between(Square::H8, Square::F6), Bitboard::from(Square::G7));
+        assert_eq!(between(Square::E1, Square::E2), Bitboard::EMPTY);
+        assert_eq!(between(Square::E1, Square::F3), Bitboard::EMPTY);
+    }
+}
@@ -1,27 +1,46 @@
-use std::ops::{Index, IndexMut};
+use std::{fmt, ops::Index};
 
 use crate::{
+    bitboard::{Bitboard, Bitboards},
     pieces::{
-        movement::{get_moves_from_square, Move, MovesList},
+        movement::{
+            castling_side, drop_targets, en_passant_target, get_moves_from_square, move_rule,
+            CastlingFiles, CastlingSide, Move, MovesList,
+        },
         Color, ColorPiece, CombinationPiece, Piece, UnitaryPiece,
     },
+    pocket::Pockets,
+    rules::{Promotion, RuleSet},
     square::{File, Rank, Square},
+    zobrist,
 };
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// The state of a game: the pieces on the board and everything else needed to carry on playing.
+///
+/// Two boards are equal when their states are, whatever their cached Zobrist keys.
+// The derived (de)serialization is only reached through the `Serialize` and `Deserialize`
+// implementations below, which recompute the Zobrist key rather than trusting one from outside.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(remote = "Self")
+)]
 pub struct BoardRepr {
+    /// The pieces on the board, which are only stored as bitboards,
+    /// see [`BoardRepr::bitboards`] and [`BoardRepr::pieces`].
     #[cfg_attr(
         feature = "serde",
         serde(
+            rename = "pieces",
             serialize_with = "serialize_pieces",
             deserialize_with = "deserialize_pieces"
         )
     )]
-    pub pieces: [Option<ColorPiece>; 64],
+    bitboards: Bitboards,
     /// If the last move made an en passant capture possible, the square on which the pawn
     /// to be captured is located is stored here.
     pub en_passant_square: Option<Square>,
@@ -33,27 +52,223 @@ pub struct BoardRepr {
     /// The order is: white king side, white queen side, black king side, black queen side
     pub castling_rights: [bool; 4],
 
+    /// The files that the king and the castling rooks of each side started on, indexed by color.
+    /// They are the usual ones except in Chess960, see [`crate::chess960`].
+    ///
+    /// Only the files needed by the remaining castling rights are kept:
+    /// the others go back to the usual ones as the rights are lost,
+    /// so that positions that can castle the same way are equal.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub castling_files: [CastlingFiles; 2],
+
     /// The move that was just played by the opposite player.
     /// None if there is no previous move.
     pub previous_move: Option<Move>,
+
+    /// The number of halfmoves since the last capture or pawn move
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub halfmove_clock: u32,
+
+    /// The number of the current full move, starting at 1 and incremented after Black's move
+    #[cfg_attr(feature = "serde", serde(default = "first_move"))]
+    pub fullmove_number: u32,
+
+    /// The rules that moves are generated and played by
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rules: RuleSet,
+
+    /// The pieces each side holds in hand, if pieces can be dropped, see [`crate::pocket`].
+    /// Without pockets, captured pieces simply leave the game.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub pockets: Option<Pockets>,
+
+    /// How many times each side has given check, indexed by color, if checks are counted,
+    /// as in [`crate::variant::ThreeCheck`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub checks_given: Option<[u8; 2]>,
+
+    /// The Zobrist key of the position, see [`crate::zobrist`]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    zobrist_key: u64,
+}
+
+impl PartialEq for BoardRepr {
+    fn eq(&self, other: &Self) -> bool {
+        // The key is left out: it follows from the rest, unless the state was changed directly
+        // and the key not refreshed yet
+        let BoardRepr {
+            bitboards,
+            en_passant_square,
+            side_to_move,
+            castling_rights,
+            castling_files,
+            previous_move,
+            halfmove_clock,
+            fullmove_number,
+            rules,
+            pockets,
+            checks_given,
+            zobrist_key: _,
+        } = self;
+        *bitboards == other.bitboards
+            && *en_passant_square == other.en_passant_square
+            && *side_to_move == other.side_to_move
+            && *castling_rights == other.castling_rights
+            && *castling_files == other.castling_files
+            && *previous_move == other.previous_move
+            && *halfmove_clock == other.halfmove_clock
+            && *fullmove_number == other.fullmove_number
+            && *rules == other.rules
+            && *pockets == other.pockets
+            && *checks_given == other.checks_given
+    }
 }
 
+impl Eq for BoardRepr {}
+
 #[cfg(feature = "serde")]
-fn serialize_pieces<S>(pieces: &[Option<ColorPiece>; 64], serializer: S) -> Result<S::Ok, S::Error>
+impl Serialize for BoardRepr {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        BoardRepr::serialize(self, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for BoardRepr {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let mut board = BoardRepr::deserialize(deserializer)?;
+        board.refresh_zobrist_key();
+        Ok(board)
+    }
+}
+
+/// The reason a move can't be played.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum IllegalMoveError {
+    /// There is no piece on the source square.
+    EmptySquare { square: Square },
+    /// The piece on the source square belongs to the side that isn't moving.
+    NotYourPiece { square: Square },
+    /// A half was chosen to move, but the piece is unitary.
+    NoSuchHalf { square: Square },
+    /// The move would merge a piece with the king, which can't be combined.
+    MergeWithKing { from: Square, to: Square },
+    /// The move would merge a piece with a friendly piece,
+    /// but only two unitary pieces can be merged, and a piece that splits off can't merge.
+    InvalidMerge { from: Square, to: Square },
+    /// The piece can't move to the destination square.
+    UnreachableSquare { from: Square, to: Square },
+    /// The move would leave the king of the side that moved in check.
+    LeavesKingInCheck { from: Square, to: Square },
+    /// The move is legal, but the variant being played doesn't allow it.
+    ForbiddenByVariant { from: Square, to: Square },
+    /// The side to move has no such piece in hand to drop, or there are no pockets at all.
+    NotInPocket { piece: UnitaryPiece, to: Square },
+    /// The piece can't be dropped there: the square holds an enemy piece, a combination or the king,
+    /// or a pawn would be dropped on the first or last rank.
+    InvalidDrop { piece: UnitaryPiece, to: Square },
+}
+
+impl fmt::Display for IllegalMoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IllegalMoveError::EmptySquare { square } => write!(f, "there is no piece on {square}"),
+            IllegalMoveError::NotYourPiece { square } => {
+                write!(f, "the piece on {square} belongs to the other side")
+            }
+            IllegalMoveError::NoSuchHalf { square } => {
+                write!(
+                    f,
+                    "the piece on {square} is not a combination, so it has no halves"
+                )
+            }
+            IllegalMoveError::MergeWithKing { from, to } => {
+                write!(
+                    f,
+                    "moving from {from} to {to} would merge a piece with the king"
+                )
+            }
+            IllegalMoveError::InvalidMerge { from, to } => {
+                write!(f, "the pieces on {from} and {to} can't be merged")
+            }
+            IllegalMoveError::UnreachableSquare { from, to } => {
+                write!(f, "the piece on {from} can't move to {to}")
+            }
+            IllegalMoveError::LeavesKingInCheck { from, to } => {
+                write!(
+                    f,
+                    "moving from {from} to {to} would leave the king in check"
+                )
+            }
+            IllegalMoveError::ForbiddenByVariant { from, to } => {
+                write!(f, "the variant doesn't allow moving from {from} to {to}")
+            }
+            IllegalMoveError::NotInPocket { piece, to } => {
+                write!(f, "there is no {piece} in hand to drop on {to}")
+            }
+            IllegalMoveError::InvalidDrop { piece, to } => {
+                write!(f, "a {piece} can't be dropped on {to}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for IllegalMoveError {}
+
+/// Everything that [`BoardRepr::make_move`] changes and can't be recomputed,
+/// so that [`BoardRepr::unmake_move`] can restore the board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UndoInfo {
+    pub move_: Move,
+    /// The piece that was on the source square; for a split, the whole combination;
+    /// for a drop, the dropped piece.
+    pub moved: ColorPiece,
+    /// The piece that was on the destination square:
+    /// either a captured enemy, or a friendly piece that was merged with.
+    pub destination: Option<ColorPiece>,
+    /// The captured piece and its square, which is not the destination for en passant.
+    pub captured: Option<(Square, ColorPiece)>,
+    /// If the move was castling, the side the king castled to.
+    pub castling: Option<CastlingSide>,
+    pub en_passant_square: Option<Square>,
+    pub castling_rights: [bool; 4],
+    pub castling_files: [CastlingFiles; 2],
+    pub previous_move: Option<Move>,
+    pub halfmove_clock: u32,
+    pub pockets: Option<Pockets>,
+    pub checks_given: Option<[u8; 2]>,
+    pub zobrist_key: u64,
+}
+
+/// The fullmove number of boards serialized without one.
+#[cfg(feature = "serde")]
+fn first_move() -> u32 {
+    1
+}
+
+#[cfg(feature = "serde")]
+fn serialize_pieces<S>(bitboards: &Bitboards, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: serde::Serializer,
 {
     use serde::ser::SerializeSeq as _;
 
     let mut seq = serializer.serialize_seq(Some(64))?;
-    for piece in pieces {
+    for piece in &bitboards.to_pieces() {
         seq.serialize_element(piece)?;
     }
     seq.end()
 }
 
 #[cfg(feature = "serde")]
-fn deserialize_pieces<'de, D>(deserializer: D) -> Result<[Option<ColorPiece>; 64], D::Error>
+fn deserialize_pieces<'de, D>(deserializer: D) -> Result<Bitboards, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
@@ -69,20 +284,36 @@ where
     for (i, piece) in pieces_out.iter_mut().enumerate() {
         *piece = pieces[i];
     }
-    Ok(pieces_out)
+    Ok(Bitboards::from(&pieces_out))
 }
 
-impl Index<Square> for BoardRepr {
-    type Output = Option<ColorPiece>;
+/// Everything a square can hold: nothing, or any piece of either color,
+/// laid out by [`square_contents_index`].
+/// The pieces are only stored in the bitboards, so indexing a board returns a reference into this table.
+static SQUARE_CONTENTS: [Option<ColorPiece>; 1 + 2 * Piece::COUNT] = {
+    let mut contents = [None; 1 + 2 * Piece::COUNT];
+    let mut i = 0;
+    while i < Piece::COUNT {
+        contents[1 + i] = Some(ColorPiece::White(Piece::ALL[i]));
+        contents[1 + Piece::COUNT + i] = Some(ColorPiece::Black(Piece::ALL[i]));
+        i += 1;
+    }
+    contents
+};
 
-    fn index(&self, index: Square) -> &Self::Output {
-        &self.pieces[index as usize]
+fn square_contents_index(piece: Option<ColorPiece>) -> usize {
+    match piece {
+        None => 0,
+        Some(piece) => 1 + piece.color() as usize * Piece::COUNT + piece.piece().index(),
     }
 }
 
-impl IndexMut<Square> for BoardRepr {
-    fn index_mut(&mut self, index: Square) -> &mut Self::Output {
-        &mut self.pieces[index as usize]
+/// Reads the piece on a square. Pieces are placed with [`BoardRepr::set_piece`].
+impl Index<Square> for BoardRepr {
+    type Output = Option<ColorPiece>;
+
+    fn index(&self, index: Square) -> &Self::Output {
+        &SQUARE_CONTENTS[square_contents_index(self.bitboards.piece_at(index))]
     }
 }
 
@@ -90,27 +321,76 @@ impl Index<usize> for BoardRepr {
     type Output = Option<ColorPiece>;
 
     fn index(&self, index: usize) -> &Self::Output {
-        &self.pieces[index]
-    }
-}
-
-impl IndexMut<usize> for BoardRepr {
-    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        &mut self.pieces[index]
+        &self[Square::ALL[index]]
     }
 }
 
 impl BoardRepr {
     pub const fn empty() -> Self {
         Self {
-            pieces: [None; 64],
+            bitboards: Bitboards::empty(),
             en_passant_square: None,
             side_to_move: Color::White,
             castling_rights: [false; 4],
+            castling_files: [CastlingFiles::STANDARD; 2],
             previous_move: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            rules: RuleSet::THIS_PROJECT,
+            pockets: None,
+            checks_given: None,
+            // Nothing on the board, and White to move
+            zobrist_key: 0,
         }
     }
 
+    /// The pieces on this board as bitboards, which is how they are stored
+    /// and what the move generators work with.
+    pub fn bitboards(&self) -> &Bitboards {
+        &self.bitboards
+    }
+
+    /// The piece on every square, from a1 to h8.
+    pub fn pieces(&self) -> [Option<ColorPiece>; 64] {
+        self.bitboards.to_pieces()
+    }
+
+    /// The Zobrist key of this position, which is kept up to date as moves are played.
+    pub fn zobrist_key(&self) -> u64 {
+        self.zobrist_key
+    }
+
+    /// Recomputes the Zobrist key.
+    /// This must be called after changing the state of the board directly,
+    /// such as the side to move or the castling rights, rather than by playing moves.
+    /// Pieces placed with [`BoardRepr::set_piece`] keep the key up to date.
+    pub fn refresh_zobrist_key(&mut self) {
+        self.zobrist_key = self.compute_zobrist_key();
+    }
+
+    /// Replaces the pockets, updating the Zobrist key.
+    fn set_pockets(&mut self, pockets: Option<Pockets>) {
+        self.zobrist_key ^= zobrist::pockets_key(self.pockets) ^ zobrist::pockets_key(pockets);
+        self.pockets = pockets;
+    }
+
+    /// Replaces every piece on the board, updating the Zobrist key.
+    pub fn set_pieces(&mut self, pieces: &[Option<ColorPiece>; 64]) {
+        self.bitboards = Bitboards::from(pieces);
+        self.refresh_zobrist_key();
+    }
+
+    /// Puts a piece on a square (or empties it), updating the Zobrist key.
+    pub fn set_piece(&mut self, square: Square, piece: Option<ColorPiece>) {
+        if let Some(old) = self[square] {
+            self.zobrist_key ^= zobrist::piece_key(old, square);
+        }
+        if let Some(new) = piece {
+            self.zobrist_key ^= zobrist::piece_key(new, square);
+        }
+        self.bitboards.set(square, piece);
+    }
+
     pub fn iter_pieces(&self) -> BoardPieceIter {
         BoardPieceIter {
             board: self,
@@ -119,402 +399,518 @@ impl BoardRepr {
     }
 
     pub fn is_safe_move(&self, move_: Move, side: Color) -> bool {
-        // log::debug!("Testing move: {move_:?}");
-        // Make a temporary copy of the board
+        self.check_safe_move(move_, side).is_ok()
+    }
+
+    /// Checks that the move can be made by the side to move,
+    /// and that it doesn't leave the king of the given side in check.
+    pub fn check_safe_move(&self, move_: Move, side: Color) -> Result<(), IllegalMoveError> {
+        self.check_pseudo_legal(move_)?;
+
+        // Make the move on a temporary copy of the board,
+        // and check if the king of the specified side is in check after the move
         let mut board = *self;
-        if board.play(move_).is_err() {
-            log::debug!("Failed to play {move_:?} on temp board");
-            return false;
+        board.make_move(move_);
+        if board.king_in_check(side) {
+            return Err(IllegalMoveError::LeavesKingInCheck {
+                from: move_.from,
+                to: move_.to,
+            });
         }
-
-        // Check if the king of the specified side is in check after the move
-        // if not, the move is safe
-        !board.king_in_check(side)
+        Ok(())
     }
 
+    /// The square of the king of the given side.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the side has no king, see [`BoardRepr::try_king_square`].
     pub fn king_square(&self, side: Color) -> Square {
-        for (square, piece) in self.iter_pieces() {
-            if piece.color() == side && piece.piece().contains(UnitaryPiece::King) {
-                return square;
-            }
-        }
-        panic!("There's no king of the side {side:?} on the board");
+        self.try_king_square(side)
+            .unwrap_or_else(|| panic!("There's no king of the side {side:?} on the board"))
     }
 
+    /// The square of the king of the given side, or `None` if it has no king,
+    /// as in a puzzle diagram or a position that is still being set up.
+    pub fn try_king_square(&self, side: Color) -> Option<Square> {
+        let bitboards = self.bitboards();
+        (bitboards.by_component(UnitaryPiece::King) & bitboards.by_color(side)).first()
+    }
+
+    /// Checks whether the king of the given side is attacked.
+    /// A side without a king is never in check.
     pub fn king_in_check(&self, side: Color) -> bool {
-        let side_to_move = side.opposite();
-        // For every enemy piece,
-        // check if it can move to the king's square
-        let king_square = self.king_square(side);
-        for (square, piece) in self.iter_pieces() {
-            if piece.color() != side {
-                let moves =
-                    get_moves_from_square(MovesList::new_const(), self, side_to_move, square, None);
-                let mut checking_moves = moves.iter().filter(|m| m.to == king_square);
-                let first_checking_move = checking_moves.next();
-                if first_checking_move.is_some() {
-                    log::warn!("Found a checking move: {first_checking_move:?}, therefore {side:?} is in check");
-                    return true;
-                }
-            }
-        }
-        false
-    }
-
-    pub fn play(&mut self, move_: crate::pieces::movement::Move) -> Result<(), ()> {
-        fn play_inner(this: &mut BoardRepr, move_: Move) -> Result<(), ()> {
-            let Move {
-                from,
-                to,
-                which_half,
-            } = move_;
-            // If the source square is empty, there's nothing to do
-            let src_piece = this[move_.from].ok_or(())?;
-            let dst_piece = this[move_.to];
-
-            // Generate the legal moves from the source square
-            let moves = get_moves_from_square(
-                MovesList::new_const(),
-                this,
-                this.side_to_move,
-                move_.from,
-                move_.which_half,
-            );
+        self.try_king_square(side).is_some_and(|king_square| {
+            self.bitboards()
+                .attackers_of(king_square, side.opposite())
+                .any()
+        })
+    }
 
-            // Check if the move is legal
-            if !moves.contains(&move_) {
-                return Err(());
-            }
+    /// Plays a move, if it is legal for the side to move.
+    pub fn play(&mut self, move_: Move) -> Result<(), IllegalMoveError> {
+        self.validate_move(move_)?;
+        self.make_move(move_);
+        Ok(())
+    }
 
-            let mut did_set_en_passant = false;
+    /// Checks that the move is legal for the side to move, and if not, explains why.
+    pub fn validate_move(&self, move_: Move) -> Result<(), IllegalMoveError> {
+        self.check_safe_move(move_, self.side_to_move)
+    }
 
-            // If the piece that's moving contains a pawn,
-            // if it moved two squares vertically starting at the pawn source rank
-            // then set the en passant square
-            if src_piece.piece().contains(UnitaryPiece::Pawn) {
-                let required_start_rank = match src_piece.color() {
-                    Color::White => Rank::Second,
-                    Color::Black => Rank::Seventh,
-                };
+    /// Checks that the move is one that the piece on the source square can make
+    /// for the side to move, without checking whether it leaves the king in check.
+    fn check_pseudo_legal(&self, move_: Move) -> Result<(), IllegalMoveError> {
+        let Move {
+            from,
+            to,
+            which_half,
+            drop,
+        } = move_;
+        if let Some(piece) = drop {
+            return self.check_drop(piece, move_);
+        }
+        let piece = self[from].ok_or(IllegalMoveError::EmptySquare { square: from })?;
+        if piece.color() != self.side_to_move {
+            return Err(IllegalMoveError::NotYourPiece { square: from });
+        }
+        if which_half.is_some() && piece.is_unitary() {
+            return Err(IllegalMoveError::NoSuchHalf { square: from });
+        }
 
-                if from.rank() == required_start_rank
-                    && to.rank().distance(from.rank()) == 2
-                    && to.file() == from.file()
+        let moves = get_moves_from_square(
+            MovesList::new_const(),
+            self,
+            self.side_to_move,
+            from,
+            which_half,
+        );
+        if moves.contains(&move_) {
+            return Ok(());
+        }
+
+        // Find out why the piece can't go there
+        Err(match self[to] {
+            Some(dst) if dst.color() == piece.color() => {
+                if piece.piece().contains(UnitaryPiece::King)
+                    || dst.piece().contains(UnitaryPiece::King)
                 {
-                    this.en_passant_square = Some(to);
-                    did_set_en_passant = true;
+                    IllegalMoveError::MergeWithKing { from, to }
+                } else {
+                    IllegalMoveError::InvalidMerge { from, to }
                 }
             }
+            _ => IllegalMoveError::UnreachableSquare { from, to },
+        })
+    }
 
-            // If the half to move is unset,
-            // then we're moving the entire piece
-            // The following cases can happen:
-            // - either the destination square is empty
-            // - or the destination square is occupied by an enemy (in which case it is removed)
-            // - or the destination square is occupied by a friendly (in which case both of them must be unitary, and the result is a merge)
-            fn process_unitary_move(
-                this: &mut BoardRepr,
-                from: Square,
-                to: Square,
-                src_piece: ColorPiece,
-                dst_piece: Option<ColorPiece>,
-            ) -> Result<(), ()> {
-                // If the piece that's moving is a king,
-                // then it loses its castling rights
-                // TODO: check that the king doesn't castle through check
-                if src_piece.piece().contains(UnitaryPiece::King) {
-                    match src_piece.color() {
-                        Color::White => {
-                            // If the king has castling rights kingside,
-                            // and is moving from E1 to G1,
-                            // and the rook is on H1,
-                            // then move the king and the rook
-
-                            if this.castling_rights[0]
-                                && from == Square::E1
-                                && to == Square::G1
-                                && this[Square::H1]
-                                    .is_some_and(|v| v.piece().contains(UnitaryPiece::Rook))
-                                && this[Square::F1].is_none()
-                                && this[Square::G1].is_none()
-                            {
-                                this[Square::G1] = this[Square::E1];
-                                this[Square::E1] = None;
-                                this[Square::F1] = this[Square::H1];
-                                this[Square::H1] = None;
-                                this.castling_rights[0] = false;
-                                this.castling_rights[1] = false;
-                                return Ok(());
-                            }
-
-                            // If the king has castling rights queenside,
-                            // and is moving from E1 to C1,
-                            // and the rook is on A1,
-                            // then move the king and the rook
-                            if this.castling_rights[1]
-                                && from == Square::E1
-                                && to == Square::C1
-                                && this[Square::A1]
-                                    .is_some_and(|v| v.piece().contains(UnitaryPiece::Rook))
-                                && this[Square::B1].is_none()
-                                && this[Square::C1].is_none()
-                                && this[Square::D1].is_none()
-                            {
-                                this[Square::C1] = this[Square::E1];
-                                this[Square::E1] = None;
-                                this[Square::D1] = this[Square::A1];
-                                this[Square::A1] = None;
-                                this.castling_rights[0] = false;
-                                this.castling_rights[1] = false;
-                                return Ok(());
-                            }
-
-                            this.castling_rights[0] = false;
-                            this.castling_rights[1] = false;
-                        }
-                        Color::Black => {
-                            // If the king has castling rights kingside,
-                            // and is moving from E8 to G8,
-                            // and the rook is on H8,
-                            // then move the king and the rook
-                            if this.castling_rights[2]
-                                && from == Square::E8
-                                && to == Square::G8
-                                && this[Square::H8]
-                                    .is_some_and(|v| v.piece().contains(UnitaryPiece::Rook))
-                                && this[Square::F8].is_none()
-                                && this[Square::G8].is_none()
-                            {
-                                this[Square::G8] = this[Square::E8];
-                                this[Square::E8] = None;
-                                this[Square::F8] = this[Square::H8];
-                                this[Square::H8] = None;
-                                this.castling_rights[2] = false;
-                                this.castling_rights[3] = false;
-                                return Ok(());
-                            }
-
-                            // If the king has castling rights queenside,
-                            // and is moving from E8 to C8,
-                            // and the rook is on A8,
-                            // then move the king and the rook
-                            if this.castling_rights[3]
-                                && from == Square::E8
-                                && to == Square::C8
-                                && this[Square::A8]
-                                    .is_some_and(|v| v.piece().contains(UnitaryPiece::Rook))
-                                && this[Square::B8].is_none()
-                                && this[Square::C8].is_none()
-                                && this[Square::D8].is_none()
-                            {
-                                this[Square::C8] = this[Square::E8];
-                                this[Square::E8] = None;
-                                this[Square::D8] = this[Square::A8];
-                                this[Square::A8] = None;
-                                this.castling_rights[2] = false;
-                                this.castling_rights[3] = false;
-                                return Ok(());
-                            }
-
-                            this.castling_rights[2] = false;
-                            this.castling_rights[3] = false;
-                        }
-                    }
-                }
+    /// Checks that the side to move has the piece in hand, and can drop it on the destination square.
+    fn check_drop(&self, piece: UnitaryPiece, move_: Move) -> Result<(), IllegalMoveError> {
+        let to = move_.to;
+        if self
+            .pockets
+            .is_none_or(|pockets| pockets.count(self.side_to_move, piece) == 0)
+        {
+            return Err(IllegalMoveError::NotInPocket { piece, to });
+        }
+        if move_.from != to
+            || move_.which_half.is_some()
+            || !drop_targets(self.bitboards(), self.side_to_move, piece).contains(to)
+        {
+            return Err(IllegalMoveError::InvalidDrop { piece, to });
+        }
+        Ok(())
+    }
 
-                // If this piece contains a pawn,
-                // and it is arriving at the final rank,
-                // then replace the destination square with a queen of the color of the pawn
-                if src_piece.piece().contains(UnitaryPiece::Pawn)
-                    && (to.rank() == Rank::Eighth && src_piece.color() == Color::White)
-                    || (to.rank() == Rank::First && src_piece.color() == Color::Black)
-                {
-                    this[from] = None;
-                    this[to] = Some(match src_piece.color() {
-                        Color::White => UnitaryPiece::Queen.white(),
-                        Color::Black => UnitaryPiece::Queen.black(),
-                    });
-                    return Ok(());
-                }
+    /// Plays a move without checking it, and returns what is needed to take it back
+    /// with [`BoardRepr::unmake_move`].
+    ///
+    /// The move must be one that the move generator produced for this position;
+    /// use [`BoardRepr::play`] for moves that come from elsewhere.
+    pub fn make_move(&mut self, move_: Move) -> UndoInfo {
+        let Move {
+            from,
+            to,
+            which_half,
+            drop,
+        } = move_;
+        if let Some(piece) = drop {
+            return self.make_drop(move_, piece);
+        }
+        if let Some(side) = castling_side(self, move_) {
+            return self.make_castle(move_, side);
+        }
 
-                // If the piece that's moving is a rook,
-                // and it's starting from a corner,
-                // then the side that's moving loses its castling rights in that direction
-                if src_piece.piece().contains(UnitaryPiece::Rook) {
-                    match src_piece.color() {
-                        Color::White => {
-                            if from == Square::A1 {
-                                this.castling_rights[1] = false;
-                            }
-                            if from == Square::H1 {
-                                this.castling_rights[0] = false;
-                            }
-                        }
-                        Color::Black => {
-                            if from == Square::A8 {
-                                this.castling_rights[3] = false;
-                            }
-                            if from == Square::H8 {
-                                this.castling_rights[2] = false;
-                            }
-                        }
-                    }
-                }
+        let moved = self[from].expect("make_move called with an empty source square");
+        let color = moved.color();
+        let color_constructor = match color {
+            Color::White => ColorPiece::White,
+            Color::Black => ColorPiece::Black,
+        };
 
-                // If the destination square is empty,
-                // and this piece contains a pawn,
-                // and it is to the left or right of the en passant square,
-                // and it is moving to the same file as the en passant square
-                if let Some(ep_square) = this.en_passant_square {
-                    if from.file().distance(ep_square.file()) == 1
-                        && from.rank() == ep_square.rank()
-                        && to.file() == ep_square.file()
-                    {
-                        this[from] = None;
-                        this[ep_square] = None;
-                        this[to] = Some(src_piece);
-                        this.en_passant_square = None;
-                        return Ok(());
-                    }
-                }
+        let mut undo = UndoInfo {
+            move_,
+            moved,
+            destination: self[to],
+            captured: self[to].filter(|p| p.color() != color).map(|p| (to, p)),
+            castling: None,
+            en_passant_square: self.en_passant_square,
+            castling_rights: self.castling_rights,
+            castling_files: self.castling_files,
+            previous_move: self.previous_move,
+            halfmove_clock: self.halfmove_clock,
+            pockets: self.pockets,
+            checks_given: self.checks_given,
+            zobrist_key: self.zobrist_key,
+        };
 
-                // If the destination square is empty
-                if dst_piece.is_none() {
-                    this[to] = Some(src_piece);
-                    this[from] = None;
-                    return Ok(());
-                }
+        // If a half is moving, the other half stays on the source square
+        let (mover, remaining) = match (which_half, moved.piece()) {
+            (Some(half), Piece::Combination(combo)) => (
+                Piece::Unitary(combo[half]),
+                Some(color_constructor(Piece::Unitary(combo[half.opposite()]))),
+            ),
+            (_, piece) => (piece, None),
+        };
+        let moving_pawn = mover.contains(UnitaryPiece::Pawn);
 
-                if let Some(dst_piece) = dst_piece {
-                    // If the destination square is occupied by an enemy
-                    if dst_piece.color() != src_piece.color() {
-                        this[from] = None;
-                        this[to] = Some(src_piece);
-                        return Ok(());
-                    } else {
-                        // If the destination square is occupied by a friendly unitary piece
-                        if dst_piece.is_unitary() && dst_piece.color() == src_piece.color() {
-                            let (p1, p2) = match (src_piece, dst_piece) {
-                                (
-                                    ColorPiece::White(Piece::Unitary(p1)),
-                                    ColorPiece::White(Piece::Unitary(p2)),
-                                ) => (p1, p2),
-                                (
-                                    ColorPiece::Black(Piece::Unitary(p1)),
-                                    ColorPiece::Black(Piece::Unitary(p2)),
-                                ) => (p1, p2),
-                                _ => {
-                                    log::warn!("Fell through match statement in merging step in process_unitary_move, declaring illegal");
-                                    return Err(());
-                                }
-                            };
-                            // Merge the pieces
-                            let color_constructor = match src_piece.color() {
-                                Color::White => ColorPiece::White,
-                                Color::Black => ColorPiece::Black,
-                            };
-                            let combo = CombinationPiece::new(p1, p2);
-                            if combo.is_none() {
-                                log::warn!("While merging, a combination was invalid, declaring illegal. Tried merging p1: {p1:?}, p2: {p2:?}");
-                                return Err(());
-                            }
-                            this[from] = None;
-                            this[to] = Some(color_constructor(Piece::Combination(combo.unwrap())));
-                            return Ok(());
-                        }
-                    }
-                }
+        // Only a move by the pawn's rule can be a double step or an en passant capture,
+        // so a Knawn jumping like a knight doesn't capture anything en passant
+        let pawn_rule = match mover {
+            Piece::Unitary(UnitaryPiece::Pawn) => true,
+            _ => moving_pawn && move_rule(self, move_) == Some(UnitaryPiece::Pawn),
+        };
 
-                // Some unexpected combination of conditions occurred,
-                // the move is probably illegal
-                log::warn!("Fell through conditions in process_unitary_move, declaring illegal");
-                Err(())
+        // If the piece that's moving is a king, then it loses its castling rights
+        if mover == Piece::Unitary(UnitaryPiece::King) {
+            for side in CastlingSide::ALL {
+                self.castling_rights[side.right_index(color)] = false;
             }
+        }
 
-            if which_half.is_none() {
-                process_unitary_move(this, from, to, src_piece, dst_piece)?;
-            } else {
-                // The half to move is set;
-                // the old square will now contain the remaining half.
-                let src_combo_piece = match src_piece.piece() {
-                    crate::pieces::Piece::Unitary(_) => {
-                        // We already checked for this above,
-                        // so this should never happen
-                        unreachable!()
-                    }
-                    crate::pieces::Piece::Combination(p) => p,
-                };
-                let which_half = which_half.unwrap();
-                let color_constructor = match src_piece.color() {
-                    Color::White => ColorPiece::White,
-                    Color::Black => ColorPiece::Black,
-                };
-
-                let unitary_to_remain = src_combo_piece[which_half.opposite()];
-                let unitary_to_move = src_combo_piece[which_half];
-                let color_to_move = color_constructor(Piece::Unitary(unitary_to_move));
-                this[from] = Some(color_constructor(Piece::Unitary(
-                    src_combo_piece[which_half],
-                )));
-
-                // Now, the source square contains only the half that's moving.
-                // The piece that's staying behind is in temporary memory.
-
-                // Check that the move is legal for this half.
-                let current_move_without_half = Move {
-                    from,
-                    to,
-                    which_half: None,
-                };
-                let moves_for_moving_half = get_moves_from_square(
-                    MovesList::new_const(),
-                    this,
-                    this.side_to_move,
-                    from,
-                    None,
-                );
-
-                if !moves_for_moving_half.contains(&current_move_without_half) {
-                    return Err(());
+        // If a rook leaves its starting square, or is captured there,
+        // then its side loses its castling rights in that direction
+        for side in CastlingSide::ALL {
+            if mover.contains(UnitaryPiece::Rook) && from == side.rook_from(self, color) {
+                self.castling_rights[side.right_index(color)] = false;
+            }
+            if let Some((square, captured)) = undo.captured {
+                if square == side.rook_from(self, captured.color()) {
+                    self.castling_rights[side.right_index(captured.color())] = false;
                 }
+            }
+        }
 
-                process_unitary_move(this, from, to, color_to_move, dst_piece)?;
+        // If a pawn moves to the square behind the pawn that just made a double step,
+        // then it captures that pawn
+        if pawn_rule && en_passant_target(self) == Some(to) {
+            let ep_square = self.en_passant_square.unwrap();
+            undo.captured = self[ep_square].map(|p| (ep_square, p));
+            self.set_piece(ep_square, None);
+        }
 
-                // Finally, the temporary piece is stored back in the source square
-                this[from] = Some(color_constructor(Piece::Unitary(unitary_to_remain)));
+        // With pockets, the captured piece goes to the capturer's hand, split into its components.
+        // A pocket can only be too full to take it in a position that was set up that way,
+        // and then the captured piece leaves the game
+        if let (Some(mut pockets), Some((_, captured))) = (self.pockets, undo.captured) {
+            if pockets.add_captured(color, captured.piece()) {
+                self.set_pockets(Some(pockets));
             }
+        }
+
+        // The moving piece either lands on an empty square, captures an enemy,
+        // or merges with a friendly unitary piece
+        let mut landed = match (mover, undo.destination) {
+            (Piece::Unitary(p1), Some(dst)) if dst.color() == color => merge(p1, dst, move_),
+            _ => mover,
+        };
+
+        // If the moving piece contains a pawn, and it is arriving at the final rank,
+        // then it is promoted
+        let last_rank = match color {
+            Color::White => Rank::Eighth,
+            Color::Black => Rank::First,
+        };
+        if moving_pawn && to.rank() == last_rank {
+            landed = promote(landed, self.rules.promotion);
+        }
+        self.set_piece(from, remaining);
+        self.set_piece(to, Some(color_constructor(landed)));
+
+        // If the pawn's rule moved the piece two squares vertically from the pawn source rank,
+        // then set the en passant square
+        let start_rank = match color {
+            Color::White => Rank::Second,
+            Color::Black => Rank::Seventh,
+        };
+        self.en_passant_square = (self.rules.en_passant
+            && pawn_rule
+            && from.rank() == start_rank
+            && to.rank().distance(from.rank()) == 2
+            && to.file() == from.file())
+        .then_some(to);
+
+        // The halfmove clock is reset by captures (including en passant)
+        // and by moves of pieces containing a pawn
+        if moving_pawn || undo.captured.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        self.pass_turn(&undo);
+        undo
+    }
+
+    /// The part of [`BoardRepr::make_move`] that plays a drop.
+    fn make_drop(&mut self, move_: Move, piece: UnitaryPiece) -> UndoInfo {
+        let color = self.side_to_move;
+        let color_constructor = match color {
+            Color::White => ColorPiece::White,
+            Color::Black => ColorPiece::Black,
+        };
+        let undo = UndoInfo {
+            move_,
+            moved: color_constructor(piece.into()),
+            destination: self[move_.to],
+            captured: None,
+            castling: None,
+            en_passant_square: self.en_passant_square,
+            castling_rights: self.castling_rights,
+            castling_files: self.castling_files,
+            previous_move: self.previous_move,
+            halfmove_clock: self.halfmove_clock,
+            pockets: self.pockets,
+            checks_given: self.checks_given,
+            zobrist_key: self.zobrist_key,
+        };
+
+        let mut pockets = self
+            .pockets
+            .expect("make_move called with a drop, but there are no pockets");
+        assert!(
+            pockets.remove(color, piece),
+            "make_move called with a drop of a piece that is not in hand: {move_:?}"
+        );
+        self.set_pockets(Some(pockets));
+
+        // The dropped piece lands on an empty square, or merges with a friendly unitary piece
+        let landed = match undo.destination {
+            Some(dst) => merge(piece, dst, move_),
+            None => piece.into(),
+        };
+        self.set_piece(move_.to, Some(color_constructor(landed)));
 
-            // If we didn't set en-passant on this move,
-            // then unset it
-            if !did_set_en_passant {
-                this.en_passant_square = None;
+        // Dropping a pawn resets the halfmove clock, like moving one
+        self.en_passant_square = None;
+        if piece == UnitaryPiece::Pawn {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        self.pass_turn(&undo);
+        undo
+    }
+
+    /// The part of [`BoardRepr::make_move`] that plays castling.
+    /// The rook moves too, along with its other half if it has one.
+    fn make_castle(&mut self, move_: Move, side: CastlingSide) -> UndoInfo {
+        let color = self.side_to_move;
+        let (king_from, rook_from) = (side.king_from(self, color), side.rook_from(self, color));
+        let king = self[king_from].expect("make_move called with an empty source square");
+        let rook = self[rook_from];
+        let undo = UndoInfo {
+            move_,
+            moved: king,
+            destination: self[move_.to],
+            captured: None,
+            castling: Some(side),
+            en_passant_square: self.en_passant_square,
+            castling_rights: self.castling_rights,
+            castling_files: self.castling_files,
+            previous_move: self.previous_move,
+            halfmove_clock: self.halfmove_clock,
+            pockets: self.pockets,
+            checks_given: self.checks_given,
+            zobrist_key: self.zobrist_key,
+        };
+
+        // Both pieces are lifted before either lands,
+        // since in Chess960 each can land on the other's starting square
+        self.set_piece(king_from, None);
+        self.set_piece(rook_from, None);
+        self.set_piece(side.king_to(color), Some(king));
+        self.set_piece(side.rook_to(color), rook);
+
+        for side in CastlingSide::ALL {
+            self.castling_rights[side.right_index(color)] = false;
+        }
+        self.en_passant_square = None;
+        self.halfmove_clock += 1;
+        self.pass_turn(&undo);
+        undo
+    }
+
+    /// Goes back to the usual starting files for the castling rights that were lost,
+    /// see [`BoardRepr::castling_files`], and updates the Zobrist key to match.
+    pub(crate) fn forget_lost_castling_files(&mut self) {
+        let old_files = self.castling_files;
+        for color in [Color::White, Color::Black] {
+            let files = &mut self.castling_files[color as usize];
+            let rights =
+                CastlingSide::ALL.map(|side| self.castling_rights[side.right_index(color)]);
+            for side in CastlingSide::ALL {
+                if !rights[side as usize] {
+                    files.rooks[side as usize] = CastlingFiles::STANDARD.rook(side);
+                }
             }
+            if rights == [false; 2] {
+                files.king = CastlingFiles::STANDARD.king;
+            }
+        }
+        self.zobrist_key ^= zobrist::castling_files_key(old_files)
+            ^ zobrist::castling_files_key(self.castling_files);
+    }
 
-            // Swap the side to move
-            this.side_to_move = this.side_to_move.opposite();
+    /// Hands the turn to the other side after a move, counts the check it gave if checks are counted,
+    /// and updates the Zobrist key for the changes to the state that the move made.
+    fn pass_turn(&mut self, undo: &UndoInfo) {
+        self.forget_lost_castling_files();
+        if self.side_to_move == Color::Black {
+            self.fullmove_number += 1;
+        }
 
-            Ok(())
+        let mover = self.side_to_move;
+        self.side_to_move = mover.opposite();
+        self.previous_move = Some(undo.move_);
+        if let Some(mut checks) = self.checks_given {
+            if self.king_in_check(self.side_to_move) {
+                checks[mover as usize] = checks[mover as usize].saturating_add(1);
+                self.checks_given = Some(checks);
+            }
         }
+        self.zobrist_key ^= zobrist::side_key(Color::Black)
+            ^ zobrist::castling_key(undo.castling_rights)
+            ^ zobrist::castling_key(self.castling_rights)
+            ^ zobrist::en_passant_key(undo.en_passant_square)
+            ^ zobrist::en_passant_key(self.en_passant_square)
+            ^ zobrist::checks_key(undo.checks_given)
+            ^ zobrist::checks_key(self.checks_given);
+    }
+
+    /// Takes back a move played with [`BoardRepr::make_move`].
+    /// Moves must be taken back in the reverse order to how they were made.
+    pub fn unmake_move(&mut self, undo: UndoInfo) {
+        let Move { from, to, .. } = undo.move_;
 
-        let old_self = *self;
-        if let Err(why) = play_inner(self, move_) {
-            *self = old_self;
-            return Err(why);
+        self.side_to_move = self.side_to_move.opposite();
+        if self.side_to_move == Color::Black {
+            self.fullmove_number -= 1;
         }
 
-        // The move was played successfully,
-        // so update the previous move
-        self.previous_move = Some(move_);
-        Ok(())
+        self.castling_rights = undo.castling_rights;
+        self.castling_files = undo.castling_files;
+
+        if let Some(side) = undo.castling {
+            // The Zobrist key is restored below, so only the bitboards need updating
+            let color = undo.moved.color();
+            let rook = self[side.rook_to(color)];
+            self.bitboards.set(side.rook_to(color), None);
+            self.bitboards.set(side.king_to(color), None);
+            self.bitboards.set(from, Some(undo.moved));
+            self.bitboards.set(side.rook_from(self, color), rook);
+        } else {
+            // A dropped piece goes back to the pocket, and has no source square
+            if !undo.move_.is_drop() {
+                self.bitboards.set(from, Some(undo.moved));
+            }
+            self.bitboards.set(to, undo.destination);
+            if let Some((square, piece)) = undo.captured {
+                self.bitboards.set(square, Some(piece));
+            }
+        }
+
+        self.en_passant_square = undo.en_passant_square;
+        self.previous_move = undo.previous_move;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.pockets = undo.pockets;
+        self.checks_given = undo.checks_given;
+        self.zobrist_key = undo.zobrist_key;
     }
 
+    /// Checks whether the side has too few pieces to ever checkmate,
+    /// even with the help of the opponent.
+    /// A side with pieces in hand can always drop them, so its material is sufficient.
     pub fn has_insufficient_material(&self, side: Color) -> bool {
-        // TODO: implement real check for this
-        // right now, only insufficient material is when there is only kings on the board.
-        !self
-            .iter_pieces()
-            .filter(|(_, piece)| piece.color() == side)
-            .any(|(_, piece)| piece.piece() != Piece::Unitary(UnitaryPiece::King))
+        use UnitaryPiece::*;
+
+        if self.pockets.is_some_and(|pockets| !pockets.is_empty(side)) {
+            return false;
+        }
+
+        let bitboards = self.bitboards();
+        let ours = bitboards.by_color(side);
+        let theirs = bitboards.by_color(side.opposite());
+        let component = |piece| bitboards.by_component(piece);
+
+        // Pawns, rooks and queens can always mate, even as half of a combination.
+        // Pawns also include the Biawn, whose pawn half can take it to squares of the other color.
+        if (ours & (component(Pawn) | component(Rook) | component(Queen))).any() {
+            return false;
+        }
+
+        // A knight can only mate if the opponent's own pieces block their king.
+        // So it is insufficient if it is our only piece (and not combined with anything),
+        // and the opponent has nothing but queens, which would not block without giving check.
+        let knights = component(Knight);
+        if (ours & knights).any() {
+            let only_queens = component(Queen)
+                & !(component(Bishop) | knights | component(Rook) | component(Pawn));
+            return (ours & !component(King)) == (ours & knights & bitboards.unitary())
+                && (ours & knights).count() == 1
+                && (theirs & !component(King) & !only_queens).is_empty();
+        }
+
+        // Bishops (single or merged with each other) are insufficient
+        // if all the bishops on the board stay on squares of one color,
+        // and there are no pawns or knights that could be used to block the king.
+        let bishops = component(Bishop);
+        if (ours & bishops).any() {
+            let same_color = (bishops & Bitboard::DARK_SQUARES).is_empty()
+                || (bishops & Bitboard::LIGHT_SQUARES).is_empty();
+            return same_color && component(Pawn).is_empty() && knights.is_empty();
+        }
+
+        // Only the king is left
+        true
+    }
+}
+
+/// The combination that a unitary piece forms by moving or being dropped onto a friendly piece.
+fn merge(piece: UnitaryPiece, destination: ColorPiece, move_: Move) -> Piece {
+    let Piece::Unitary(other) = destination.piece() else {
+        panic!("make_move called with a merge onto a combination: {move_:?}");
+    };
+    let combo = CombinationPiece::new(piece, other).unwrap_or_else(|| {
+        panic!("make_move called with an invalid merge of {piece:?} and {other:?}")
+    });
+    Piece::Combination(combo)
+}
+
+/// The piece that a piece containing a pawn becomes on the last rank.
+fn promote(piece: Piece, promotion: Promotion) -> Piece {
+    let promote_half = |half| match half {
+        UnitaryPiece::Pawn => UnitaryPiece::Queen,
+        other => other,
+    };
+    match (promotion, piece) {
+        (Promotion::WholePieceToQueen, _) => UnitaryPiece::Queen.into(),
+        (Promotion::PawnHalfToQueen, Piece::Unitary(p)) => promote_half(p).into(),
+        (Promotion::PawnHalfToQueen, Piece::Combination(combo)) => {
+            CombinationPiece::new(promote_half(combo.first()), promote_half(combo.second()))
+                .expect("promoting a combination doesn't add a king")
+                .into()
+        }
     }
 }
 
@@ -522,25 +918,56 @@ impl Default for BoardRepr {
     fn default() -> Self {
         let mut b = BoardRepr::empty();
         for file in File::ALL {
-            b[Square::from_coords(file, Rank::Second)] = Some(UnitaryPiece::Pawn.white());
-            b[Square::from_coords(file, Rank::Seventh)] = Some(UnitaryPiece::Pawn.black());
+            b.set_piece(
+                Square::from_coords(file, Rank::Second),
+                Some(UnitaryPiece::Pawn.white()),
+            );
+            b.set_piece(
+                Square::from_coords(file, Rank::Seventh),
+                Some(UnitaryPiece::Pawn.black()),
+            );
         }
 
         for (rank, color) in [
             (Rank::First, ColorPiece::White as fn(Piece) -> ColorPiece),
             (Rank::Eighth, ColorPiece::Black as fn(Piece) -> ColorPiece),
         ] {
-            b[Square::from_coords(File::A, rank)] = Some(color(UnitaryPiece::Rook.into()));
-            b[Square::from_coords(File::B, rank)] = Some(color(UnitaryPiece::Knight.into()));
-            b[Square::from_coords(File::C, rank)] = Some(color(UnitaryPiece::Bishop.into()));
-            b[Square::from_coords(File::D, rank)] = Some(color(UnitaryPiece::Queen.into()));
-            b[Square::from_coords(File::E, rank)] = Some(color(UnitaryPiece::King.into()));
-            b[Square::from_coords(File::F, rank)] = Some(color(UnitaryPiece::Bishop.into()));
-            b[Square::from_coords(File::G, rank)] = Some(color(UnitaryPiece::Knight.into()));
-            b[Square::from_coords(File::H, rank)] = Some(color(UnitaryPiece::Rook.into()));
+            b.set_piece(
+                Square::from_coords(File::A, rank),
+                Some(color(UnitaryPiece::Rook.into())),
+            );
+            b.set_piece(
+                Square::from_coords(File::B, rank),
+                Some(color(UnitaryPiece::Knight.into())),
+            );
+            b.set_piece(
+                Square::from_coords(File::C, rank),
+                Some(color(UnitaryPiece::Bishop.into())),
+            );
+            b.set_piece(
+                Square::from_coords(File::D, rank),
+                Some(color(UnitaryPiece::Queen.into())),
+            );
+            b.set_piece(
+                Square::from_coords(File::E, rank),
+                Some(color(UnitaryPiece::King.into())),
+            );
+            b.set_piece(
+                Square::from_coords(File::F, rank),
+                Some(color(UnitaryPiece::Bishop.into())),
+            );
+            b.set_piece(
+                Square::from_coords(File::G, rank),
+                Some(color(UnitaryPiece::Knight.into())),
+            );
+            b.set_piece(
+                Square::from_coords(File::H, rank),
+                Some(color(UnitaryPiece::Rook.into())),
+            );
         }
 
         b.castling_rights = [true; 4];
+        b.refresh_zobrist_key();
         b
     }
 }
@@ -556,10 +983,361 @@ impl<'a> Iterator for BoardPieceIter<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         for i in self.square_idx..64 {
             self.square_idx = i + 1;
-            if let Some(piece) = self.board.pieces[i as usize] {
+            if let Some(piece) = self.board[i as usize] {
                 return Some((Square::new(i as u32), piece));
             }
         }
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pieces::{
+        movement::{get_all_legal_moves, get_legal_moves_from_square},
+        PieceHalf,
+    };
+
+    /// All the legal moves in the position, including moves of single halves and drops.
+    fn all_moves(board: &BoardRepr) -> Vec<Move> {
+        let mut moves = vec![];
+        for (square, piece) in board.iter_pieces() {
+            if piece.color() != board.side_to_move {
+                continue;
+            }
+            for which_half in [None, Some(PieceHalf::Left), Some(PieceHalf::Right)] {
+                moves.extend(get_legal_moves_from_square(
+                    MovesList::new(),
+                    board,
+                    board.side_to_move,
+                    square,
+                    which_half,
+                ));
+            }
+        }
+        moves.extend(
+            get_all_legal_moves(board, board.side_to_move)
+                .into_iter()
+                .filter(|move_| move_.is_drop()),
+        );
+        moves
+    }
+
+    #[test]
+    fn test_make_unmake() {
+        let starts = [
+            BoardRepr::default(),
+            BoardRepr::from_fen("r3k2r/1p4P1/1(QN)6/2Pp4/8/8/P3(BP)1p1/R3K2R w KQkq d6 0 20")
+                .unwrap(),
+            BoardRepr::from_fen("r3k2r/1p4P1/1(QN)6/2Pp4/8/8/P3(BP)1p1/R3K2R[NPbp] w KQkq d6 0 20")
+                .unwrap(),
+        ];
+
+        // Play a few games with pseudo-random moves,
+        // checking every move of every position along the way
+        let mut seed = 12345u64;
+        for start in starts {
+            let mut board = start;
+            for _ in 0..60 {
+                let moves = all_moves(&board);
+                if moves.is_empty() {
+                    break;
+                }
+                for &move_ in &moves {
+                    let mut played = board;
+                    played.play(move_).unwrap();
+
+                    let mut made = board;
+                    let undo = made.make_move(move_);
+                    assert_eq!(made, played, "{move_:?} in {}", board.to_fen());
+                    assert_eq!(made.zobrist_key(), made.compute_zobrist_key());
+                    made.unmake_move(undo);
+                    assert_eq!(made, board, "{move_:?} in {}", board.to_fen());
+                }
+
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                board
+                    .play(moves[(seed >> 33) as usize % moves.len()])
+                    .unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        // Whether white and black have insufficient material
+        let cases = [
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 1", true, true),
+            ("4k3/8/8/8/8/8/8/3NK3 w - - 0 1", true, true),
+            ("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1", true, true),
+            ("4k3/8/8/8/8/8/8/2BBK3 w - - 0 1", false, true),
+            // Same colored bishops, also merged
+            ("4k3/8/8/8/8/8/1B6/2B1K3 w - - 0 1", true, true),
+            ("4k3/8/8/8/8/8/1B6/4K2B w - - 0 1", false, true),
+            ("4k3/8/8/8/8/8/8/3(BB)K3 w - - 0 1", true, true),
+            ("2b1k3/8/8/8/8/8/8/3(BB)K3 w - - 0 1", true, true),
+            ("4kb2/8/8/8/8/8/8/3(BB)K3 w - - 0 1", false, false),
+            // Combinations with a knight are enough
+            ("4k3/8/8/8/8/8/8/3(NN)K3 w - - 0 1", false, true),
+            ("4k3/8/8/8/8/8/8/3(BN)K3 w - - 0 1", false, true),
+            ("4k3/8/8/8/8/8/8/2NNK3 w - - 0 1", false, true),
+            // Combinations with a pawn, rook or queen are enough
+            ("4k3/8/8/8/8/8/8/3(BP)K3 w - - 0 1", false, true),
+            ("4k3/8/8/8/8/8/8/3(NR)K3 w - - 0 1", false, true),
+            ("4k3/8/8/8/8/8/8/3(QB)K3 w - - 0 1", false, true),
+            // The opponent's pieces can help a knight mate, except for queens
+            ("4k3/8/8/8/8/8/8/3NK2q w - - 0 1", true, false),
+            ("4kn2/8/8/8/8/8/8/3NK3 w - - 0 1", false, false),
+            // The opponent's Biawn can block on either color, helping a bishop mate
+            ("4k3/4(bp)3/8/8/8/8/8/2B1K3 w - - 0 1", false, false),
+        ];
+
+        for (fen, white, black) in cases {
+            let board = BoardRepr::from_fen(fen).unwrap();
+            assert_eq!(
+                (
+                    board.has_insufficient_material(Color::White),
+                    board.has_insufficient_material(Color::Black)
+                ),
+                (white, black),
+                "{fen}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_illegal_move_reasons() {
+        let board = BoardRepr::from_fen("4k3/8/8/8/1b6/(BP)7/3(NP)P3/R3K1NR w KQ - 0 1").unwrap();
+        let move_ = |from, to, which_half| Move {
+            from,
+            to,
+            which_half,
+            drop: None,
+        };
+        let cases = [
+            (
+                move_(Square::D4, Square::D5, None),
+                IllegalMoveError::EmptySquare { square: Square::D4 },
+            ),
+            (
+                move_(Square::B4, Square::C3, None),
+                IllegalMoveError::NotYourPiece { square: Square::B4 },
+            ),
+            (
+                move_(Square::E2, Square::E3, Some(PieceHalf::Left)),
+                IllegalMoveError::NoSuchHalf { square: Square::E2 },
+            ),
+            (
+                move_(Square::G1, Square::G3, None),
+                IllegalMoveError::UnreachableSquare {
+                    from: Square::G1,
+                    to: Square::G3,
+                },
+            ),
+            (
+                move_(Square::A1, Square::E1, None),
+                IllegalMoveError::MergeWithKing {
+                    from: Square::A1,
+                    to: Square::E1,
+                },
+            ),
+            (
+                move_(Square::A1, Square::A3, None),
+                IllegalMoveError::InvalidMerge {
+                    from: Square::A1,
+                    to: Square::A3,
+                },
+            ),
+            // The Knawn is pinned, although either half could leave
+            (
+                move_(Square::D2, Square::D3, None),
+                IllegalMoveError::LeavesKingInCheck {
+                    from: Square::D2,
+                    to: Square::D3,
+                },
+            ),
+        ];
+
+        for (move_, error) in cases {
+            assert_eq!(board.validate_move(move_), Err(error), "{move_:?}");
+        }
+        let split = move_(Square::D2, Square::F3, Some(PieceHalf::Left));
+        assert_eq!(board.validate_move(split), Ok(()));
+    }
+
+    #[test]
+    fn test_kingless_board() {
+        let fen = "k7/8/8/8/8/8/8/1R6 w - - 0 1";
+        assert_eq!(
+            BoardRepr::from_fen(fen),
+            Err(crate::fen::ParseFenError::InvalidPosition(vec![
+                crate::validation::PositionError::MissingKing(Color::White)
+            ]))
+        );
+        let mut board = BoardRepr::from_fen_lenient(fen).unwrap();
+        assert_eq!(board.to_fen(), fen);
+        assert_eq!(
+            BoardRepr::from_bytes_lenient(&board.to_bytes().unwrap()),
+            Ok(board)
+        );
+        assert_eq!(
+            crate::builder::BoardBuilder::new()
+                .white(Square::B1, "R")
+                .black(Square::A8, "k")
+                .build_lenient(),
+            Ok(board)
+        );
+
+        assert_eq!(board.try_king_square(Color::White), None);
+        assert_eq!(board.try_king_square(Color::Black), Some(Square::A8));
+        assert!(!board.king_in_check(Color::White));
+        let mut after = board;
+        after.play(board.parse_san("Ra1").unwrap()).unwrap();
+        assert!(after.king_in_check(Color::Black));
+
+        // Without a king to protect, every move of the rook is legal
+        let moves = get_all_legal_moves(&board, Color::White);
+        assert_eq!(moves.len(), 14);
+        assert!(moves
+            .iter()
+            .all(|&move_| board.validate_move(move_).is_ok()));
+
+        // A side with nothing to move is stalemated rather than mated
+        board.set_piece(Square::B1, None);
+        assert_eq!(
+            crate::game::Game::new(board).outcome(),
+            Some(crate::game::GameOutcome::Draw {
+                reason: crate::game::DrawReason::Stalemate
+            })
+        );
+    }
+
+    #[test]
+    fn test_drops() {
+        // Black captures the Knawn, and gets both of its halves in hand
+        let mut board = BoardRepr::from_fen("4k3/8/4p3/3(NP)4/8/8/8/4K3[] b - - 0 1").unwrap();
+        board.play(board.parse_san("exd5").unwrap()).unwrap();
+        let pockets = board.pockets.unwrap();
+        assert_eq!(pockets.count(Color::Black, UnitaryPiece::Knight), 1);
+        assert_eq!(pockets.count(Color::Black, UnitaryPiece::Pawn), 1);
+        assert!(pockets.is_empty(Color::White));
+        board.play(board.parse_san("Kd2").unwrap()).unwrap();
+
+        // The knight can go to any empty square or merge with the pawn,
+        // and the pawn can't go to the first or last rank
+        let drops = get_all_legal_moves(&board, Color::Black)
+            .into_iter()
+            .filter(|move_| move_.is_drop())
+            .count();
+        assert_eq!(drops, (61 + 1) + (46 + 1));
+
+        let knawn = CombinationPiece::new(UnitaryPiece::Knight, UnitaryPiece::Pawn).unwrap();
+        let before = board;
+        let undo = board.make_move(Move::drop(UnitaryPiece::Knight, Square::D5));
+        assert_eq!(board[Square::D5], Some(knawn.black()));
+        assert_eq!(board.zobrist_key(), board.compute_zobrist_key());
+        board.unmake_move(undo);
+        assert_eq!(board, before);
+
+        let cases = [
+            (
+                Move::drop(UnitaryPiece::Queen, Square::D4),
+                IllegalMoveError::NotInPocket {
+                    piece: UnitaryPiece::Queen,
+                    to: Square::D4,
+                },
+            ),
+            (
+                Move::drop(UnitaryPiece::Pawn, Square::A1),
+                IllegalMoveError::InvalidDrop {
+                    piece: UnitaryPiece::Pawn,
+                    to: Square::A1,
+                },
+            ),
+            (
+                Move::drop(UnitaryPiece::Knight, Square::D2),
+                IllegalMoveError::InvalidDrop {
+                    piece: UnitaryPiece::Knight,
+                    to: Square::D2,
+                },
+            ),
+        ];
+        for (move_, error) in cases {
+            assert_eq!(board.validate_move(move_), Err(error), "{move_:?}");
+        }
+
+        // In check, a drop can only block, and can't merge
+        let board = BoardRepr::from_fen("4k3/8/8/8/8/8/5P2/r3K3[NP] w - - 0 1").unwrap();
+        let mut drops: Vec<Move> = get_all_legal_moves(&board, Color::White)
+            .into_iter()
+            .filter(|move_| move_.is_drop())
+            .collect();
+        drops.sort_by_key(|move_| (move_.to as usize, move_.drop));
+        let blocks = [Square::B1, Square::C1, Square::D1]
+            .map(|square| Move::drop(UnitaryPiece::Knight, square));
+        assert_eq!(drops, blocks);
+
+        // A piece captured into a full pocket leaves the game
+        let pawns = "P".repeat(usize::from(u8::MAX));
+        let mut board =
+            BoardRepr::from_fen(&format!("4k3/8/8/3p4/4P3/8/8/4K3[{pawns}] w - - 0 1")).unwrap();
+        let pockets = board.pockets;
+        board.play(board.parse_san("exd5").unwrap()).unwrap();
+        assert_eq!(board.pockets, pockets);
+        assert_eq!(board.zobrist_key(), board.compute_zobrist_key());
+    }
+
+    #[test]
+    fn test_only_pawns_promote() {
+        let mut board = BoardRepr::from_fen("4k3/8/8/8/8/8/r7/4K3 b - - 0 1").unwrap();
+        let undo = board.make_move(Move {
+            from: Square::A2,
+            to: Square::A1,
+            which_half: None,
+            drop: None,
+        });
+        assert_eq!(board[Square::A1], Some(UnitaryPiece::Rook.black()));
+        board.unmake_move(undo);
+        assert_eq!(board[Square::A2], Some(UnitaryPiece::Rook.black()));
+    }
+
+    #[test]
+    fn test_equality_ignores_zobrist_key() {
+        let mut board = BoardRepr::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+        board.side_to_move = Color::Black;
+        let parsed = BoardRepr::from_fen(&board.to_fen()).unwrap();
+        assert_ne!(board.zobrist_key(), parsed.zobrist_key());
+        assert_eq!(board, parsed);
+
+        board.refresh_zobrist_key();
+        assert_eq!(board.zobrist_key(), parsed.zobrist_key());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_recomputes_zobrist_key() {
+        let board = BoardRepr::from_fen("4k3/8/8/3(NP)4/8/8/8/4K3[Bp] b - - 3 12").unwrap();
+        let json = serde_json::to_string(&board).unwrap();
+        assert!(!json.contains("zobrist_key"));
+
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        value["zobrist_key"] = 12345.into();
+        let parsed: BoardRepr = serde_json::from_value(value).unwrap();
+        assert_eq!(parsed, board);
+        assert_eq!(parsed.zobrist_key(), board.zobrist_key());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_defaults() {
+        let board = BoardRepr::default();
+        let mut value = serde_json::to_value(board).unwrap();
+        for field in ["halfmove_clock", "fullmove_number", "rules"] {
+            value.as_object_mut().unwrap().remove(field);
+        }
+        assert_eq!(serde_json::from_value::<BoardRepr>(value).unwrap(), board);
+    }
+}
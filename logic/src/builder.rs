@@ -0,0 +1,267 @@
+//! Setting up positions piece by piece.
+//!
+//! ```
+//! use merging_board_logic::{builder::BoardBuilder, square::Square};
+//!
+//! let board = BoardBuilder::new()
+//!     .white(Square::E1, "K")
+//!     .white(Square::C3, "QN")
+//!     .black(Square::E8, "k")
+//!     .black(Square::D5, "p")
+//!     .en_passant(Square::D5)
+//!     .build()
+//!     .unwrap();
+//! assert_eq!(board.to_fen(), "4k3/8/8/3p4/8/2(QN)5/8/4K3 w - d6 0 1");
+//! ```
+
+use std::fmt;
+
+use crate::{
+    board_repr::BoardRepr,
+    fen::{is_castling_syntax, parse_castling, parse_piece_letters},
+    pieces::{Color, ColorPiece},
+    rules::RuleSet,
+    square::Square,
+    validation::PositionError,
+};
+
+/// Error when building a position with a [`BoardBuilder`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BuildBoardError {
+    /// A piece code that is not one or two piece letters, such as `"QN"`.
+    InvalidPiece(String),
+    /// Castling rights that are not written as in FEN, such as `"Kq"`.
+    InvalidCastling(String),
+    /// The position can't be played from.
+    InvalidPosition(Vec<PositionError>),
+}
+
+impl fmt::Display for BuildBoardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildBoardError::InvalidPiece(code) => write!(f, "invalid piece code: {code:?}"),
+            BuildBoardError::InvalidCastling(s) => write!(f, "invalid castling rights: {s:?}"),
+            BuildBoardError::InvalidPosition(errors) => {
+                f.write_str("invalid position: ")?;
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{error}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildBoardError {}
+
+/// Sets up a position one piece at a time, checking it once it is complete.
+///
+/// Pieces are written with the same letters as in FEN,
+/// one for a unitary piece and two for a combination, in either case.
+/// The first mistake is kept and returned by [`BoardBuilder::build`],
+/// so that the calls can be chained.
+#[derive(Clone, Debug)]
+pub struct BoardBuilder {
+    board: BoardRepr,
+    /// The castling rights, which are only resolved against the pieces once they are all placed.
+    castling: Option<String>,
+    error: Option<BuildBoardError>,
+}
+
+impl Default for BoardBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<BoardRepr> for BoardBuilder {
+    /// Starts from an existing position, to change some of it.
+    fn from(board: BoardRepr) -> Self {
+        Self {
+            board,
+            castling: None,
+            error: None,
+        }
+    }
+}
+
+impl BoardBuilder {
+    /// Starts from an empty board with White to move and no castling rights.
+    pub fn new() -> Self {
+        BoardRepr::empty().into()
+    }
+
+    fn fail(mut self, error: BuildBoardError) -> Self {
+        self.error.get_or_insert(error);
+        self
+    }
+
+    /// Puts a piece on the square, replacing whatever was there.
+    pub fn piece(mut self, square: Square, piece: ColorPiece) -> Self {
+        self.board.set_piece(square, Some(piece));
+        self
+    }
+
+    /// Puts a piece of the given color on the square, written as its letters, such as `"QN"`.
+    pub fn colored(self, color: Color, square: Square, code: &str) -> Self {
+        let letters = match color {
+            Color::White => code.to_ascii_uppercase(),
+            Color::Black => code.to_ascii_lowercase(),
+        };
+        match parse_piece_letters(&letters) {
+            Some(piece) => self.piece(square, piece),
+            None => self.fail(BuildBoardError::InvalidPiece(code.to_string())),
+        }
+    }
+
+    /// Puts a white piece on the square, written as its letters, such as `"QN"`.
+    pub fn white(self, square: Square, code: &str) -> Self {
+        self.colored(Color::White, square, code)
+    }
+
+    /// Puts a black piece on the square, written as its letters, such as `"qn"`.
+    pub fn black(self, square: Square, code: &str) -> Self {
+        self.colored(Color::Black, square, code)
+    }
+
+    /// Empties the square.
+    pub fn remove(mut self, square: Square) -> Self {
+        self.board.set_piece(square, None);
+        self
+    }
+
+    pub fn side_to_move(mut self, color: Color) -> Self {
+        self.board.side_to_move = color;
+        self
+    }
+
+    pub fn white_to_move(self) -> Self {
+        self.side_to_move(Color::White)
+    }
+
+    pub fn black_to_move(self) -> Self {
+        self.side_to_move(Color::Black)
+    }
+
+    /// Sets the castling rights, written as in FEN, such as `"Kq"` or `"-"`,
+    /// or as in X-FEN for Chess960, such as `"Bg"`.
+    pub fn castling(mut self, rights: &str) -> Self {
+        if !is_castling_syntax(rights) {
+            return self.fail(BuildBoardError::InvalidCastling(rights.to_string()));
+        }
+        self.castling = Some(rights.to_string());
+        self
+    }
+
+    /// Lets the piece on the square be captured en passant.
+    /// Unlike in FEN, this is the square of the piece that made the double step,
+    /// as in [`BoardRepr::en_passant_square`].
+    pub fn en_passant(mut self, square: Square) -> Self {
+        self.board.en_passant_square = Some(square);
+        self
+    }
+
+    pub fn halfmove_clock(mut self, halfmove_clock: u32) -> Self {
+        self.board.halfmove_clock = halfmove_clock;
+        self
+    }
+
+    pub fn fullmove_number(mut self, fullmove_number: u32) -> Self {
+        self.board.fullmove_number = fullmove_number;
+        self
+    }
+
+    pub fn rules(mut self, rules: RuleSet) -> Self {
+        self.board.rules = rules;
+        self
+    }
+
+    /// Returns the position, if every piece code was valid and the position can be played from.
+    pub fn build(self) -> Result<BoardRepr, BuildBoardError> {
+        self.finish(BoardRepr::validate)
+    }
+
+    /// Like [`BoardBuilder::build`], but a side may have no king,
+    /// see [`BoardRepr::validate_lenient`].
+    pub fn build_lenient(self) -> Result<BoardRepr, BuildBoardError> {
+        self.finish(BoardRepr::validate_lenient)
+    }
+
+    fn finish(
+        self,
+        validate: fn(&BoardRepr) -> Result<(), Vec<PositionError>>,
+    ) -> Result<BoardRepr, BuildBoardError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        let mut board = self.board;
+        if let Some(rights) = self.castling {
+            (board.castling_rights, board.castling_files) =
+                parse_castling(&rights, &board).ok_or(BuildBoardError::InvalidCastling(rights))?;
+        }
+        validate(&board).map_err(BuildBoardError::InvalidPosition)?;
+        board.refresh_zobrist_key();
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pieces::UnitaryPiece;
+
+    #[test]
+    fn test_builder_matches_fen() {
+        let board = BoardBuilder::new()
+            .white(Square::E1, "K")
+            .white(Square::H1, "nr")
+            .white(Square::C2, "P")
+            .black(Square::E8, "K")
+            .black(Square::A8, "R")
+            .black_to_move()
+            .castling("Kq")
+            .halfmove_clock(3)
+            .fullmove_number(12)
+            .build()
+            .unwrap();
+        assert_eq!(
+            board,
+            BoardRepr::from_fen("r3k3/8/8/8/8/8/2P5/4K2(NR) b Kq - 3 12").unwrap()
+        );
+        assert_eq!(board[Square::E8], Some(UnitaryPiece::King.black()));
+    }
+
+    #[test]
+    fn test_builder_errors() {
+        let kings = || {
+            BoardBuilder::new()
+                .white(Square::E1, "K")
+                .black(Square::E8, "k")
+        };
+        assert_eq!(
+            kings().white(Square::D4, "QX").build(),
+            Err(BuildBoardError::InvalidPiece("QX".into()))
+        );
+        assert_eq!(
+            kings().castling("KK").build(),
+            Err(BuildBoardError::InvalidCastling("KK".into()))
+        );
+        // The first mistake is the one reported
+        assert_eq!(
+            kings().white(Square::D4, "").castling("x").build(),
+            Err(BuildBoardError::InvalidPiece("".into()))
+        );
+        assert_eq!(
+            kings().castling("K").build(),
+            Err(BuildBoardError::InvalidPosition(vec![
+                PositionError::InvalidCastlingRight {
+                    color: Color::White,
+                    side: crate::pieces::movement::CastlingSide::KingSide,
+                }
+            ]))
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! Chess960 (Fischer random) starting positions.
+//!
+//! The pieces of the back rank are shuffled, the same way for both sides,
+//! with the bishops on squares of opposite colors and the king somewhere between the rooks.
+//! That makes 960 positions, numbered from 0 to 959 as usual (the Scharnagl numbering),
+//! where number 518 is the usual starting position.
+//!
+//! Castling follows the king and the rooks to wherever they start, see [`BoardRepr::castling_files`],
+//! and castling rights are written as in X-FEN, see [`crate::fen`].
+
+use crate::{
+    board_repr::BoardRepr,
+    pieces::{movement::CastlingFiles, UnitaryPiece},
+    square::{File, Rank, Square},
+    zobrist::splitmix64,
+};
+
+/// The number of Chess960 starting positions.
+pub const POSITION_COUNT: u16 = 960;
+
+/// The number of the usual starting position.
+pub const STANDARD_INDEX: u16 = 518;
+
+/// The ways to place the two knights on five free squares, by their indices among those squares.
+const KNIGHTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+/// The back rank of the starting position with the given number, from the a-file to the h-file.
+fn back_rank(index: u16) -> [UnitaryPiece; 8] {
+    use UnitaryPiece::*;
+
+    let mut rank: [Option<UnitaryPiece>; 8] = [None; 8];
+    let mut index = usize::from(index);
+
+    // The light-squared bishop goes on b, d, f or h, and the dark-squared one on a, c, e or g
+    rank[2 * (index % 4) + 1] = Some(Bishop);
+    index /= 4;
+    rank[2 * (index % 4)] = Some(Bishop);
+    index /= 4;
+
+    // The other pieces fill the free squares from left to right
+    let place = |rank: &mut [Option<UnitaryPiece>; 8], nth: usize, piece| {
+        let file = (0..8)
+            .filter(|file| rank[*file].is_none())
+            .nth(nth)
+            .unwrap();
+        rank[file] = Some(piece);
+    };
+    place(&mut rank, index % 6, Queen);
+    index /= 6;
+    let (first, second) = KNIGHTS[index];
+    // Placing the first knight takes away a free square before the second one
+    place(&mut rank, second, Knight);
+    place(&mut rank, first, Knight);
+    for piece in [Rook, King, Rook] {
+        place(&mut rank, 0, piece);
+    }
+
+    rank.map(|piece| piece.unwrap())
+}
+
+impl BoardRepr {
+    /// The Chess960 starting position with the given number, see the [module documentation](crate::chess960).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number is not less than [`POSITION_COUNT`].
+    pub fn chess960(index: u16) -> BoardRepr {
+        assert!(
+            index < POSITION_COUNT,
+            "there are only {POSITION_COUNT} Chess960 positions, got {index}"
+        );
+
+        let pieces = back_rank(index);
+        let file_of = |wanted: UnitaryPiece| {
+            File::ALL
+                .into_iter()
+                .filter(move |file| pieces[u32::from(*file) as usize] == wanted)
+        };
+        let king = file_of(UnitaryPiece::King).next().unwrap();
+        let mut rooks = file_of(UnitaryPiece::Rook);
+        let (queen_side_rook, king_side_rook) = (rooks.next().unwrap(), rooks.next().unwrap());
+
+        let mut board = BoardRepr::empty();
+        for (file, piece) in File::ALL.into_iter().zip(pieces) {
+            board.set_piece(Square::from_coords(file, Rank::First), Some(piece.white()));
+            board.set_piece(
+                Square::from_coords(file, Rank::Second),
+                Some(UnitaryPiece::Pawn.white()),
+            );
+            board.set_piece(
+                Square::from_coords(file, Rank::Seventh),
+                Some(UnitaryPiece::Pawn.black()),
+            );
+            board.set_piece(Square::from_coords(file, Rank::Eighth), Some(piece.black()));
+        }
+        board.castling_rights = [true; 4];
+        board.castling_files = [CastlingFiles {
+            king,
+            rooks: [king_side_rook, queen_side_rook],
+        }; 2];
+        board.refresh_zobrist_key();
+        board
+    }
+
+    /// A Chess960 starting position picked by the seed,
+    /// so that the same seed always gives the same position.
+    pub fn chess960_from_seed(seed: u64) -> BoardRepr {
+        let mut state = seed;
+        BoardRepr::chess960((splitmix64(&mut state) % u64::from(POSITION_COUNT)) as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitboard::Bitboard;
+
+    #[test]
+    fn test_standard_index() {
+        assert_eq!(BoardRepr::chess960(STANDARD_INDEX), BoardRepr::default());
+        assert_eq!(
+            BoardRepr::chess960(0).to_fen(),
+            "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1"
+        );
+    }
+
+    #[test]
+    fn test_all_positions() {
+        let mut fens = std::collections::HashSet::new();
+        for index in 0..POSITION_COUNT {
+            let board = BoardRepr::chess960(index);
+            assert_eq!(board.validate(), Ok(()), "{index}");
+            let rank = Bitboard::from_rank(Rank::First);
+            let bishops = board.bitboards().by_component(UnitaryPiece::Bishop) & rank;
+            assert_eq!((bishops & Bitboard::DARK_SQUARES).count(), 1, "{index}");
+            let files = board.castling_files[0];
+            assert!(
+                files.rooks[1] < files.king && files.king < files.rooks[0],
+                "{index}"
+            );
+            assert!(fens.insert(board.to_fen()), "{index}");
+        }
+    }
+
+    #[test]
+    fn test_from_seed() {
+        assert_eq!(
+            BoardRepr::chess960_from_seed(7),
+            BoardRepr::chess960_from_seed(7)
+        );
+        let different = (0..10)
+            .map(|seed| BoardRepr::chess960_from_seed(seed).to_fen())
+            .collect::<std::collections::HashSet<_>>();
+        assert!(different.len() > 1);
+    }
+}
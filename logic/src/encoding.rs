@@ -0,0 +1,602 @@
+//! A compact binary encoding of positions and moves, for the network and for storage.
+//!
+//! A move takes 16 bits: the source square in bits 0-5, the destination square in bits 6-11,
+//! and the half that moves in bits 12-13 (0 for the whole piece, 1 for the left half, 2 for the right one).
+//! A drop has 3 in bits 12-13, and the dropped piece instead of the source square,
+//! numbered in the order of [`Pockets::DROPPABLE`].
+//!
+//! A position is laid out as follows, with every number in little-endian order:
+//!
+//! | Bytes | Contents |
+//! |-------|----------|
+//! | 8     | The occupied squares, as a bitboard |
+//! | 2     | Flags: Black to move (bit 0), the castling rights in FEN order (bits 1-4), en passant (bit 5) and its file (bits 6-8), whether there is a previous move (bit 9), whether there are pockets (bit 10), whether the castling files are not the usual ones (bit 11), and whether checks are counted (bit 12) |
+//! | 1     | The rule set, see [`RuleSet`] |
+//! | 2     | The halfmove clock, see [`EncodeError`] if it doesn't fit |
+//! | 2     | The fullmove number, see [`EncodeError`] if it doesn't fit |
+//! | 0 or 2 | The previous move, if there is one |
+//! | 0 or 10 | The number of each piece in hand, White's then Black's, if there are pockets |
+//! | 0 or 3 | For Chess960, 9 bits for each color, White first: the files of the king, the king side rook and the queen side rook, 3 bits each |
+//! | 0 or 2 | The number of checks given by White and by Black, if checks are counted |
+//! | rest  | For each occupied square from a1 to h8, 6 bits: a 5-bit piece code and a bit that is set for Black |
+//!
+//! The starting position takes 39 bytes.
+//! The `compact_*` modules let serde use this encoding for a field with `#[serde(with = "...")]`.
+
+use std::fmt;
+
+use crate::{
+    bitboard::Bitboard,
+    board_repr::BoardRepr,
+    pieces::{
+        movement::{CastlingFiles, Move},
+        Color, ColorPiece, CombinationPiece, Piece, PieceHalf, UnitaryPiece,
+    },
+    pocket::Pockets,
+    rules::{PawnMerge, Promotion, RuleSet},
+    square::{File, Rank, Square},
+    validation::PositionError,
+};
+
+/// Error when decoding a position or a move that wasn't produced by this module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input ends before the encoding does.
+    UnexpectedEnd,
+    /// There are bytes left after the encoding ends.
+    TrailingBytes,
+    /// A move has an invalid half, or drops a piece that can't be held in hand.
+    InvalidMove(u16),
+    /// A piece code doesn't stand for any piece.
+    InvalidPiece(u8),
+    /// The flags or the rule set use bits that are always zero.
+    InvalidFlags,
+    /// The position was decoded, but can't be played from.
+    InvalidPosition(Vec<PositionError>),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEnd => f.write_str("unexpected end of encoded data"),
+            DecodeError::TrailingBytes => f.write_str("unexpected bytes after encoded data"),
+            DecodeError::InvalidMove(bits) => write!(f, "invalid encoded move: {bits:#06x}"),
+            DecodeError::InvalidPiece(code) => write!(f, "invalid encoded piece: {code}"),
+            DecodeError::InvalidFlags => f.write_str("invalid flags in encoded position"),
+            DecodeError::InvalidPosition(errors) => {
+                f.write_str("invalid encoded position: ")?;
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{error}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Error when a position doesn't fit in the encoding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncodeError {
+    /// The halfmove clock is more than 16 bits can hold.
+    HalfmoveClockTooLarge(u32),
+    /// The fullmove number is more than 16 bits can hold.
+    FullmoveNumberTooLarge(u32),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::HalfmoveClockTooLarge(clock) => {
+                write!(f, "halfmove clock too large to encode: {clock}")
+            }
+            EncodeError::FullmoveNumberTooLarge(number) => {
+                write!(f, "fullmove number too large to encode: {number}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+impl Move {
+    /// Packs the move into 16 bits, see the [module documentation](self).
+    pub fn to_u16(self) -> u16 {
+        if let Some(piece) = self.drop {
+            return piece as u16 | (self.to as u16) << 6 | 3 << 12;
+        }
+        let half = match self.which_half {
+            None => 0,
+            Some(PieceHalf::Left) => 1,
+            Some(PieceHalf::Right) => 2,
+        };
+        self.from as u16 | (self.to as u16) << 6 | half << 12
+    }
+
+    pub fn from_u16(bits: u16) -> Result<Move, DecodeError> {
+        let to = Square::ALL[usize::from((bits >> 6) & 0x3f)];
+        let which_half = match bits >> 12 {
+            0 => None,
+            1 => Some(PieceHalf::Left),
+            2 => Some(PieceHalf::Right),
+            3 => {
+                let piece = Pockets::DROPPABLE
+                    .get(usize::from(bits & 0x3f))
+                    .ok_or(DecodeError::InvalidMove(bits))?;
+                return Ok(Move::drop(*piece, to));
+            }
+            _ => return Err(DecodeError::InvalidMove(bits)),
+        };
+        Ok(Move {
+            from: Square::ALL[usize::from(bits & 0x3f)],
+            to,
+            which_half,
+            drop: None,
+        })
+    }
+
+    pub fn to_bytes(self) -> [u8; 2] {
+        self.to_u16().to_le_bytes()
+    }
+
+    pub fn from_bytes(bytes: [u8; 2]) -> Result<Move, DecodeError> {
+        Move::from_u16(u16::from_le_bytes(bytes))
+    }
+}
+
+/// Every piece, in the order of their codes:
+/// the unitary pieces, then the combinations.
+fn pieces_by_code() -> impl Iterator<Item = Piece> {
+    let unitary = UnitaryPiece::ALL.into_iter().map(Piece::Unitary);
+    let combinations = UnitaryPiece::ALL.into_iter().flat_map(|first| {
+        UnitaryPiece::ALL
+            .into_iter()
+            .filter(move |second| first <= *second)
+            .filter_map(move |second| CombinationPiece::new(first, second))
+            .map(Piece::Combination)
+    });
+    unitary.chain(combinations)
+}
+
+fn piece_code(piece: ColorPiece) -> u8 {
+    let code = pieces_by_code()
+        .position(|p| p == piece.piece())
+        .expect("every piece has a code") as u8;
+    code | u8::from(piece.is_black()) << 5
+}
+
+fn piece_from_code(code: u8) -> Result<ColorPiece, DecodeError> {
+    let piece = pieces_by_code()
+        .nth(usize::from(code & 0x1f))
+        .ok_or(DecodeError::InvalidPiece(code))?;
+    Ok(if code & 0x20 == 0 {
+        ColorPiece::White(piece)
+    } else {
+        ColorPiece::Black(piece)
+    })
+}
+
+fn rules_to_byte(rules: RuleSet) -> u8 {
+    u8::from(rules.pawn_merge == PawnMerge::Forward)
+        | u8::from(rules.en_passant) << 1
+        | u8::from(rules.promotion == Promotion::PawnHalfToQueen) << 2
+        | u8::from(rules.castle_with_combined_rook) << 3
+}
+
+fn rules_from_byte(byte: u8) -> Result<RuleSet, DecodeError> {
+    if byte >> 4 != 0 {
+        return Err(DecodeError::InvalidFlags);
+    }
+    Ok(RuleSet {
+        pawn_merge: if byte & 1 == 0 {
+            PawnMerge::Diagonal
+        } else {
+            PawnMerge::Forward
+        },
+        en_passant: byte & 2 != 0,
+        promotion: if byte & 4 == 0 {
+            Promotion::WholePieceToQueen
+        } else {
+            Promotion::PawnHalfToQueen
+        },
+        castle_with_combined_rook: byte & 8 != 0,
+    })
+}
+
+/// Reads the encoding from the front, failing if it runs out.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        let (taken, rest) = self
+            .bytes
+            .split_first_chunk::<N>()
+            .ok_or(DecodeError::UnexpectedEnd)?;
+        self.bytes = rest;
+        Ok(*taken)
+    }
+
+    fn u16(&mut self) -> Result<u16, DecodeError> {
+        self.take().map(u16::from_le_bytes)
+    }
+}
+
+impl BoardRepr {
+    /// Encodes the position compactly, see the [module documentation](crate::encoding).
+    pub fn to_bytes(&self) -> Result<Vec<u8>, EncodeError> {
+        let halfmove_clock = u16::try_from(self.halfmove_clock)
+            .map_err(|_| EncodeError::HalfmoveClockTooLarge(self.halfmove_clock))?;
+        let fullmove_number = u16::try_from(self.fullmove_number)
+            .map_err(|_| EncodeError::FullmoveNumberTooLarge(self.fullmove_number))?;
+        let occupied = self.bitboards().occupied();
+
+        let mut flags = u16::from(self.side_to_move == Color::Black);
+        for (i, right) in self.castling_rights.into_iter().enumerate() {
+            flags |= u16::from(right) << (1 + i);
+        }
+        if let Some(square) = self.en_passant_square {
+            flags |= 1 << 5 | (u32::from(square.file()) as u16) << 6;
+        }
+        if self.previous_move.is_some() {
+            flags |= 1 << 9;
+        }
+        if self.pockets.is_some() {
+            flags |= 1 << 10;
+        }
+        let chess960 = self.castling_files != [CastlingFiles::STANDARD; 2];
+        if chess960 {
+            flags |= 1 << 11;
+        }
+        if self.checks_given.is_some() {
+            flags |= 1 << 12;
+        }
+
+        let mut bytes = Vec::with_capacity(17 + (6 * occupied.count() as usize).div_ceil(8));
+        bytes.extend(occupied.0.to_le_bytes());
+        bytes.extend(flags.to_le_bytes());
+        bytes.push(rules_to_byte(self.rules));
+        bytes.extend(halfmove_clock.to_le_bytes());
+        bytes.extend(fullmove_number.to_le_bytes());
+        if let Some(move_) = self.previous_move {
+            bytes.extend(move_.to_bytes());
+        }
+        if let Some(pockets) = self.pockets {
+            for color in [Color::White, Color::Black] {
+                bytes.extend(Pockets::DROPPABLE.map(|piece| pockets.count(color, piece)));
+            }
+        }
+        if chess960 {
+            let packed = self
+                .castling_files
+                .iter()
+                .flat_map(|files| [files.king, files.rooks[0], files.rooks[1]])
+                .enumerate()
+                .fold(0u32, |packed, (i, file)| {
+                    packed | u32::from(file) << (3 * i)
+                });
+            bytes.extend(&packed.to_le_bytes()[..3]);
+        }
+        if let Some(checks_given) = self.checks_given {
+            bytes.extend(checks_given);
+        }
+
+        // The piece codes are packed 6 bits at a time, starting from the lowest bit
+        let mut buffer = 0u32;
+        let mut bits = 0;
+        for square in occupied {
+            let piece = self[square].expect("occupied square has a piece");
+            buffer |= u32::from(piece_code(piece)) << bits;
+            bits += 6;
+            while bits >= 8 {
+                bytes.push(buffer as u8);
+                buffer >>= 8;
+                bits -= 8;
+            }
+        }
+        if bits > 0 {
+            bytes.push(buffer as u8);
+        }
+        Ok(bytes)
+    }
+
+    /// Decodes a position encoded by [`BoardRepr::to_bytes`],
+    /// and checks that it can be played from.
+    pub fn from_bytes(bytes: &[u8]) -> Result<BoardRepr, DecodeError> {
+        BoardRepr::decode(bytes, BoardRepr::validate)
+    }
+
+    /// Like [`BoardRepr::from_bytes`], but a side may have no king,
+    /// see [`BoardRepr::validate_lenient`].
+    pub fn from_bytes_lenient(bytes: &[u8]) -> Result<BoardRepr, DecodeError> {
+        BoardRepr::decode(bytes, BoardRepr::validate_lenient)
+    }
+
+    fn decode(
+        bytes: &[u8],
+        validate: fn(&BoardRepr) -> Result<(), Vec<PositionError>>,
+    ) -> Result<BoardRepr, DecodeError> {
+        let mut reader = Reader { bytes };
+        let occupied = Bitboard(u64::from_le_bytes(reader.take()?));
+        let flags = reader.u16()?;
+        if flags >> 13 != 0 {
+            return Err(DecodeError::InvalidFlags);
+        }
+
+        let mut board = BoardRepr::empty();
+        board.side_to_move = if flags & 1 == 0 {
+            Color::White
+        } else {
+            Color::Black
+        };
+        for (i, right) in board.castling_rights.iter_mut().enumerate() {
+            *right = flags & (1 << (1 + i)) != 0;
+        }
+        if flags & (1 << 5) != 0 {
+            // The piece that can be captured is the one that just made a double step
+            let rank = match board.side_to_move {
+                Color::White => Rank::Fifth,
+                Color::Black => Rank::Fourth,
+            };
+            let file = File::new(u32::from((flags >> 6) & 7));
+            board.en_passant_square = Some(Square::from_coords(file, rank));
+        }
+        board.rules = rules_from_byte(reader.take::<1>()?[0])?;
+        board.halfmove_clock = reader.u16()?.into();
+        board.fullmove_number = reader.u16()?.into();
+        if flags & (1 << 9) != 0 {
+            board.previous_move = Some(Move::from_bytes(reader.take()?)?);
+        }
+        if flags & (1 << 10) != 0 {
+            let mut pockets = Pockets::default();
+            for color in [Color::White, Color::Black] {
+                let counts: [u8; Pockets::DROPPABLE.len()] = reader.take()?;
+                for (piece, count) in Pockets::DROPPABLE.into_iter().zip(counts) {
+                    pockets.set_count(color, piece, count);
+                }
+            }
+            board.pockets = Some(pockets);
+        }
+        if flags & (1 << 11) != 0 {
+            let [low, middle, high] = reader.take()?;
+            let packed = u32::from_le_bytes([low, middle, high, 0]);
+            let file = |i: u32| File::new((packed >> (3 * i)) & 7);
+            for (i, files) in (0..).step_by(3).zip(&mut board.castling_files) {
+                *files = CastlingFiles {
+                    king: file(i),
+                    rooks: [file(i + 1), file(i + 2)],
+                };
+            }
+        }
+        if flags & (1 << 12) != 0 {
+            board.checks_given = Some(reader.take()?);
+        }
+
+        let packed_len = (6 * occupied.count() as usize).div_ceil(8);
+        let packed = reader
+            .bytes
+            .get(..packed_len)
+            .ok_or(DecodeError::UnexpectedEnd)?;
+        if reader.bytes.len() > packed_len {
+            return Err(DecodeError::TrailingBytes);
+        }
+        let mut packed = packed.iter();
+        let mut buffer = 0u32;
+        let mut bits = 0;
+        for square in occupied {
+            if bits < 6 {
+                buffer |= u32::from(*packed.next().ok_or(DecodeError::UnexpectedEnd)?) << bits;
+                bits += 8;
+            }
+            board.set_piece(square, Some(piece_from_code((buffer & 0x3f) as u8)?));
+            buffer >>= 6;
+            bits -= 6;
+        }
+
+        board.forget_lost_castling_files();
+        validate(&board).map_err(DecodeError::InvalidPosition)?;
+        board.refresh_zobrist_key();
+        Ok(board)
+    }
+}
+
+#[cfg(feature = "serde")]
+struct BytesVisitor;
+
+#[cfg(feature = "serde")]
+impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("bytes")
+    }
+
+    fn visit_bytes<E: serde::de::Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+        Ok(bytes.to_vec())
+    }
+
+    fn visit_byte_buf<E: serde::de::Error>(self, bytes: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(bytes)
+    }
+
+    // Self-describing formats like JSON write bytes as a sequence of numbers
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(bytes)
+    }
+}
+
+/// Serializes a [`BoardRepr`] with [`BoardRepr::to_bytes`], for `#[serde(with = "compact_board")]`.
+#[cfg(feature = "serde")]
+pub mod compact_board {
+    use serde::{Deserializer, Serializer};
+
+    use super::BytesVisitor;
+    use crate::board_repr::BoardRepr;
+
+    pub fn serialize<S: Serializer>(board: &BoardRepr, serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes = board.to_bytes().map_err(serde::ser::Error::custom)?;
+        serializer.serialize_bytes(&bytes)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BoardRepr, D::Error> {
+        let bytes = deserializer.deserialize_bytes(BytesVisitor)?;
+        BoardRepr::from_bytes(&bytes).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Serializes a [`Move`] as its 16-bit encoding, for `#[serde(with = "compact_move")]`.
+#[cfg(feature = "serde")]
+pub mod compact_move {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::pieces::movement::Move;
+
+    pub fn serialize<S: Serializer>(move_: &Move, serializer: S) -> Result<S::Ok, S::Error> {
+        move_.to_u16().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Move, D::Error> {
+        Move::from_u16(u16::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Serializes a list of [`Move`]s as their 16-bit encodings one after the other,
+/// for `#[serde(with = "compact_moves")]`.
+#[cfg(feature = "serde")]
+pub mod compact_moves {
+    use serde::{Deserializer, Serializer};
+
+    use super::{BytesVisitor, DecodeError};
+    use crate::pieces::movement::Move;
+
+    pub fn serialize<S: Serializer>(moves: &[Move], serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes: Vec<u8> = moves.iter().flat_map(|move_| move_.to_bytes()).collect();
+        serializer.serialize_bytes(&bytes)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Move>, D::Error> {
+        let bytes = deserializer.deserialize_bytes(BytesVisitor)?;
+        let (chunks, rest) = bytes.as_chunks::<2>();
+        if !rest.is_empty() {
+            return Err(serde::de::Error::custom(DecodeError::UnexpectedEnd));
+        }
+        chunks
+            .iter()
+            .map(|chunk| Move::from_bytes(*chunk).map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pieces::movement::get_all_legal_moves;
+
+    #[test]
+    fn test_piece_codes() {
+        let codes: Vec<Piece> = pieces_by_code().collect();
+        assert_eq!(codes.len(), 21);
+        for (code, piece) in codes.into_iter().enumerate() {
+            for colored in [ColorPiece::White(piece), ColorPiece::Black(piece)] {
+                assert_eq!(piece_from_code(piece_code(colored)), Ok(colored));
+            }
+            assert_eq!(piece_code(ColorPiece::White(piece)), code as u8);
+        }
+        assert_eq!(piece_from_code(21), Err(DecodeError::InvalidPiece(21)));
+    }
+
+    #[test]
+    fn test_move_round_trip() {
+        let board =
+            BoardRepr::from_fen("r3k2r/1p4P1/1(QN)6/2Pp4/8/8/P3(BP)1p1/R3K2R w KQkq d6 0 20")
+                .unwrap();
+        for move_ in get_all_legal_moves(&board, board.side_to_move) {
+            assert_eq!(Move::from_bytes(move_.to_bytes()), Ok(move_));
+        }
+        let drop = Move::drop(UnitaryPiece::Pawn, Square::E4);
+        assert_eq!(Move::from_bytes(drop.to_bytes()), Ok(drop));
+        assert_eq!(
+            Move::from_u16(0x3005),
+            Err(DecodeError::InvalidMove(0x3005))
+        );
+    }
+
+    #[test]
+    fn test_board_round_trip() {
+        assert_eq!(BoardRepr::default().to_bytes().unwrap().len(), 39);
+
+        let mut board =
+            BoardRepr::from_fen("r3k2r/1p4P1/1(QN)6/2Pp4/8/8/P3(BP)1p1/R3K2R w KQkq d6 3 20")
+                .unwrap();
+        board.rules = RuleSet::ANDROID_APP;
+        board.refresh_zobrist_key();
+        for move_ in get_all_legal_moves(&board, board.side_to_move) {
+            let mut after = board;
+            after.make_move(move_);
+            assert_eq!(BoardRepr::from_bytes(&after.to_bytes().unwrap()), Ok(after));
+        }
+
+        let bytes = board.to_bytes().unwrap();
+        assert_eq!(BoardRepr::from_bytes(&bytes), Ok(board));
+
+        let crazyhouse = BoardRepr::from_fen(
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R[NPPbp] w KQkq - 2 3",
+        )
+        .unwrap();
+        assert_eq!(
+            BoardRepr::from_bytes(&crazyhouse.to_bytes().unwrap()),
+            Ok(crazyhouse)
+        );
+        let chess960 = BoardRepr::chess960(0);
+        assert_eq!(chess960.to_bytes().unwrap().len(), 42);
+        assert_eq!(
+            BoardRepr::from_bytes(&chess960.to_bytes().unwrap()),
+            Ok(chess960)
+        );
+        let three_check = BoardRepr::from_fen(
+            "rnbqkb1r/pppp1ppp/5n2/4p2Q/4P3/8/PPPP1PPP/RNB1KBNR w KQkq - 2 3 +1+0",
+        )
+        .unwrap();
+        assert_eq!(
+            BoardRepr::from_bytes(&three_check.to_bytes().unwrap()),
+            Ok(three_check)
+        );
+        assert_eq!(
+            BoardRepr::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::UnexpectedEnd)
+        );
+        assert_eq!(
+            BoardRepr::from_bytes(&[bytes.as_slice(), &[0]].concat()),
+            Err(DecodeError::TrailingBytes)
+        );
+    }
+
+    #[test]
+    fn test_clocks_too_large() {
+        let mut board =
+            BoardRepr::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 65535 65535")
+                .unwrap();
+        assert_eq!(BoardRepr::from_bytes(&board.to_bytes().unwrap()), Ok(board));
+
+        board.halfmove_clock += 1;
+        assert_eq!(
+            board.to_bytes(),
+            Err(EncodeError::HalfmoveClockTooLarge(65536))
+        );
+        board.halfmove_clock = 0;
+        board.fullmove_number += 1;
+        assert_eq!(
+            board.to_bytes(),
+            Err(EncodeError::FullmoveNumberTooLarge(65536))
+        );
+    }
+}
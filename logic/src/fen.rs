@@ -0,0 +1,703 @@
+//! A FEN-style text notation for positions.
+//!
+//! This is the usual Forsyth–Edwards Notation, extended so that combination pieces can be written down:
+//! a combination is written as its two component letters in parentheses,
+//! uppercase for White and lowercase for Black.
+//! For example, `(NP)` is a white Knawn and `(qq)` is a black double queen.
+//!
+//! If the position has pockets, see [`crate::pocket`], the pieces in hand follow the piece placement
+//! in brackets, uppercase for White and lowercase for Black, as in Crazyhouse FEN:
+//! `rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[Nn] w KQkq - 0 1`.
+//! Empty brackets mean that there are pockets, but nothing in them.
+//!
+//! Castling rights can also be written as in X-FEN and Shredder-FEN, for Chess960 positions:
+//! `K` and `Q` stand for the outermost rook on that side of the king,
+//! and the file letter of a rook stands for that rook, such as `HAha` for `KQkq`.
+//! When writing, `K` and `Q` are used whenever they are enough, and the file letter otherwise.
+//!
+//! If checks are counted, as in Three-check, the number of checks given by White and by Black
+//! follows the other fields as in Lichess's Three-check FEN: `... w KQkq - 0 1 +2+0`.
+//!
+//! The en passant field holds the square that the capturing piece would move to,
+//! as in regular FEN, even though [`BoardRepr::en_passant_square`] stores the square of the piece to be captured.
+
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use crate::{
+    board_repr::BoardRepr,
+    pieces::{
+        movement::{CastlingFiles, CastlingSide},
+        Color, ColorPiece, CombinationPiece, Piece, UnitaryPiece,
+    },
+    pocket::Pockets,
+    square::{File, Rank, Square},
+    validation::PositionError,
+};
+
+/// The FEN of the usual starting position.
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// Error when parsing an invalid FEN string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseFenError {
+    /// The string doesn't have 4 or 6 space-separated fields, besides the checks given.
+    WrongFieldCount(usize),
+    /// The piece placement doesn't describe 8 ranks of 8 squares each.
+    InvalidBoard,
+    /// The piece placement contains something that is not a piece.
+    InvalidPiece(String),
+    /// The pieces in hand contain something that is not a piece, or a king,
+    /// or more of a piece than can be counted.
+    InvalidPocket(String),
+    InvalidSideToMove(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+    /// The checks given are not written as `+W+B` with two numbers.
+    InvalidChecksGiven(String),
+    /// The FEN is well-formed, but the position can't be played from.
+    InvalidPosition(Vec<PositionError>),
+}
+
+impl fmt::Display for ParseFenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseFenError::WrongFieldCount(n) => {
+                write!(f, "expected 4 or 6 fields in FEN, got {n}")
+            }
+            ParseFenError::InvalidBoard => f.write_str("invalid piece placement in FEN"),
+            ParseFenError::InvalidPiece(p) => write!(f, "invalid piece in FEN: {p:?}"),
+            ParseFenError::InvalidPocket(p) => write!(f, "invalid pieces in hand in FEN: {p:?}"),
+            ParseFenError::InvalidSideToMove(s) => write!(f, "invalid side to move in FEN: {s:?}"),
+            ParseFenError::InvalidCastling(s) => write!(f, "invalid castling rights in FEN: {s:?}"),
+            ParseFenError::InvalidEnPassant(s) => {
+                write!(f, "invalid en passant square in FEN: {s:?}")
+            }
+            ParseFenError::InvalidHalfmoveClock(s) => {
+                write!(f, "invalid halfmove clock in FEN: {s:?}")
+            }
+            ParseFenError::InvalidFullmoveNumber(s) => {
+                write!(f, "invalid fullmove number in FEN: {s:?}")
+            }
+            ParseFenError::InvalidChecksGiven(s) => {
+                write!(f, "invalid checks given in FEN: {s:?}")
+            }
+            ParseFenError::InvalidPosition(errors) => {
+                f.write_str("invalid position in FEN: ")?;
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{error}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseFenError {}
+
+/// Writes a piece the way it appears in the piece placement field.
+pub(crate) fn write_piece(out: &mut String, piece: ColorPiece) {
+    let case = |ch: char| match piece.color() {
+        Color::White => ch,
+        Color::Black => ch.to_ascii_lowercase(),
+    };
+    match piece.piece() {
+        Piece::Unitary(p) => out.push(case(p.char())),
+        Piece::Combination(combo) => {
+            out.push('(');
+            out.push(case(combo.first().char()));
+            out.push(case(combo.second().char()));
+            out.push(')');
+        }
+    }
+}
+
+/// Parses the letters of a single piece: one letter for a unitary piece,
+/// or two for a combination (without the parentheses).
+/// All the letters must have the same case, which determines the color.
+pub(crate) fn parse_piece_letters(letters: &str) -> Option<ColorPiece> {
+    let color = if letters.chars().all(|c| c.is_ascii_uppercase()) {
+        Color::White
+    } else if letters.chars().all(|c| c.is_ascii_lowercase()) {
+        Color::Black
+    } else {
+        return None;
+    };
+
+    let mut chars = letters.chars();
+    let piece = match (chars.next(), chars.next(), chars.next()) {
+        (Some(a), None, None) => Piece::Unitary(UnitaryPiece::from_char(a)?),
+        (Some(a), Some(b), None) => Piece::Combination(CombinationPiece::new(
+            UnitaryPiece::from_char(a)?,
+            UnitaryPiece::from_char(b)?,
+        )?),
+        _ => return None,
+    };
+
+    Some(match color {
+        Color::White => ColorPiece::White(piece),
+        Color::Black => ColorPiece::Black(piece),
+    })
+}
+
+/// Checks that castling rights are made of the letters that can appear in them,
+/// before the pieces are known, see [`parse_castling`].
+pub(crate) fn is_castling_syntax(castling: &str) -> bool {
+    castling == "-"
+        || (!castling.is_empty()
+            && castling
+                .chars()
+                .all(|ch| matches!(ch.to_ascii_uppercase(), 'K' | 'Q' | 'A'..='H')))
+}
+
+/// Parses castling rights written as in FEN, X-FEN or Shredder-FEN:
+/// `-`, or at most one right for each side of each king.
+/// Returns the rights along with the starting files of the kings and rooks they refer to,
+/// which are found from the pieces of the board.
+pub(crate) fn parse_castling(
+    castling: &str,
+    board: &BoardRepr,
+) -> Option<([bool; 4], [CastlingFiles; 2])> {
+    let mut rights = [false; 4];
+    let mut files = [CastlingFiles::STANDARD; 2];
+    if castling == "-" {
+        return Some((rights, files));
+    }
+    if !is_castling_syntax(castling) {
+        return None;
+    }
+    for ch in castling.chars() {
+        let color = if ch.is_ascii_uppercase() {
+            Color::White
+        } else {
+            Color::Black
+        };
+        // Without a king on the back rank the rights are invalid anyway,
+        // which the validation of the position reports
+        let back_rank = CastlingSide::back_rank(color);
+        let king = board
+            .try_king_square(color)
+            .filter(|king| king.rank() == back_rank)
+            .map_or(CastlingFiles::STANDARD.king, Square::file);
+
+        let (side, rook) = match ch.to_ascii_uppercase() {
+            'K' => (CastlingSide::KingSide, None),
+            'Q' => (CastlingSide::QueenSide, None),
+            letter => {
+                let rook = File::from_char(letter.to_ascii_lowercase())?;
+                match rook.cmp(&king) {
+                    Ordering::Greater => (CastlingSide::KingSide, Some(rook)),
+                    Ordering::Less => (CastlingSide::QueenSide, Some(rook)),
+                    Ordering::Equal => return None,
+                }
+            }
+        };
+        let rook = rook
+            .or_else(|| outermost_rook(board, color, side, king))
+            .unwrap_or(CastlingFiles::STANDARD.rook(side));
+
+        let idx = side.right_index(color);
+        if rights[idx] {
+            return None;
+        }
+        rights[idx] = true;
+        files[color as usize].king = king;
+        files[color as usize].rooks[side as usize] = rook;
+    }
+    Some((rights, files))
+}
+
+/// The file of the piece containing a rook that is furthest from the king
+/// on the given side of it, on the back rank.
+fn outermost_rook(board: &BoardRepr, color: Color, side: CastlingSide, king: File) -> Option<File> {
+    let back_rank = CastlingSide::back_rank(color);
+    let mut files = File::ALL.into_iter().filter(|file| match side {
+        CastlingSide::KingSide => *file > king,
+        CastlingSide::QueenSide => *file < king,
+    });
+    let is_rook = |file: &File| {
+        board[Square::from_coords(*file, back_rank)]
+            .is_some_and(|p| p.color() == color && p.piece().contains(UnitaryPiece::Rook))
+    };
+    match side {
+        CastlingSide::KingSide => files.rfind(is_rook),
+        CastlingSide::QueenSide => files.find(is_rook),
+    }
+}
+
+/// Parses the pieces in hand, written between the brackets after the piece placement.
+/// There can be at most [`u8::MAX`] of each piece.
+fn parse_pockets(pockets: &str) -> Option<Pockets> {
+    let mut parsed = Pockets::default();
+    for ch in pockets.chars() {
+        let piece = UnitaryPiece::from_char(ch).filter(|p| *p != UnitaryPiece::King)?;
+        let color = if ch.is_ascii_uppercase() {
+            Color::White
+        } else {
+            Color::Black
+        };
+        if !parsed.add(color, piece) {
+            return None;
+        }
+    }
+    Some(parsed)
+}
+
+/// Parses the checks given by each side, written as `+W+B`.
+fn parse_checks_given(checks: &str) -> Option<[u8; 2]> {
+    let (white, black) = checks.strip_prefix('+')?.split_once('+')?;
+    // Unlike `u8::from_str`, a sign is not allowed
+    let count = |count: &str| {
+        Some(count)
+            .filter(|count| count.bytes().all(|b| b.is_ascii_digit()))?
+            .parse()
+            .ok()
+    };
+    Some([count(white)?, count(black)?])
+}
+
+fn parse_placement(placement: &str) -> Result<[Option<ColorPiece>; 64], ParseFenError> {
+    let mut pieces = [None; 64];
+
+    let ranks: Vec<&str> = placement.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(ParseFenError::InvalidBoard);
+    }
+
+    // The first rank in the string is the eighth rank
+    for (rank_str, rank) in ranks.into_iter().zip(Rank::ALL.into_iter().rev()) {
+        let mut file_idx = 0;
+        let mut chars = rank_str.chars();
+        while let Some(ch) = chars.next() {
+            if let Some(skip) = ch.to_digit(10) {
+                if skip == 0 || skip > 8 {
+                    return Err(ParseFenError::InvalidBoard);
+                }
+                file_idx += skip;
+                continue;
+            }
+
+            let piece = if ch == '(' {
+                let (letters, rest) = chars
+                    .as_str()
+                    .split_once(')')
+                    .ok_or_else(|| ParseFenError::InvalidPiece(format!("({}", chars.as_str())))?;
+                chars = rest.chars();
+                parse_piece_letters(letters)
+                    .filter(|p| p.piece().is_combination())
+                    .ok_or_else(|| ParseFenError::InvalidPiece(format!("({letters})")))?
+            } else {
+                parse_piece_letters(&ch.to_string())
+                    .ok_or_else(|| ParseFenError::InvalidPiece(ch.to_string()))?
+            };
+
+            if file_idx >= 8 {
+                return Err(ParseFenError::InvalidBoard);
+            }
+            pieces[Square::from_coords(File::new(file_idx), rank) as usize] = Some(piece);
+            file_idx += 1;
+        }
+
+        if file_idx != 8 {
+            return Err(ParseFenError::InvalidBoard);
+        }
+    }
+
+    Ok(pieces)
+}
+
+impl BoardRepr {
+    /// Parses a position from its FEN, which may include combination pieces.
+    ///
+    /// The halfmove clock and fullmove number can be omitted,
+    /// in which case they are set to 0 and 1.
+    pub fn from_fen(fen: &str) -> Result<BoardRepr, ParseFenError> {
+        BoardRepr::parse_fen(fen, BoardRepr::validate)
+    }
+
+    /// Like [`BoardRepr::from_fen`], but a side may have no king,
+    /// see [`BoardRepr::validate_lenient`].
+    pub fn from_fen_lenient(fen: &str) -> Result<BoardRepr, ParseFenError> {
+        BoardRepr::parse_fen(fen, BoardRepr::validate_lenient)
+    }
+
+    fn parse_fen(
+        fen: &str,
+        validate: fn(&BoardRepr) -> Result<(), Vec<PositionError>>,
+    ) -> Result<BoardRepr, ParseFenError> {
+        let mut fields: Vec<&str> = fen.split_whitespace().collect();
+        let mut board = BoardRepr::empty();
+        if let Some(checks) = fields.last().filter(|field| field.starts_with('+')) {
+            board.checks_given = Some(
+                parse_checks_given(checks)
+                    .ok_or_else(|| ParseFenError::InvalidChecksGiven(checks.to_string()))?,
+            );
+            fields.pop();
+        }
+        if fields.len() != 4 && fields.len() != 6 {
+            return Err(ParseFenError::WrongFieldCount(fields.len()));
+        }
+
+        let placement = match fields[0].strip_suffix(']') {
+            Some(placement) => {
+                let (placement, pockets) = placement
+                    .split_once('[')
+                    .ok_or(ParseFenError::InvalidBoard)?;
+                board.pockets = Some(
+                    parse_pockets(pockets)
+                        .ok_or_else(|| ParseFenError::InvalidPocket(pockets.to_string()))?,
+                );
+                placement
+            }
+            None => fields[0],
+        };
+        board.set_pieces(&parse_placement(placement)?);
+
+        board.side_to_move = match fields[1] {
+            "w" => Color::White,
+            "b" => Color::Black,
+            other => return Err(ParseFenError::InvalidSideToMove(other.to_string())),
+        };
+
+        (board.castling_rights, board.castling_files) = parse_castling(fields[2], &board)
+            .ok_or_else(|| ParseFenError::InvalidCastling(fields[2].to_string()))?;
+
+        if fields[3] != "-" {
+            let invalid = || ParseFenError::InvalidEnPassant(fields[3].to_string());
+            let target: Square = fields[3].parse().map_err(|_| invalid())?;
+            // The piece that can be captured is just past the target square,
+            // from the point of view of the side to move
+            let pawn_square = match board.side_to_move {
+                Color::White if target.rank() == Rank::Sixth => target.down(),
+                Color::Black if target.rank() == Rank::Third => target.up(),
+                _ => None,
+            };
+            board.en_passant_square = Some(pawn_square.ok_or_else(invalid)?);
+        }
+
+        if fields.len() == 6 {
+            board.halfmove_clock = fields[4]
+                .parse()
+                .map_err(|_| ParseFenError::InvalidHalfmoveClock(fields[4].to_string()))?;
+            board.fullmove_number = fields[5]
+                .parse()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or_else(|| ParseFenError::InvalidFullmoveNumber(fields[5].to_string()))?;
+        }
+
+        validate(&board).map_err(ParseFenError::InvalidPosition)?;
+        board.refresh_zobrist_key();
+        Ok(board)
+    }
+
+    /// Writes this position as FEN, using the extended syntax for combination pieces.
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        for rank in Rank::ALL.into_iter().rev() {
+            let mut empty = 0;
+            for file in File::ALL {
+                match self[Square::from_coords(file, rank)] {
+                    None => empty += 1,
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        write_piece(&mut fen, piece);
+                    }
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank != Rank::First {
+                fen.push('/');
+            }
+        }
+
+        if let Some(pockets) = self.pockets {
+            fen.push('[');
+            for color in [Color::White, Color::Black] {
+                for (piece, count) in pockets.pieces(color) {
+                    let letter = match color {
+                        Color::White => piece.char(),
+                        Color::Black => piece.char().to_ascii_lowercase(),
+                    };
+                    fen.extend(std::iter::repeat_n(letter, count.into()));
+                }
+            }
+            fen.push(']');
+        }
+
+        fen.push(' ');
+        fen.push(match self.side_to_move {
+            Color::White => 'w',
+            Color::Black => 'b',
+        });
+
+        fen.push(' ');
+        if self.castling_rights.iter().any(|r| *r) {
+            for color in [Color::White, Color::Black] {
+                let files = self.castling_files[color as usize];
+                for side in CastlingSide::ALL {
+                    if !self.castling_rights[side.right_index(color)] {
+                        continue;
+                    }
+                    let rook = files.rook(side);
+                    let ch = if outermost_rook(self, color, side, files.king) == Some(rook) {
+                        match side {
+                            CastlingSide::KingSide => 'k',
+                            CastlingSide::QueenSide => 'q',
+                        }
+                    } else {
+                        rook.char()
+                    };
+                    fen.push(match color {
+                        Color::White => ch.to_ascii_uppercase(),
+                        Color::Black => ch,
+                    });
+                }
+            }
+        } else {
+            fen.push('-');
+        }
+
+        fen.push(' ');
+        let target = self
+            .en_passant_square
+            .and_then(|sq| match self.side_to_move {
+                Color::White => sq.up(),
+                Color::Black => sq.down(),
+            });
+        match target {
+            Some(target) => fen.push_str(&target.to_string()),
+            None => fen.push('-'),
+        }
+
+        fen.push_str(&format!(
+            " {} {}",
+            self.halfmove_clock, self.fullmove_number
+        ));
+        if let Some([white, black]) = self.checks_given {
+            fen.push_str(&format!(" +{white}+{black}"));
+        }
+        fen
+    }
+}
+
+impl FromStr for BoardRepr {
+    type Err = ParseFenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BoardRepr::from_fen(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_starting_position() {
+        assert_eq!(BoardRepr::default().to_fen(), STARTING_FEN);
+        assert_eq!(
+            BoardRepr::from_fen(STARTING_FEN).unwrap(),
+            BoardRepr::default()
+        );
+    }
+
+    #[test]
+    fn test_combinations_round_trip() {
+        let fen = "r1bqk2r/pp(np)ppppp/8/3(NP)4/3(bb)P3/8/PPP2PPP/(QR)3K2R b Kq e3 3 12";
+        let board = BoardRepr::from_fen(fen).unwrap();
+        assert_eq!(
+            board[Square::D5],
+            Some(
+                CombinationPiece::new(UnitaryPiece::Knight, UnitaryPiece::Pawn)
+                    .unwrap()
+                    .white()
+            )
+        );
+        assert_eq!(
+            board[Square::D4],
+            Some(
+                CombinationPiece::new(UnitaryPiece::Bishop, UnitaryPiece::Bishop)
+                    .unwrap()
+                    .black()
+            )
+        );
+        assert_eq!(board.en_passant_square, Some(Square::E4));
+        assert_eq!(board.castling_rights, [true, false, false, true]);
+        assert_eq!(board.halfmove_clock, 3);
+        assert_eq!(board.fullmove_number, 12);
+        assert_eq!(board.to_fen(), fen);
+
+        // Combination letters can be given in any order
+        let board = BoardRepr::from_fen("4k3/8/8/8/8/8/(pn)7/(RQ)3K3 w - -").unwrap();
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/(np)7/(QR)3K3 w - - 0 1");
+    }
+
+    #[test]
+    fn test_pockets() {
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R[NPPbp] w KQkq - 2 3";
+        let board = BoardRepr::from_fen(fen).unwrap();
+        let pockets = board.pockets.unwrap();
+        assert_eq!(pockets.count(Color::White, UnitaryPiece::Pawn), 2);
+        assert_eq!(pockets.count(Color::Black, UnitaryPiece::Bishop), 1);
+        assert_eq!(board.to_fen(), fen);
+
+        // Pockets with nothing in them are different from no pockets at all
+        let board = BoardRepr::from_fen(&STARTING_FEN.replacen(' ', "[] ", 1)).unwrap();
+        assert_eq!(board.pockets, Some(Pockets::default()));
+        assert_eq!(board.to_fen(), STARTING_FEN.replacen(' ', "[] ", 1));
+        assert_eq!(BoardRepr::default().pockets, None);
+    }
+
+    #[test]
+    fn test_xfen_castling() {
+        let shredder =
+            BoardRepr::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1");
+        assert_eq!(shredder.unwrap(), BoardRepr::default());
+
+        // The rook that castles isn't the outermost one, so it is named by its file
+        let fen = "4k3/8/8/8/8/8/8/R1R1K3 w C - 0 1";
+        let board = BoardRepr::from_fen(fen).unwrap();
+        assert_eq!(
+            board.castling_files[0].rook(CastlingSide::QueenSide),
+            File::C
+        );
+        assert_eq!(board.to_fen(), fen);
+        let board = BoardRepr::from_fen("4k3/8/8/8/8/8/8/R1R1K3 w Q - 0 1").unwrap();
+        assert_eq!(
+            board.castling_files[0].rook(CastlingSide::QueenSide),
+            File::A
+        );
+
+        let fen = "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1";
+        assert_eq!(BoardRepr::from_fen(fen).unwrap(), BoardRepr::chess960(0));
+        assert_eq!(
+            BoardRepr::from_fen("bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFhf - 0 1"),
+            Ok(BoardRepr::chess960(0))
+        );
+
+        for castling in ["KG", "E"] {
+            assert_eq!(
+                BoardRepr::from_fen(&format!("4k3/8/8/8/8/8/8/4K1RR w {castling} - 0 1")),
+                Err(ParseFenError::InvalidCastling(castling.into()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_clocks_after_moves() {
+        use crate::pieces::movement::Move;
+
+        let mut board = BoardRepr::default();
+        for (from, to) in [
+            (Square::E2, Square::E4),
+            (Square::E7, Square::E5),
+            (Square::G1, Square::F3),
+        ] {
+            board
+                .play(Move {
+                    from,
+                    to,
+                    which_half: None,
+                    drop: None,
+                })
+                .unwrap();
+        }
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+    }
+
+    #[test]
+    fn test_split_double_step_round_trip() {
+        use crate::pieces::movement::{get_all_legal_moves, Move};
+
+        let mut board = BoardRepr::default();
+        for (from, to) in [(Square::G1, Square::E2), (Square::A7, Square::A6)] {
+            board
+                .play(Move {
+                    from,
+                    to,
+                    which_half: None,
+                    drop: None,
+                })
+                .unwrap();
+        }
+        // The pawn half of the Knawn double-steps, and the knight stays behind
+        let split = get_all_legal_moves(&board, board.side_to_move)
+            .into_iter()
+            .find(|m| m.from == Square::E2 && m.to == Square::E4 && m.which_half.is_some())
+            .unwrap();
+        board.play(split).unwrap();
+
+        let fen = board.to_fen();
+        assert_eq!(
+            fen,
+            "rnbqkbnr/1ppppppp/p7/8/4P3/8/PPPPNPPP/RNBQKB1R b KQkq e3 0 2"
+        );
+        assert_eq!(BoardRepr::from_fen(&fen).unwrap().to_fen(), fen);
+    }
+
+    #[test]
+    fn test_errors() {
+        use ParseFenError as E;
+        let cases = [
+            ("8/8/8/8/8/8/8/8 w -", E::WrongFieldCount(3)),
+            ("8/8/8/8/8/8/8 w - -", E::InvalidBoard),
+            ("8/8/8/8/8/8/8/9 w - -", E::InvalidBoard),
+            ("8/8/8/8/8/8/8/7KK w - -", E::InvalidBoard),
+            ("8/8/8/8/8/8/8/7X w - -", E::InvalidPiece("X".into())),
+            ("8/8/8/8/8/8/8/6(Kq) w - -", E::InvalidPiece("(Kq)".into())),
+            ("8/8/8/8/8/8/8/6(KQ) w - -", E::InvalidPiece("(KQ)".into())),
+            (
+                "4k3/8/8/8/8/8/8/4K2(NR w K - 0 1",
+                E::InvalidPiece("(NR".into()),
+            ),
+            ("8/8/8/8/8/8/8/8[Qk] w - -", E::InvalidPocket("Qk".into())),
+            ("8/8/8/8/8/8/8/8Q] w - -", E::InvalidBoard),
+            ("8/8/8/8/8/8/8/8 x - -", E::InvalidSideToMove("x".into())),
+            ("8/8/8/8/8/8/8/8 w KK -", E::InvalidCastling("KK".into())),
+            ("8/8/8/8/8/8/8/8 w - e3", E::InvalidEnPassant("e3".into())),
+            (
+                "8/8/8/8/8/8/8/8 w - - x 1",
+                E::InvalidHalfmoveClock("x".into()),
+            ),
+            (
+                "8/8/8/8/8/8/8/8 w - - 0 0",
+                E::InvalidFullmoveNumber("0".into()),
+            ),
+            (
+                "8/8/8/8/8/8/8/8 w - - +1",
+                E::InvalidChecksGiven("+1".into()),
+            ),
+            (
+                "8/8/8/8/8/8/8/8 w - - 0 1 +1++2",
+                E::InvalidChecksGiven("+1++2".into()),
+            ),
+            ("8/8/8/8/8/8/8/8 w - - 0 +0+0", E::WrongFieldCount(5)),
+        ];
+        for (fen, err) in cases {
+            assert_eq!(BoardRepr::from_fen(fen), Err(err), "{fen}");
+        }
+
+        let pawns = "P".repeat(usize::from(u8::MAX));
+        let fen = format!("4k3/8/8/8/8/8/8/4K3[{pawns}] w - - 0 1");
+        assert!(BoardRepr::from_fen(&fen).is_ok());
+        let fen = format!("4k3/8/8/8/8/8/8/4K3[{pawns}P] w - - 0 1");
+        assert_eq!(
+            BoardRepr::from_fen(&fen),
+            Err(E::InvalidPocket(format!("{pawns}P")))
+        );
+    }
+}
@@ -0,0 +1,419 @@
+//! A game in progress: the current position, the moves that led to it,
+//! and the rules for when the game ends.
+
+use std::fmt;
+
+use crate::{
+    board_repr::{BoardRepr, IllegalMoveError, UndoInfo},
+    game_record::{GameRecord, GameResult, TAG_VARIANT},
+    pieces::{
+        movement::{get_all_legal_moves, Move},
+        Color,
+    },
+    variant::Variant,
+};
+
+/// How many times a position must occur for the game to be drawn by repetition.
+pub const REPETITION_LIMIT: usize = 3;
+
+/// How many halfmoves without a capture or pawn move draw the game.
+pub const FIFTY_MOVE_LIMIT: u32 = 100;
+
+/// How a game has ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GameOutcome {
+    Win { winner: Color, reason: WinReason },
+    Draw { reason: DrawReason },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WinReason {
+    /// The loser's king is in check, and they have no legal moves.
+    Checkmate,
+    /// The winner's king reached the center, see [`crate::variant::KingOfTheHill`].
+    KingOfTheHill,
+    /// The winner gave check for the third time, see [`crate::variant::ThreeCheck`].
+    ThreeChecks,
+    /// The winner's king reached the eighth rank first, see [`crate::variant::RacingKings`].
+    RaceWon,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DrawReason {
+    /// The side to move has no legal moves, but is not in check.
+    Stalemate,
+    /// The same position has occurred three times.
+    ThreefoldRepetition,
+    /// Fifty moves by each side have been played without a capture or a pawn move.
+    FiftyMoveRule,
+    /// Neither side has enough pieces left to checkmate.
+    InsufficientMaterial,
+    /// Both kings reached the eighth rank, see [`crate::variant::RacingKings`].
+    BothKingsReachedGoal,
+}
+
+impl GameOutcome {
+    pub fn winner(self) -> Option<Color> {
+        match self {
+            GameOutcome::Win { winner, .. } => Some(winner),
+            GameOutcome::Draw { .. } => None,
+        }
+    }
+
+    /// The result to write in a [`GameRecord`].
+    pub fn result(self) -> GameResult {
+        match self.winner() {
+            Some(Color::White) => GameResult::WhiteWins,
+            Some(Color::Black) => GameResult::BlackWins,
+            None => GameResult::Draw,
+        }
+    }
+}
+
+impl fmt::Display for GameOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameOutcome::Win { winner, reason } => {
+                let winner = match winner {
+                    Color::White => "White",
+                    Color::Black => "Black",
+                };
+                match reason {
+                    WinReason::Checkmate => write!(f, "{winner} wins by checkmate"),
+                    WinReason::KingOfTheHill => {
+                        write!(f, "{winner} wins by reaching the center")
+                    }
+                    WinReason::ThreeChecks => write!(f, "{winner} wins by giving three checks"),
+                    WinReason::RaceWon => write!(f, "{winner} wins the race"),
+                }
+            }
+            GameOutcome::Draw { reason } => match reason {
+                DrawReason::Stalemate => f.write_str("Draw by stalemate"),
+                DrawReason::ThreefoldRepetition => f.write_str("Draw by threefold repetition"),
+                DrawReason::FiftyMoveRule => f.write_str("Draw by the fifty-move rule"),
+                DrawReason::InsufficientMaterial => f.write_str("Draw by insufficient material"),
+                DrawReason::BothKingsReachedGoal => {
+                    f.write_str("Draw as both kings reached the last rank")
+                }
+            },
+        }
+    }
+}
+
+/// A game, with the moves played since its starting position.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Game {
+    variant: &'static dyn Variant,
+    start: BoardRepr,
+    board: BoardRepr,
+
+    /// What is needed to take back each move that was played, in order.
+    undo_stack: Vec<UndoInfo>,
+
+    /// The Zobrist keys of all the positions in the game, including the starting and current ones.
+    history: Vec<u64>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new(BoardRepr::default())
+    }
+}
+
+impl Game {
+    /// Starts a game of the standard variant from the given position.
+    pub fn new(start: BoardRepr) -> Self {
+        Self::from_position(start, Default::default())
+    }
+
+    /// Starts a game of the variant from its usual starting position.
+    pub fn from_variant(variant: &'static dyn Variant) -> Self {
+        Self::from_position(variant.start_position(), variant)
+    }
+
+    /// Starts a game of the variant from the given position.
+    pub fn from_position(start: BoardRepr, variant: &'static dyn Variant) -> Self {
+        Self {
+            variant,
+            start,
+            board: start,
+            undo_stack: vec![],
+            history: vec![start.zobrist_key()],
+        }
+    }
+
+    /// The current position.
+    pub fn board(&self) -> &BoardRepr {
+        &self.board
+    }
+
+    pub fn start_position(&self) -> &BoardRepr {
+        &self.start
+    }
+
+    pub fn variant(&self) -> &'static dyn Variant {
+        self.variant
+    }
+
+    /// Every position of the game in order, from the starting one to the current one.
+    pub fn positions(&self) -> impl Iterator<Item = BoardRepr> + '_ {
+        let mut board = self.start;
+        std::iter::once(self.start).chain(self.moves().map(move |move_| {
+            board.make_move(move_);
+            board
+        }))
+    }
+
+    /// The moves that can be played in the current position, in this variant.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        get_all_legal_moves(&self.board, self.board.side_to_move)
+            .into_iter()
+            .filter(|move_| self.variant.allows_move(&self.board, *move_))
+            .collect()
+    }
+
+    /// The moves played so far, in order.
+    pub fn moves(&self) -> impl Iterator<Item = Move> + '_ {
+        self.undo_stack.iter().map(|undo| undo.move_)
+    }
+
+    /// Plays a move, if it is legal in the current position and the variant allows it.
+    pub fn play(&mut self, move_: Move) -> Result<(), IllegalMoveError> {
+        self.board.validate_move(move_)?;
+        if !self.variant.allows_move(&self.board, move_) {
+            return Err(IllegalMoveError::ForbiddenByVariant {
+                from: move_.from,
+                to: move_.to,
+            });
+        }
+        self.make_move(move_);
+        Ok(())
+    }
+
+    /// Plays a move that is known to be legal, such as one from [`Game::legal_moves`].
+    pub(crate) fn make_move(&mut self, move_: Move) {
+        self.undo_stack.push(self.board.make_move(move_));
+        self.history.push(self.board.zobrist_key());
+    }
+
+    /// Takes back the last move, and returns it.
+    pub fn undo(&mut self) -> Option<Move> {
+        let undo = self.undo_stack.pop()?;
+        self.history.pop();
+        self.board.unmake_move(undo);
+        Some(undo.move_)
+    }
+
+    /// How many times the current position has occurred in the game, including now.
+    pub fn repetition_count(&self) -> usize {
+        // Positions before the last capture or pawn move can't be repeated,
+        // so only the ones since then need to be checked.
+        // The key covers the castling files as well as the rights, so it tells Chess960 positions apart
+        let key = self.board.zobrist_key();
+        self.history
+            .iter()
+            .rev()
+            .take(self.board.halfmove_clock as usize + 1)
+            .filter(|k| **k == key)
+            .count()
+    }
+
+    /// Checks whether the game is over, and how.
+    pub fn outcome(&self) -> Option<GameOutcome> {
+        if let Some(outcome) = self.variant.outcome(self) {
+            return Some(outcome);
+        }
+
+        let side = self.board.side_to_move;
+        if self.legal_moves().is_empty() {
+            return Some(if self.board.king_in_check(side) {
+                GameOutcome::Win {
+                    winner: side.opposite(),
+                    reason: WinReason::Checkmate,
+                }
+            } else {
+                GameOutcome::Draw {
+                    reason: DrawReason::Stalemate,
+                }
+            });
+        }
+
+        let reason = if self
+            .variant
+            .has_insufficient_material(&self.board, Color::White)
+            && self
+                .variant
+                .has_insufficient_material(&self.board, Color::Black)
+        {
+            DrawReason::InsufficientMaterial
+        } else if self.repetition_count() >= REPETITION_LIMIT {
+            DrawReason::ThreefoldRepetition
+        } else if self.board.halfmove_clock >= FIFTY_MOVE_LIMIT {
+            DrawReason::FiftyMoveRule
+        } else {
+            return None;
+        };
+        Some(GameOutcome::Draw { reason })
+    }
+
+    /// Makes a record of the moves of this game, with its result if it is over.
+    pub fn to_record(&self) -> GameRecord {
+        let moves: Vec<Move> = self.moves().collect();
+        let result = self
+            .outcome()
+            .map_or(GameResult::Unknown, GameOutcome::result);
+        let mut record = GameRecord::from_moves(self.start, &moves, result);
+        if self.variant.name() != crate::variant::Standard.name() {
+            record.set_tag(TAG_VARIANT, self.variant.name());
+        }
+        record
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play_san(game: &mut Game, moves: &[&str]) {
+        for san in moves {
+            let move_ = game.board().parse_san(san).unwrap();
+            game.play(move_).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_checkmate() {
+        let mut game = Game::default();
+        play_san(&mut game, &["f3", "e5", "g4"]);
+        assert_eq!(game.outcome(), None);
+        play_san(&mut game, &["Qh4"]);
+        assert_eq!(
+            game.outcome(),
+            Some(GameOutcome::Win {
+                winner: Color::Black,
+                reason: WinReason::Checkmate
+            })
+        );
+        assert_eq!(
+            game.to_record().to_pgn().unwrap(),
+            "[Result \"0-1\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n"
+        );
+    }
+
+    #[test]
+    fn test_split_escapes_mate() {
+        // Moving the pinned rook-knight as a whole would expose the king to the bishop,
+        // but either half can block the check and leave the other half pinned
+        let game =
+            Game::new(BoardRepr::from_fen("b1k5/8/8/8/5n2/5(RN)p1/8/r6K w - - 0 1").unwrap());
+        assert!(game.board().king_in_check(Color::White));
+        assert_eq!(game.outcome(), None);
+    }
+
+    #[test]
+    fn test_stalemate_and_insufficient_material() {
+        let game = Game::new(BoardRepr::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap());
+        assert_eq!(
+            game.outcome(),
+            Some(GameOutcome::Draw {
+                reason: DrawReason::Stalemate
+            })
+        );
+
+        let game = Game::new(BoardRepr::from_fen("7k/8/6K1/8/8/8/8/8 b - - 0 1").unwrap());
+        assert_eq!(
+            game.outcome(),
+            Some(GameOutcome::Draw {
+                reason: DrawReason::InsufficientMaterial
+            })
+        );
+    }
+
+    #[test]
+    fn test_repetition() {
+        let mut game = Game::default();
+        let shuffle = ["Nf3", "Nf6", "Ng1", "Ng8"];
+        play_san(&mut game, &shuffle);
+        assert_eq!(game.repetition_count(), 2);
+        assert_eq!(game.outcome(), None);
+
+        play_san(&mut game, &shuffle);
+        assert_eq!(game.repetition_count(), 3);
+        assert_eq!(
+            game.outcome(),
+            Some(GameOutcome::Draw {
+                reason: DrawReason::ThreefoldRepetition
+            })
+        );
+
+        // Taking back a move leaves the repeated position
+        assert!(game.undo().is_some());
+        assert_eq!(game.outcome(), None);
+        assert_eq!(game.moves().count(), 7);
+    }
+
+    #[test]
+    fn test_chess960_repetition() {
+        // The rooks coming back to b1 and b8 don't repeat the start,
+        // since the castling rights with them are lost
+        let mut game =
+            Game::new(BoardRepr::from_fen("1r4kr/p7/8/8/8/8/P7/1R4KR w HBhb - 0 1").unwrap());
+        let shuffle = ["Rb2", "Rb7", "Rb1", "Rb8"];
+        play_san(&mut game, &shuffle);
+        assert_ne!(game.board(), game.start_position());
+        assert_eq!(game.repetition_count(), 1);
+
+        play_san(&mut game, &shuffle);
+        play_san(&mut game, &shuffle);
+        assert_eq!(game.repetition_count(), 3);
+        assert_eq!(
+            game.outcome(),
+            Some(GameOutcome::Draw {
+                reason: DrawReason::ThreefoldRepetition
+            })
+        );
+    }
+
+    #[test]
+    fn test_fifty_move_rule() {
+        let mut game =
+            Game::new(BoardRepr::from_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 99 80").unwrap());
+        assert_eq!(game.outcome(), None);
+        play_san(&mut game, &["Ra2"]);
+        assert_eq!(
+            game.outcome(),
+            Some(GameOutcome::Draw {
+                reason: DrawReason::FiftyMoveRule
+            })
+        );
+
+        // A pawn move resets the count
+        game.undo();
+        play_san(&mut game, &["e3"]);
+        assert_eq!(game.board().halfmove_clock, 0);
+        assert_eq!(game.outcome(), None);
+    }
+
+    #[test]
+    fn test_illegal_moves_are_rejected() {
+        // The pinned knight can't move
+        let mut game = Game::new(BoardRepr::from_fen("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1").unwrap());
+        let move_ = Move {
+            from: crate::square::Square::E2,
+            to: crate::square::Square::C3,
+            which_half: None,
+            drop: None,
+        };
+        assert_eq!(
+            game.play(move_),
+            Err(IllegalMoveError::LeavesKingInCheck {
+                from: move_.from,
+                to: move_.to
+            })
+        );
+        assert_eq!(game.moves().count(), 0);
+    }
+}
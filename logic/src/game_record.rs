@@ -0,0 +1,698 @@
+//! A PGN-style text format for recording games.
+//!
+//! A record starts with tag pairs like `[White "Alice"]`, followed by the movetext:
+//! moves in algebraic notation (see [`crate::san`]), with move numbers,
+//! `{comments}` or `; comments` to the end of the line, NAGs like `$1`, and nested `(variations)`,
+//! ending with the game result.
+//!
+//! Moves are checked for legality while reading, in the variant named by the Variant tag if there is one,
+//! and stored as [`Move`]s,
+//! so writing a record that was read back produces the same text
+//! as long as the original was written in the canonical form that [`GameRecord::to_pgn`] produces.
+
+use std::fmt;
+
+use crate::{
+    board_repr::BoardRepr,
+    fen::ParseFenError,
+    pieces::{movement::Move, Color},
+    rules::RuleSet,
+    san::ParseSanError,
+    variant::{self, Variant},
+};
+
+pub const TAG_WHITE: &str = "White";
+pub const TAG_BLACK: &str = "Black";
+pub const TAG_RESULT: &str = "Result";
+pub const TAG_RULES: &str = "Rules";
+pub const TAG_TIME_CONTROL: &str = "TimeControl";
+pub const TAG_FEN: &str = "FEN";
+pub const TAG_VARIANT: &str = "Variant";
+
+/// How a game ended, as written at the end of the movetext.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    /// The game is still going, or the result is unknown.
+    #[default]
+    Unknown,
+}
+
+impl GameResult {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unknown => "*",
+        }
+    }
+
+    pub fn from_token(s: &str) -> Option<GameResult> {
+        match s {
+            "1-0" => Some(GameResult::WhiteWins),
+            "0-1" => Some(GameResult::BlackWins),
+            "1/2-1/2" => Some(GameResult::Draw),
+            "*" => Some(GameResult::Unknown),
+            _ => None,
+        }
+    }
+
+    pub fn winner(self) -> Option<Color> {
+        match self {
+            GameResult::WhiteWins => Some(Color::White),
+            GameResult::BlackWins => Some(Color::Black),
+            GameResult::Draw | GameResult::Unknown => None,
+        }
+    }
+}
+
+/// A sequence of moves: either the main line of the game, or a variation.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct Line {
+    /// Comments that come before the first move of the line.
+    /// See [`MoveNode::comments`] for what they can hold.
+    pub comments: Vec<String>,
+    pub moves: Vec<MoveNode>,
+}
+
+/// A move in a line, with its annotations.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MoveNode {
+    pub move_: Move,
+    /// Numeric annotation glyphs, like 1 for "good move" (`$1` or `!`).
+    pub nags: Vec<u8>,
+    /// Comments that come after the move.
+    /// They are written in braces, or after a `;` up to the end of the line if they contain a `}`,
+    /// which would end a comment in braces early.
+    /// A comment with both a `}` and a line break can't be written, see [`WriteGameRecordError`].
+    pub comments: Vec<String>,
+    /// Alternatives to this move, each starting from the position before it.
+    pub variations: Vec<Line>,
+}
+
+impl MoveNode {
+    pub fn new(move_: Move) -> Self {
+        Self {
+            move_,
+            nags: vec![],
+            comments: vec![],
+            variations: vec![],
+        }
+    }
+}
+
+/// A recorded game, with its tag pairs and annotated moves.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct GameRecord {
+    /// The tag pairs, in the order they are written.
+    pub tags: Vec<(String, String)>,
+    pub mainline: Line,
+    pub result: GameResult,
+}
+
+/// Error when reading a game record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseGameRecordError {
+    /// A line in the tag section is not a valid tag pair.
+    InvalidTag(String),
+    /// The FEN tag doesn't hold a valid position.
+    InvalidFen(ParseFenError),
+    /// The Rules tag doesn't name one of the [`RuleSet::PRESETS`].
+    UnknownRules(String),
+    /// The Variant tag doesn't name one of the variants in [`variant::ALL`].
+    UnknownVariant(String),
+    /// A move couldn't be read, or is not legal in its position.
+    InvalidMove(ParseSanError),
+    /// A comment is opened with `{` but never closed.
+    UnterminatedComment,
+    /// A variation is opened but never closed, or closed without being opened,
+    /// or it is opened before any move that it could be an alternative to.
+    UnbalancedVariation,
+    /// A NAG doesn't follow a move, or is not a number from 0 to 255.
+    InvalidNag(String),
+    /// Something follows the game result.
+    TrailingText(String),
+}
+
+impl fmt::Display for ParseGameRecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseGameRecordError::InvalidTag(line) => write!(f, "invalid tag pair: {line:?}"),
+            ParseGameRecordError::InvalidFen(e) => write!(f, "invalid FEN tag: {e}"),
+            ParseGameRecordError::UnknownRules(name) => write!(f, "unknown rule set: {name:?}"),
+            ParseGameRecordError::UnknownVariant(name) => write!(f, "unknown variant: {name:?}"),
+            ParseGameRecordError::InvalidMove(e) => e.fmt(f),
+            ParseGameRecordError::UnterminatedComment => f.write_str("unterminated comment"),
+            ParseGameRecordError::UnbalancedVariation => f.write_str("unbalanced variation"),
+            ParseGameRecordError::InvalidNag(nag) => write!(f, "invalid NAG: {nag:?}"),
+            ParseGameRecordError::TrailingText(text) => {
+                write!(f, "unexpected text after the game result: {text:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseGameRecordError {}
+
+/// Error when writing a game record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WriteGameRecordError {
+    /// A comment contains both a `}` and a line break,
+    /// so it would end early in braces and after a `;` alike.
+    UnwritableComment(String),
+}
+
+impl fmt::Display for WriteGameRecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteGameRecordError::UnwritableComment(comment) => {
+                write!(f, "comment can't be written: {comment:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WriteGameRecordError {}
+
+impl From<ParseSanError> for ParseGameRecordError {
+    fn from(e: ParseSanError) -> Self {
+        ParseGameRecordError::InvalidMove(e)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token<'a> {
+    Comment(&'a str),
+    Nag(u8),
+    OpenVariation,
+    CloseVariation,
+    Result(GameResult),
+    Move(&'a str),
+}
+
+fn tokenize(movetext: &str) -> Result<Vec<Token<'_>>, ParseGameRecordError> {
+    let mut tokens = vec![];
+    let mut rest = movetext;
+
+    loop {
+        rest = rest.trim_start();
+        let Some(ch) = rest.chars().next() else {
+            break;
+        };
+        match ch {
+            '{' => {
+                let end = rest
+                    .find('}')
+                    .ok_or(ParseGameRecordError::UnterminatedComment)?;
+                tokens.push(Token::Comment(rest[1..end].trim()));
+                rest = &rest[end + 1..];
+            }
+            ';' => {
+                // A comment until the end of the line
+                let end = rest.find('\n').unwrap_or(rest.len());
+                tokens.push(Token::Comment(rest[1..end].trim()));
+                rest = &rest[end..];
+            }
+            '(' => {
+                tokens.push(Token::OpenVariation);
+                rest = &rest[1..];
+            }
+            ')' => {
+                tokens.push(Token::CloseVariation);
+                rest = &rest[1..];
+            }
+            _ => {
+                let end = rest
+                    .find(|c: char| c.is_whitespace() || "{};()".contains(c))
+                    .unwrap_or(rest.len());
+                let word = &rest[..end];
+                rest = &rest[end..];
+
+                if let Some(nag) = word.strip_prefix('$') {
+                    let nag = nag
+                        .parse()
+                        .map_err(|_| ParseGameRecordError::InvalidNag(word.to_string()))?;
+                    tokens.push(Token::Nag(nag));
+                } else if let Some(result) = GameResult::from_token(word) {
+                    tokens.push(Token::Result(result));
+                } else if word == "e.p." {
+                    // En passant captures may be marked with a separate "e.p.", which carries no information
+                } else {
+                    // Skip the move number, which may be directly followed by the move
+                    let after_digits = word.trim_start_matches(|c: char| c.is_ascii_digit());
+                    let word = if after_digits.starts_with('.') || after_digits.is_empty() {
+                        after_digits.trim_start_matches('.')
+                    } else {
+                        // The digits are part of the move (like "0-0")
+                        word
+                    };
+                    if !word.is_empty() {
+                        push_move_with_glyphs(&mut tokens, word);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Pushes a move token, turning the traditional suffixes like "!?" into NAGs.
+fn push_move_with_glyphs<'a>(tokens: &mut Vec<Token<'a>>, word: &'a str) {
+    let san = word.trim_end_matches(['!', '?']);
+    tokens.push(Token::Move(san));
+    let nag = match &word[san.len()..] {
+        "!" => 1,
+        "?" => 2,
+        "!!" => 3,
+        "??" => 4,
+        "!?" => 5,
+        "?!" => 6,
+        _ => return,
+    };
+    tokens.push(Token::Nag(nag));
+}
+
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let (name, value) = inner.split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => unescaped.push(chars.next()?),
+            '"' => return None,
+            _ => unescaped.push(ch),
+        }
+    }
+    Some((name.to_string(), unescaped))
+}
+
+struct LineParser<'a, 't> {
+    tokens: &'t [Token<'a>],
+    pos: usize,
+    variant: &'static dyn Variant,
+}
+
+impl LineParser<'_, '_> {
+    /// Parses moves until the end of the current line,
+    /// which is either a closing parenthesis, a result, or the end of the text.
+    fn parse_line(&mut self, mut board: BoardRepr) -> Result<Line, ParseGameRecordError> {
+        let mut line = Line::default();
+        let mut board_before_last = None;
+
+        while let Some(token) = self.tokens.get(self.pos) {
+            match token {
+                Token::Comment(comment) => match line.moves.last_mut() {
+                    Some(node) => node.comments.push(comment.to_string()),
+                    None => line.comments.push(comment.to_string()),
+                },
+                Token::Nag(nag) => match line.moves.last_mut() {
+                    Some(node) => node.nags.push(*nag),
+                    None => return Err(ParseGameRecordError::InvalidNag(format!("${nag}"))),
+                },
+                Token::OpenVariation => {
+                    let board_before_last =
+                        board_before_last.ok_or(ParseGameRecordError::UnbalancedVariation)?;
+                    self.pos += 1;
+                    let variation = self.parse_line(board_before_last)?;
+                    if self.tokens.get(self.pos) != Some(&Token::CloseVariation) {
+                        return Err(ParseGameRecordError::UnbalancedVariation);
+                    }
+                    line.moves.last_mut().unwrap().variations.push(variation);
+                }
+                Token::CloseVariation | Token::Result(_) => return Ok(line),
+                Token::Move(san) => {
+                    let move_ = board.parse_san(san)?;
+                    let illegal = || {
+                        ParseGameRecordError::InvalidMove(ParseSanError::IllegalMove(
+                            san.to_string(),
+                        ))
+                    };
+                    if !self.variant.allows_move(&board, move_) {
+                        return Err(illegal());
+                    }
+                    board_before_last = Some(board);
+                    board.play(move_).map_err(|_| illegal())?;
+                    line.moves.push(MoveNode::new(move_));
+                }
+            }
+            self.pos += 1;
+        }
+
+        Ok(line)
+    }
+}
+
+/// Writes a comment in braces, or up to the end of the line if it contains a closing brace.
+fn write_comment(out: &mut Vec<String>, comment: &str) -> Result<(), WriteGameRecordError> {
+    match (comment.contains('}'), comment.contains('\n')) {
+        (false, _) => out.push(format!("{{{comment}}}")),
+        (true, false) => out.push(format!("; {comment}\n")),
+        (true, true) => return Err(WriteGameRecordError::UnwritableComment(comment.to_string())),
+    }
+    Ok(())
+}
+
+/// Joins the written tokens with spaces, except after a comment that ends the line.
+fn join_tokens(tokens: &[String]) -> String {
+    let mut text = String::new();
+    for token in tokens {
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push(' ');
+        }
+        text.push_str(token);
+    }
+    text
+}
+
+fn write_line(
+    out: &mut Vec<String>,
+    line: &Line,
+    mut board: BoardRepr,
+) -> Result<(), WriteGameRecordError> {
+    for comment in &line.comments {
+        write_comment(out, comment)?;
+    }
+
+    // Black's moves only get a number if it isn't clear from the previous move
+    let mut needs_number = true;
+    for node in &line.moves {
+        match board.side_to_move {
+            Color::White => out.push(format!("{}.", board.fullmove_number)),
+            Color::Black if needs_number => out.push(format!("{}...", board.fullmove_number)),
+            Color::Black => {}
+        }
+
+        out.push(node.move_.to_san(&board));
+        for nag in &node.nags {
+            out.push(format!("${nag}"));
+        }
+        for comment in &node.comments {
+            write_comment(out, comment)?;
+        }
+        for variation in &node.variations {
+            let mut inner = vec![];
+            write_line(&mut inner, variation, board)?;
+            out.push(format!("({})", join_tokens(&inner)));
+        }
+        needs_number = !node.comments.is_empty() || !node.variations.is_empty();
+
+        board
+            .play(node.move_)
+            .expect("game record contains an illegal move");
+    }
+    Ok(())
+}
+
+impl GameRecord {
+    /// Makes a record of a game without any annotations.
+    ///
+    /// The rules of the starting position are recorded if they are one of the presets,
+    /// other than the default one.
+    pub fn from_moves(start: BoardRepr, moves: &[Move], result: GameResult) -> Self {
+        let mut record = GameRecord {
+            tags: vec![],
+            mainline: Line {
+                comments: vec![],
+                moves: moves.iter().copied().map(MoveNode::new).collect(),
+            },
+            result,
+        };
+        if start.rules != RuleSet::default() {
+            if let Some(name) = start.rules.name() {
+                record.set_tag(TAG_RULES, name);
+            }
+        }
+        let mut standard_start = BoardRepr::default();
+        standard_start.rules = start.rules;
+        if start != standard_start {
+            record.set_tag("SetUp", "1");
+            record.set_tag(TAG_FEN, &start.to_fen());
+        }
+        record.set_tag(TAG_RESULT, result.as_str());
+        record
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Sets the value of a tag, replacing the old value or adding the tag at the end.
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// The variant named in the Variant tag, or the standard game if there is none.
+    pub fn variant(&self) -> Result<&'static dyn Variant, ParseGameRecordError> {
+        match self.tag(TAG_VARIANT) {
+            Some(name) => variant::from_name(name)
+                .ok_or_else(|| ParseGameRecordError::UnknownVariant(name.to_string())),
+            None => Ok(Default::default()),
+        }
+    }
+
+    /// The position the game starts from: either the one in the FEN tag,
+    /// or the usual starting position of the variant,
+    /// played by the rules named in the Rules tag.
+    pub fn start_position(&self) -> Result<BoardRepr, ParseGameRecordError> {
+        let mut board = match self.tag(TAG_FEN) {
+            Some(fen) => BoardRepr::from_fen(fen).map_err(ParseGameRecordError::InvalidFen)?,
+            None => self.variant()?.start_position(),
+        };
+        if let Some(name) = self.tag(TAG_RULES) {
+            board.rules = RuleSet::from_name(name)
+                .ok_or_else(|| ParseGameRecordError::UnknownRules(name.to_string()))?;
+        }
+        Ok(board)
+    }
+
+    /// The moves of the main line, without annotations.
+    pub fn mainline_moves(&self) -> Vec<Move> {
+        self.mainline.moves.iter().map(|node| node.move_).collect()
+    }
+
+    /// Reads a game record from its text form.
+    pub fn from_pgn(pgn: &str) -> Result<GameRecord, ParseGameRecordError> {
+        let mut record = GameRecord::default();
+
+        // The tag pairs are each on their own line,
+        // and the movetext starts at the first line that isn't a tag pair
+        let mut rest = pgn.trim_start();
+        while rest.starts_with('[') {
+            let (line, next) = rest.split_once('\n').unwrap_or((rest, ""));
+            let tag = parse_tag(line.trim())
+                .ok_or_else(|| ParseGameRecordError::InvalidTag(line.to_string()))?;
+            record.tags.push(tag);
+            rest = next.trim_start();
+        }
+
+        let board = record.start_position()?;
+        let tokens = tokenize(rest)?;
+        let mut parser = LineParser {
+            tokens: &tokens,
+            pos: 0,
+            variant: record.variant()?,
+        };
+        record.mainline = parser.parse_line(board)?;
+
+        match tokens.get(parser.pos) {
+            None => {}
+            Some(Token::Result(result)) => {
+                record.result = *result;
+                if let Some(trailing) = tokens.get(parser.pos + 1) {
+                    return Err(ParseGameRecordError::TrailingText(format!("{trailing:?}")));
+                }
+            }
+            Some(_) => return Err(ParseGameRecordError::UnbalancedVariation),
+        }
+
+        Ok(record)
+    }
+
+    /// Writes this record in its text form.
+    ///
+    /// Fails if a comment can't be written so that it reads back the same,
+    /// see [`MoveNode::comments`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the FEN or Rules tag is invalid, or if a move is illegal in its position.
+    pub fn to_pgn(&self) -> Result<String, WriteGameRecordError> {
+        let mut pgn = String::new();
+        for (name, value) in &self.tags {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            pgn.push_str(&format!("[{name} \"{value}\"]\n"));
+        }
+        if !self.tags.is_empty() {
+            pgn.push('\n');
+        }
+
+        let board = self
+            .start_position()
+            .expect("game record has an invalid starting position");
+        let mut tokens = vec![];
+        write_line(&mut tokens, &self.mainline, board)?;
+        tokens.push(self.result.as_str().to_string());
+        pgn.push_str(&join_tokens(&tokens));
+        pgn.push('\n');
+        Ok(pgn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::square::Square;
+
+    const ANNOTATED: &str = r#"[Event "Casual game"]
+[White "Alice \"The Merger\""]
+[Black "Bob"]
+[Result "0-1"]
+[Rules "this project"]
+[TimeControl "300+5"]
+
+{Opening comment} 1. e4 $1 e5 2. N+d2 {Knawn} (2. Nf3 Nc6 (2... d6 3. d4) 3. Bc4 $5) 2... Nc6 3. NPd4 0-1
+"#;
+
+    #[test]
+    fn test_round_trip() {
+        let record = GameRecord::from_pgn(ANNOTATED).unwrap();
+        assert_eq!(record.tag(TAG_WHITE), Some("Alice \"The Merger\""));
+        assert_eq!(record.tag(TAG_TIME_CONTROL), Some("300+5"));
+        assert_eq!(record.result, GameResult::BlackWins);
+        assert_eq!(record.mainline.comments, vec!["Opening comment"]);
+        assert_eq!(record.mainline.moves.len(), 5);
+
+        let knawn = &record.mainline.moves[2];
+        assert_eq!(
+            knawn.move_,
+            Move {
+                from: Square::B1,
+                to: Square::D2,
+                which_half: None,
+                drop: None,
+            }
+        );
+        assert_eq!(knawn.comments, vec!["Knawn"]);
+        assert_eq!(knawn.variations.len(), 1);
+        assert_eq!(knawn.variations[0].moves[1].variations.len(), 1);
+        assert_eq!(knawn.variations[0].moves[2].nags, vec![5]);
+
+        assert_eq!(record.to_pgn().unwrap(), ANNOTATED);
+        assert_eq!(
+            GameRecord::from_pgn(&record.to_pgn().unwrap()).unwrap(),
+            record
+        );
+    }
+
+    #[test]
+    fn test_closing_brace_in_comment() {
+        // A closing brace would end a comment in braces, so the comment goes to the end of the line
+        let mut record = GameRecord::from_pgn(ANNOTATED).unwrap();
+        record.mainline.comments = vec!["1-0} 1. d4".to_string()];
+        record.mainline.moves[2].variations[0].moves[0]
+            .comments
+            .push("{x}".to_string());
+        let pgn = record.to_pgn().unwrap();
+        assert!(pgn.contains("; 1-0} 1. d4\n1. e4"), "{pgn}");
+        assert_eq!(GameRecord::from_pgn(&pgn).unwrap(), record);
+
+        // With a line break as well, it can't be written either way
+        record.mainline.comments = vec!["}\n1-0".to_string()];
+        assert_eq!(
+            record.to_pgn(),
+            Err(WriteGameRecordError::UnwritableComment(
+                "}\n1-0".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_lenient_input() {
+        let record =
+            GameRecord::from_pgn("1.e4 e5 2.Nf3!? ; a line comment\n Nc6 3.Bb5 a6 4.O-O *")
+                .unwrap();
+        assert_eq!(record.mainline.moves.len(), 7);
+        assert_eq!(record.mainline.moves[2].nags, vec![5]);
+        assert_eq!(record.mainline.moves[2].comments, vec!["a line comment"]);
+        assert_eq!(
+            record.to_pgn().unwrap(),
+            "1. e4 e5 2. Nf3 $5 {a line comment} 2... Nc6 3. Bb5 a6 4. O-O *\n"
+        );
+    }
+
+    #[test]
+    fn test_from_moves_with_position() {
+        let start =
+            BoardRepr::from_fen("rnbqkbnr/pppp1ppp/8/3(NP)p3/8/8/PPPP1PPP/R1BQKBNR w KQkq e6 0 3")
+                .unwrap();
+        let moves = [Move {
+            from: Square::D5,
+            to: Square::E6,
+            which_half: Some(crate::pieces::PieceHalf::Right),
+            drop: None,
+        }];
+        let record = GameRecord::from_moves(start, &moves, GameResult::Unknown);
+        let pgn = record.to_pgn().unwrap();
+        assert!(pgn.ends_with("\n3. dxe6 *\n"), "{pgn}");
+        assert_eq!(GameRecord::from_pgn(&pgn).unwrap().mainline_moves(), moves);
+
+        // The paper's notation with a separate "e.p." is accepted too
+        let pgn = pgn.replace("dxe6", "dxe6 e.p.");
+        assert_eq!(GameRecord::from_pgn(&pgn).unwrap(), record);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            GameRecord::from_pgn("1. e4 {unfinished"),
+            Err(ParseGameRecordError::UnterminatedComment)
+        );
+        assert_eq!(
+            GameRecord::from_pgn("1. e4 (1. d4 *"),
+            Err(ParseGameRecordError::UnbalancedVariation)
+        );
+        assert_eq!(
+            GameRecord::from_pgn("(1. d4) 1. e4 *"),
+            Err(ParseGameRecordError::UnbalancedVariation)
+        );
+        assert_eq!(
+            GameRecord::from_pgn("1. e5 *"),
+            Err(ParseGameRecordError::InvalidMove(
+                ParseSanError::IllegalMove("e5".into())
+            ))
+        );
+        assert_eq!(
+            GameRecord::from_pgn("[White Alice]\n1. e4 *"),
+            Err(ParseGameRecordError::InvalidTag("[White Alice]".into()))
+        );
+        assert_eq!(
+            GameRecord::from_pgn("[Variant \"Atomic\"]\n1. e4 *"),
+            Err(ParseGameRecordError::UnknownVariant("Atomic".into()))
+        );
+
+        // Giving check is legal, but not in Racing Kings
+        let fen = "[FEN \"8/8/8/8/8/8/k6K/7R w - - 0 1\"]\n";
+        let racing_kings = format!("[Variant \"Racing Kings\"]\n{fen}");
+        assert!(GameRecord::from_pgn(&format!("{fen}\n1. Ra1+ *")).is_ok());
+        assert!(GameRecord::from_pgn(&format!("{racing_kings}\n1. Rb1 *")).is_ok());
+        assert_eq!(
+            GameRecord::from_pgn(&format!("{racing_kings}\n1. Ra1+ *")),
+            Err(ParseGameRecordError::InvalidMove(
+                ParseSanError::IllegalMove("Ra1+".into())
+            ))
+        );
+    }
+}
@@ -1,4 +1,22 @@
+pub mod attacks;
+pub mod bitboard;
 pub mod board_repr;
+pub mod builder;
+pub mod chess960;
+pub mod encoding;
+pub mod fen;
+pub mod game;
+pub mod game_record;
+pub mod perft;
 pub mod pieces;
+pub mod pocket;
+#[cfg(feature = "serde")]
+pub mod readable;
+pub mod rules;
+pub mod san;
 pub mod square;
 pub mod strategy;
+pub mod transform;
+pub mod validation;
+pub mod variant;
+pub mod zobrist;
@@ -0,0 +1,115 @@
+//! Perft: counting the positions reachable in a number of moves.
+//!
+//! Comparing these counts against known values is the standard way
+//! to check that a move generator is correct, and that a change to it didn't alter its output.
+//! The reference suite is in `tests/perft_suite.epd`, and is run both as a test and as a benchmark.
+
+use crate::{
+    board_repr::BoardRepr,
+    pieces::movement::{get_all_legal_moves, Move},
+};
+
+/// Counts the leaf nodes of the tree of legal moves of the given depth.
+pub fn perft(board: &BoardRepr, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let moves = get_all_legal_moves(board, board.side_to_move);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    let mut board = *board;
+    let mut nodes = 0;
+    for move_ in moves {
+        let undo = board.make_move(move_);
+        nodes += perft(&board, depth - 1);
+        board.unmake_move(undo);
+    }
+    nodes
+}
+
+/// Like [`perft`], but counts the nodes after each first move separately,
+/// which helps find the move whose subtree differs from the reference.
+pub fn perft_divide(board: &BoardRepr, depth: u32) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return vec![];
+    }
+
+    let mut board = *board;
+    get_all_legal_moves(&board, board.side_to_move)
+        .into_iter()
+        .map(|move_| {
+            let undo = board.make_move(move_);
+            let nodes = perft(&board, depth - 1);
+            board.unmake_move(undo);
+            (move_, nodes)
+        })
+        .collect()
+}
+
+/// A position from a perft suite, with the expected node counts at each depth.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PerftPosition {
+    pub fen: String,
+    pub expected: Vec<(u32, u64)>,
+}
+
+/// Reads a perft suite in the EPD-like format `<fen> ;D1 <nodes> ;D2 <nodes> ...`,
+/// one position per line.
+/// Empty lines and lines starting with `#` are skipped.
+///
+/// # Panics
+///
+/// Panics if a line is malformed, since suites are checked into the repository.
+pub fn parse_perft_suite(suite: &str) -> Vec<PerftPosition> {
+    suite
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let mut fields = line.split(';');
+            let fen = fields.next().unwrap().trim().to_string();
+            let expected = fields
+                .map(|field| {
+                    let (depth, nodes) = field
+                        .trim()
+                        .strip_prefix('D')
+                        .and_then(|field| field.split_once(' '))
+                        .unwrap_or_else(|| panic!("malformed perft suite entry: {field:?}"));
+                    (depth.parse().unwrap(), nodes.trim().parse().unwrap())
+                })
+                .collect();
+            PerftPosition { fen, expected }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_divide_matches_perft() {
+        let board = BoardRepr::default();
+        let divided = perft_divide(&board, 2);
+        assert_eq!(divided.len() as u64, perft(&board, 1));
+        assert_eq!(
+            divided.iter().map(|(_, nodes)| nodes).sum::<u64>(),
+            perft(&board, 2)
+        );
+    }
+
+    #[test]
+    fn test_parse_suite() {
+        let suite = "# comment\n\n8/8/8/8/8/8/8/K6k w - - 0 1 ;D1 3 ;D2 9\n";
+        assert_eq!(
+            parse_perft_suite(suite),
+            vec![PerftPosition {
+                fen: "8/8/8/8/8/8/8/K6k w - - 0 1".into(),
+                expected: vec![(1, 3), (2, 9)],
+            }]
+        );
+    }
+}
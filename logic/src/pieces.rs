@@ -31,6 +31,49 @@ impl Piece {
         }
     }
 
+    /// The number of different pieces: 6 unitary pieces and 15 combinations.
+    pub const COUNT: usize = 21;
+
+    /// Every piece, in the order of [`Piece::index`].
+    pub const ALL: [Piece; Piece::COUNT] = {
+        let mut all = [Piece::Unitary(UnitaryPiece::Queen); Piece::COUNT];
+        let mut i = 0;
+        while i < UnitaryPiece::ALL.len() {
+            all[i] = Piece::Unitary(UnitaryPiece::ALL[i]);
+            i += 1;
+        }
+        // The king doesn't combine, and comes last
+        let mut first = 0;
+        while first < 5 {
+            let mut second = first;
+            while second < 5 {
+                all[i] = Piece::Combination(CombinationPiece {
+                    first: UnitaryPiece::ALL[first],
+                    second: UnitaryPiece::ALL[second],
+                });
+                i += 1;
+                second += 1;
+            }
+            first += 1;
+        }
+        all
+    };
+
+    /// A distinct number for each piece, from 0 to [`Piece::COUNT`] (exclusive).
+    /// Unitary pieces come first, in the order of [`UnitaryPiece::ALL`],
+    /// followed by the combinations in their canonical order.
+    pub fn index(self) -> usize {
+        match self {
+            Piece::Unitary(p) => p as usize,
+            Piece::Combination(p) => {
+                // The combinations starting with each piece come after
+                // all the ones starting with the pieces before it
+                let (first, second) = (p.first() as usize, p.second() as usize);
+                6 + 5 * first - first * first.saturating_sub(1) / 2 + (second - first)
+            }
+        }
+    }
+
     pub fn for_components(self, mut f: impl FnMut(UnitaryPiece)) {
         match self {
             Piece::Unitary(p) => f(p),
@@ -127,6 +170,15 @@ impl ColorPiece {
     pub fn is_unitary(self) -> bool {
         self.piece().is_unitary()
     }
+
+    /// The same piece, of the other color.
+    #[must_use]
+    pub fn flip_color(self) -> Self {
+        match self {
+            ColorPiece::White(piece) => ColorPiece::Black(piece),
+            ColorPiece::Black(piece) => ColorPiece::White(piece),
+        }
+    }
 }
 
 /// A single unitary piece. Combinations are made of two of these,
@@ -167,6 +219,25 @@ impl Display for UnitaryPiece {
 }
 
 impl UnitaryPiece {
+    /// The uppercase letter used for this piece in text notations (FEN, SAN).
+    pub fn char(self) -> char {
+        match self {
+            UnitaryPiece::Queen => 'Q',
+            UnitaryPiece::Bishop => 'B',
+            UnitaryPiece::Knight => 'N',
+            UnitaryPiece::Rook => 'R',
+            UnitaryPiece::Pawn => 'P',
+            UnitaryPiece::King => 'K',
+        }
+    }
+
+    /// Parses a piece letter, in either case.
+    pub fn from_char(ch: char) -> Option<UnitaryPiece> {
+        UnitaryPiece::ALL
+            .into_iter()
+            .find(|p| p.char() == ch.to_ascii_uppercase())
+    }
+
     pub fn white(self) -> ColorPiece {
         ColorPiece::White(self.into())
     }
@@ -305,4 +376,22 @@ mod tests {
 
         assert_eq!(combo_pieces, target);
     }
+
+    #[test]
+    fn test_piece_index() {
+        let mut pieces: Vec<Piece> = UnitaryPiece::ALL.into_iter().map(Piece::from).collect();
+        for first in UnitaryPiece::ALL {
+            for second in UnitaryPiece::ALL {
+                if let Some(piece) = CombinationPiece::new(first, second) {
+                    if !pieces.contains(&piece.into()) {
+                        pieces.push(piece.into());
+                    }
+                }
+            }
+        }
+
+        let indices: Vec<usize> = pieces.iter().map(|p| p.index()).collect();
+        assert_eq!(indices, (0..Piece::COUNT).collect::<Vec<_>>());
+        assert_eq!(pieces, Piece::ALL);
+    }
 }
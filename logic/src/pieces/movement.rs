@@ -1,14 +1,19 @@
 use arrayvec::ArrayVec;
 
 use crate::{
+    bitboard::{
+        between, bishop_attacks, king_attacks, knight_attacks, pawn_attacks, queen_attacks,
+        rook_attacks, Bitboard, Bitboards,
+    },
     board_repr::BoardRepr,
-    square::{Rank, Square},
+    rules::PawnMerge,
+    square::{File, Rank, Square},
 };
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-use super::{Color, ColorPiece, Piece, PieceHalf, UnitaryPiece};
+use super::{Color, Piece, PieceHalf, UnitaryPiece};
 
 pub type MovesList = ArrayVec<Move, 1024>;
 
@@ -18,665 +23,1175 @@ pub struct Move {
     pub from: Square,
     pub to: Square,
     pub which_half: Option<PieceHalf>,
+    /// The piece taken from the pocket, if this is a drop rather than a move on the board,
+    /// see [`Move::drop`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub drop: Option<UnitaryPiece>,
 }
 
-pub fn find_any_legal_move(board_repr: &BoardRepr, side_to_move: Color) -> Option<Move> {
-    let mut moves = MovesList::new();
-    for (square, piece) in board_repr.iter_pieces() {
-        if piece.color() == side_to_move {
-            moves = get_moves_from_square(moves, board_repr, side_to_move, square, None);
-            moves.retain(|x| board_repr.is_safe_move(*x, side_to_move));
-            if !moves.is_empty() {
-                return Some(moves[0]);
-            }
+impl Move {
+    /// Drops a piece from the pocket of the side to move onto the square, see [`crate::pocket`].
+    /// A drop has no source square, so `from` is the same as `to`.
+    pub fn drop(piece: UnitaryPiece, to: Square) -> Move {
+        Move {
+            from: to,
+            to,
+            which_half: None,
+            drop: Some(piece),
         }
     }
 
-    None
+    pub fn is_drop(self) -> bool {
+        self.drop.is_some()
+    }
 }
 
-pub fn get_all_legal_moves(board_repr: &BoardRepr, side_to_move: Color) -> MovesList {
-    let mut moves = MovesList::new();
-    for (square, piece) in board_repr.iter_pieces() {
-        if piece.color() == side_to_move {
-            moves = get_moves_from_square(moves, board_repr, side_to_move, square, None);
+/// The two directions a king can castle in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CastlingSide {
+    KingSide,
+    QueenSide,
+}
+
+impl CastlingSide {
+    pub const ALL: [CastlingSide; 2] = [CastlingSide::KingSide, CastlingSide::QueenSide];
+
+    /// The index of the matching castling right in [`BoardRepr::castling_rights`].
+    pub fn right_index(self, color: Color) -> usize {
+        color as usize * 2 + self as usize
+    }
+
+    pub(crate) fn back_rank(color: Color) -> Rank {
+        match color {
+            Color::White => Rank::First,
+            Color::Black => Rank::Eighth,
         }
     }
 
-    // log::debug!("All semi-legal moves of position: {moves:?}");
-    moves.retain(|x| board_repr.is_safe_move(*x, side_to_move));
-    // log::debug!("Only legal moves of position: {moves:?}");
-    moves
-}
+    /// Where the king starts, see [`BoardRepr::castling_files`].
+    pub fn king_from(self, board_repr: &BoardRepr, color: Color) -> Square {
+        let files = board_repr.castling_files[color as usize];
+        Square::from_coords(files.king, Self::back_rank(color))
+    }
 
-pub fn get_legal_moves_from_square(
-    moves: MovesList,
-    board_repr: &BoardRepr,
-    side_to_move: Color,
-    from: Square,
-    which_half: Option<PieceHalf>,
-) -> MovesList {
-    let mut moves = get_moves_from_square(moves, board_repr, side_to_move, from, which_half);
+    pub fn king_to(self, color: Color) -> Square {
+        let file = match self {
+            CastlingSide::KingSide => File::G,
+            CastlingSide::QueenSide => File::C,
+        };
+        Square::from_coords(file, Self::back_rank(color))
+    }
 
-    // log::debug!("All moves: {moves:?}");
-    moves.retain(|x| board_repr.is_safe_move(*x, side_to_move));
+    /// Where the rook starts: the corner in the usual starting position,
+    /// see [`BoardRepr::castling_files`].
+    pub fn rook_from(self, board_repr: &BoardRepr, color: Color) -> Square {
+        let files = board_repr.castling_files[color as usize];
+        Square::from_coords(files.rook(self), Self::back_rank(color))
+    }
 
-    // log::debug!("Legal moves: {moves:?}");
-    moves
-}
+    pub fn rook_to(self, color: Color) -> Square {
+        let file = match self {
+            CastlingSide::KingSide => File::F,
+            CastlingSide::QueenSide => File::D,
+        };
+        Square::from_coords(file, Self::back_rank(color))
+    }
 
-pub fn get_moves_from_square(
-    mut moves: MovesList,
-    board_repr: &BoardRepr,
-    side_to_move: Color,
-    from: Square,
-    which_half: Option<PieceHalf>,
-) -> MovesList {
-    // If there is no piece of the current color on the square, there are no moves
-    let piece = if let Some(piece) = board_repr[from] {
-        if piece.color() == side_to_move {
-            piece
+    /// The destination of the king's move when castling.
+    ///
+    /// This is where the king lands if it moves at least two files, as in the usual starting position.
+    /// Otherwise, as can happen in Chess960, it is the square of the rook,
+    /// so that castling can't be mistaken for a normal king move
+    /// (the king can never move onto a friendly piece, since it doesn't merge).
+    pub fn castling_target(self, board_repr: &BoardRepr, color: Color) -> Square {
+        let (king_from, king_to) = (self.king_from(board_repr, color), self.king_to(color));
+        if king_from.file().distance(king_to.file()) >= 2 {
+            king_to
         } else {
-            return moves;
+            self.rook_from(board_repr, color)
         }
-    } else {
-        return moves;
+    }
+}
+
+/// The files that the king and the castling rooks of one side start on,
+/// which decide where castling takes them from.
+///
+/// In the usual starting position the king starts on the e-file and the rooks in the corners.
+/// In Chess960 they can start anywhere on the back rank, with the king between the rooks,
+/// but castling still puts the king and the rook on the same squares as usual.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CastlingFiles {
+    pub king: File,
+    /// The files of the rooks, in the order of [`CastlingSide::ALL`].
+    pub rooks: [File; 2],
+}
+
+impl CastlingFiles {
+    /// The files of the usual starting position.
+    pub const STANDARD: CastlingFiles = CastlingFiles {
+        king: File::E,
+        rooks: [File::H, File::A],
     };
-    let color = piece.color();
-    let piece = piece.piece();
 
-    // If the color of the piece is not the same as the side to move, there are no moves
-    if color != side_to_move {
-        return MovesList::new();
+    pub fn rook(self, side: CastlingSide) -> File {
+        self.rooks[side as usize]
     }
+}
 
-    // If the piece is unitary, then it has no halves
-    // If a half is specified in such a case, then there are no moves
-    if which_half.is_some() && piece.is_unitary() {
-        return MovesList::new();
+impl Default for CastlingFiles {
+    fn default() -> Self {
+        CastlingFiles::STANDARD
     }
+}
 
-    let mut add_unitary = |piece: UnitaryPiece| match piece {
-        super::UnitaryPiece::Pawn => {
-            get_pawn_moves_from_square(board_repr, side_to_move, from, which_half, &mut moves)
-        }
-        super::UnitaryPiece::King => {
-            get_king_moves_from_square(board_repr, side_to_move, from, which_half, &mut moves)
-        }
-        super::UnitaryPiece::Queen => {
-            get_rook_moves_from_square(board_repr, side_to_move, from, which_half, &mut moves);
-            get_bishop_moves_from_square(board_repr, side_to_move, from, which_half, &mut moves);
-        }
-        super::UnitaryPiece::Rook => {
-            get_rook_moves_from_square(board_repr, side_to_move, from, which_half, &mut moves)
-        }
-        super::UnitaryPiece::Bishop => {
-            get_bishop_moves_from_square(board_repr, side_to_move, from, which_half, &mut moves)
-        }
-        super::UnitaryPiece::Knight => {
-            get_knight_moves_from_square(board_repr, side_to_move, from, which_half, &mut moves)
+/// The squares on a rank between two squares (both excluded), or including them.
+fn rank_span(a: Square, b: Square, inclusive: bool) -> Bitboard {
+    let (low, high) = (a.file().min(b.file()) as u32, a.file().max(b.file()) as u32);
+    let files = if inclusive {
+        low..=high
+    } else {
+        low + 1..=high.saturating_sub(1)
+    };
+    files
+        .map(|file| Square::from_coords(File::new(file), a.rank()))
+        .collect()
+}
+
+/// If the move is castling, returns the side that the king castles to.
+/// The rook's part of the move is implied, see [`CastlingSide::castling_target`].
+pub fn castling_side(board_repr: &BoardRepr, move_: Move) -> Option<CastlingSide> {
+    let piece = board_repr[move_.from]?;
+    if piece.piece() != Piece::Unitary(UnitaryPiece::King)
+        || move_.which_half.is_some()
+        || move_.is_drop()
+    {
+        return None;
+    }
+
+    // With the castling right, the king and the rook are still on their starting squares,
+    // so the king can't get to the castling target by a normal move
+    let color = piece.color();
+    CastlingSide::ALL.into_iter().find(|side| {
+        board_repr.castling_rights[side.right_index(color)]
+            && move_.from == side.king_from(board_repr, color)
+            && move_.to == side.castling_target(board_repr, color)
+    })
+}
+
+/// What a move does to the square it lands on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MoveKind {
+    /// The piece moves to an empty square.
+    Quiet,
+    /// The piece captures an enemy piece on the destination square.
+    Capture,
+    /// The piece merges with a friendly piece on the destination square.
+    Merge,
+    /// The king castles, and the rook moves along with it.
+    Castle,
+    /// A piece containing a pawn captures a pawn that just made a double step, by the pawn's rule.
+    EnPassant,
+    /// A piece is dropped from the pocket, onto an empty square or merging with a friendly piece.
+    Drop,
+}
+
+/// A description of a move in the position it is played in,
+/// for UIs, notation and move ordering.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MoveInfo {
+    /// The component whose movement rule the move follows, see [`move_rule`].
+    pub rule: UnitaryPiece,
+    pub kind: MoveKind,
+    /// Whether only one half of a combination moves, leaving the other half behind.
+    pub split: bool,
+    /// Whether a piece containing a pawn reaches the last rank, and is promoted.
+    pub promotion: bool,
+}
+
+/// The component of the moving piece whose movement rule produced the move,
+/// or `None` if no component of the piece can make it.
+/// For a drop, this is the dropped piece.
+///
+/// When several components can make the same move, like a queen-pawn stepping forward,
+/// the pawn's rule is chosen, so that the move is a double step or an en passant capture
+/// whenever it could be one.
+/// [`Move`] doesn't say which rule was meant, so a whole rook-pawn moving from e2 to e4
+/// is always a double step, and can be captured en passant.
+/// To move it like a rook instead, the rook has to split off, leaving the pawn behind.
+pub fn move_rule(board_repr: &BoardRepr, move_: Move) -> Option<UnitaryPiece> {
+    if move_.is_drop() {
+        return move_.drop;
+    }
+    let piece = board_repr[move_.from]?;
+    // The components that could have made the move, with the pawn first
+    let components = match (piece.piece(), move_.which_half) {
+        (Piece::Unitary(unitary), _) => [Some(unitary), None],
+        (Piece::Combination(combo), Some(half)) => [Some(combo[half]), None],
+        (Piece::Combination(combo), None) if combo.second() == UnitaryPiece::Pawn => {
+            [Some(UnitaryPiece::Pawn), Some(combo.first())]
         }
+        (Piece::Combination(combo), None) => [Some(combo.first()), Some(combo.second())],
     };
 
-    // If the piece is unitary, then we just return the list matching its state
-    if let Piece::Unitary(piece) = piece {
-        add_unitary(piece);
+    let bitboards = board_repr.bitboards();
+    components.into_iter().flatten().find(|unitary| {
+        component_targets(
+            board_repr,
+            bitboards,
+            piece.color(),
+            move_.from,
+            piece.piece(),
+            *unitary,
+        )
+        .contains(move_.to)
+    })
+}
+
+/// The square that a pawn capturing en passant lands on, if there is one.
+pub fn en_passant_target(board_repr: &BoardRepr) -> Option<Square> {
+    let square = board_repr.en_passant_square?;
+    match board_repr.side_to_move {
+        Color::White => square.up(),
+        Color::Black => square.down(),
     }
+}
 
-    // If the piece is not unitary, then we add the moves for the specified half
-    // (or for the piece together)
-    if let Piece::Combination(combo) = piece {
-        if let Some(half) = which_half {
-            add_unitary(combo[half]);
-        } else {
-            for half in [PieceHalf::Left, PieceHalf::Right] {
-                add_unitary(combo[half]);
+/// Describes a move, or returns `None` if the piece on the source square can't make it.
+/// The move doesn't need to be legal otherwise.
+pub fn move_info(board_repr: &BoardRepr, move_: Move) -> Option<MoveInfo> {
+    if let Some(rule) = move_.drop {
+        return Some(MoveInfo {
+            rule,
+            kind: MoveKind::Drop,
+            split: false,
+            promotion: false,
+        });
+    }
+    let piece = board_repr[move_.from]?;
+    let rule = move_rule(board_repr, move_)?;
+
+    let kind = if castling_side(board_repr, move_).is_some() {
+        MoveKind::Castle
+    } else {
+        match board_repr[move_.to] {
+            Some(dst) if dst.color() == piece.color() => MoveKind::Merge,
+            Some(_) => MoveKind::Capture,
+            None if rule == UnitaryPiece::Pawn
+                && en_passant_target(board_repr) == Some(move_.to) =>
+            {
+                MoveKind::EnPassant
             }
+            None => MoveKind::Quiet,
         }
-    }
+    };
 
-    moves
+    let split = move_.which_half.is_some() && !piece.piece().is_unitary();
+    let moving_pawn = match (piece.piece(), move_.which_half) {
+        (Piece::Combination(combo), Some(half)) => combo[half] == UnitaryPiece::Pawn,
+        (moving, _) => moving.contains(UnitaryPiece::Pawn),
+    };
+    let last_rank = match piece.color() {
+        Color::White => Rank::Eighth,
+        Color::Black => Rank::First,
+    };
+
+    Some(MoveInfo {
+        rule,
+        kind,
+        split,
+        promotion: moving_pawn && move_.to.rank() == last_rank,
+    })
 }
 
-fn get_pawn_moves_from_square(
+/// Checks whether the side can castle in the given direction:
+/// - it still has the castling right;
+/// - the king and a piece containing a rook are on their starting squares;
+/// - the squares that the king and the rook pass through or land on are empty,
+///   apart from the king and the rook themselves;
+/// - the king is not in check, and does not pass through or land on an attacked square.
+///
+/// A rook that is merged into a combination castles together with its other half,
+/// if the rules allow it.
+pub fn can_castle(
     board_repr: &BoardRepr,
-    side_to_move: Color,
-    from: Square,
-    which_half: Option<PieceHalf>,
-    moves: &mut MovesList,
-) {
-    match side_to_move {
-        Color::White => {
-            // If the square up is empty, then the move is valid
-            if let Some(up) = from.up() {
-                if board_repr[up].is_none() {
-                    moves.push(Move {
-                        from,
-                        to: up,
-                        which_half,
-                    });
-                }
+    bitboards: &Bitboards,
+    color: Color,
+    side: CastlingSide,
+) -> bool {
+    let king_from = side.king_from(board_repr, color);
+    let rook_from = side.rook_from(board_repr, color);
+    let (king_to, rook_to) = (side.king_to(color), side.rook_to(color));
+    let path = rank_span(king_from, king_to, true) | rank_span(rook_from, rook_to, true);
+    let blockers = bitboards.occupied()
+        & !Bitboard::from_square(king_from)
+        & !Bitboard::from_square(rook_from);
 
-                // If the square up up is empty, and it is the second rank,
-                // then the move is valid
-                if let Some(up_up) = up.up() {
-                    if board_repr[up].is_none()
-                        && board_repr[up_up].is_none()
-                        && from.rank() == Rank::Second
-                    {
-                        moves.push(Move {
-                            from,
-                            to: up_up,
-                            which_half,
-                        });
+    board_repr.castling_rights[side.right_index(color)]
+        && board_repr[king_from]
+            .is_some_and(|p| p.color() == color && p.piece() == Piece::Unitary(UnitaryPiece::King))
+        && board_repr[rook_from].is_some_and(|p| {
+            p.color() == color
+                && match p.piece() {
+                    Piece::Unitary(piece) => piece == UnitaryPiece::Rook,
+                    Piece::Combination(combo) => {
+                        board_repr.rules.castle_with_combined_rook
+                            && combo.contains(UnitaryPiece::Rook)
                     }
                 }
+        })
+        && (path & blockers).is_empty()
+        && {
+            // The rook leaves its square, which may have been shielding the king's path,
+            // as when the king stays in place and the rook jumps over it in Chess960
+            let mut without_rook = *bitboards;
+            without_rook.set(rook_from, None);
+            (rank_span(king_from, king_to, true) & without_rook.attacked_squares(color.opposite()))
+                .is_empty()
+        }
+}
 
-                // If the square up left is occupied by an enemy piece
-                if let Some(left) = up.left() {
-                    if let Some(piece) = board_repr[left] {
-                        if piece.color() != side_to_move {
-                            moves.push(Move {
-                                from,
-                                to: left,
-                                which_half,
-                            });
-                        }
-                    }
-                }
+/// The ways a piece can be moved: as a whole, and for a combination, each of its halves alone.
+/// If both halves are the same piece, moving either of them has the same effect,
+/// so only the left half is included.
+fn ways_to_move(piece: Piece) -> &'static [Option<PieceHalf>] {
+    match piece {
+        Piece::Unitary(_) => &[None],
+        Piece::Combination(combo) if combo.first() == combo.second() => {
+            &[None, Some(PieceHalf::Left)]
+        }
+        Piece::Combination(_) => &[None, Some(PieceHalf::Left), Some(PieceHalf::Right)],
+    }
+}
 
-                // If the square up right is occupied by an enemy piece
-                if let Some(right) = up.right() {
-                    if let Some(piece) = board_repr[right] {
-                        if piece.color() != side_to_move {
-                            moves.push(Move {
-                                from,
-                                to: right,
-                                which_half,
-                            });
-                        }
-                    }
-                }
+/// What is needed to tell which moves leave the king in check, computed once for a position,
+/// so that moves don't have to be tried out on a copy of the board.
+struct Legality {
+    /// The king of the side to move, if it has one.
+    /// Without a king, every move is legal.
+    king: Option<Square>,
 
-                // If this piece is unitary, and the piece up right or up left is also unitary,
-                // then the move is valid (it will merge)
-                if let Some(piece) = board_repr[from] {
-                    if let Piece::Unitary(UnitaryPiece::Pawn) = piece.piece() {
-                        if let Some(right) = up.right() {
-                            if let Some(ColorPiece::White(Piece::Unitary(_))) = board_repr[right] {
-                                moves.push(Move {
-                                    from,
-                                    to: right,
-                                    which_half,
-                                });
-                            }
-                        }
-                        if let Some(left) = up.left() {
-                            if let Some(ColorPiece::White(Piece::Unitary(_))) = board_repr[left] {
-                                moves.push(Move {
-                                    from,
-                                    to: left,
-                                    which_half,
-                                });
-                            }
-                        }
-                    }
-                }
+    /// The enemy pieces that give check.
+    checkers: Bitboard,
 
-                // If standing next to the en passant square,
-                // and the square up in the direction of the en passant square
-                // is empty
-                if let Some(en_passant_square) = board_repr.en_passant_square {
-                    if let Some(upleft) = up.left() {
-                        if let Some(left) = from.left() {
-                            if left == en_passant_square && board_repr[upleft].is_none() {
-                                moves.push(Move {
-                                    from,
-                                    to: upleft,
-                                    which_half,
-                                });
-                            }
-                        }
-                    }
+    /// The squares that a piece other than the king must move to in order to stop a check:
+    /// the checker and the squares between it and the king.
+    /// When not in check, every square.
+    check_mask: Bitboard,
 
-                    if let Some(upright) = up.right() {
-                        if let Some(right) = from.right() {
-                            if right == en_passant_square && board_repr[upright].is_none() {
-                                moves.push(Move {
-                                    from,
-                                    to: upright,
-                                    which_half,
-                                });
-                            }
-                        }
-                    }
+    /// The pinned pieces, with the squares each can move to as a whole without exposing the king:
+    /// the squares between the king and the pinning piece, and the pinning piece itself.
+    /// Moving only a half of a pinned combination is never a problem,
+    /// since the other half keeps blocking the line.
+    pins: ArrayVec<(Square, Bitboard), 8>,
+
+    /// The squares attacked by the enemy, with the king taken off the board,
+    /// so that the king can't step back along the line of a slider that checks it.
+    king_danger: Bitboard,
+
+    /// Where a pawn capturing en passant lands, since those captures remove a piece
+    /// from a square the mover doesn't land on, and are checked by trying them out.
+    en_passant_target: Option<Square>,
+}
+
+impl Legality {
+    fn new(board_repr: &BoardRepr, bitboards: &Bitboards, side_to_move: Color) -> Self {
+        let enemy = side_to_move.opposite();
+        let en_passant_target = en_passant_target(board_repr);
+        let Some(king) =
+            (bitboards.by_component(UnitaryPiece::King) & bitboards.by_color(side_to_move)).first()
+        else {
+            return Self {
+                king: None,
+                checkers: Bitboard::EMPTY,
+                check_mask: Bitboard::FULL,
+                pins: ArrayVec::new(),
+                king_danger: Bitboard::EMPTY,
+                en_passant_target,
+            };
+        };
+
+        let checkers = bitboards.attackers_of(king, enemy);
+        let check_mask = match checkers.single_square() {
+            _ if checkers.is_empty() => Bitboard::FULL,
+            Some(checker) => between(king, checker) | Bitboard::from_square(checker),
+            // In double check, only the king can move
+            None => Bitboard::EMPTY,
+        };
+
+        // An enemy slider on a line from the king pins the piece between them,
+        // if it is the only piece in the way and it is ours
+        let mut pins = ArrayVec::new();
+        let snipers = (rook_attacks(king, Bitboard::EMPTY) & bitboards.rook_like(enemy))
+            | (bishop_attacks(king, Bitboard::EMPTY) & bitboards.bishop_like(enemy));
+        for sniper in snipers {
+            let line = between(king, sniper);
+            if let Some(pinned) = (line & bitboards.occupied()).single_square() {
+                if bitboards.by_color(side_to_move).contains(pinned) {
+                    pins.push((pinned, line | Bitboard::from_square(sniper)));
                 }
             }
         }
 
-        Color::Black => {
-            // If the square down is empty, then the move is valid
-            if let Some(down) = from.down() {
-                if board_repr[down].is_none() {
-                    moves.push(Move {
-                        from,
-                        to: down,
-                        which_half,
-                    });
-                }
-
-                // If the square down down is empty, and it is the 7th rank,
-                // then the move is valid
-                if let Some(down_down) = down.down() {
-                    if board_repr[down].is_none()
-                        && board_repr[down_down].is_none()
-                        && from.rank() == Rank::Seventh
-                    {
-                        moves.push(Move {
-                            from,
-                            to: down_down,
-                            which_half,
-                        });
-                    }
-                }
+        let mut without_king = *bitboards;
+        without_king.set(king, None);
 
-                // If the square down left is occupied by a piece (friendly or enemy)
-                if let Some(left) = down.left() {
-                    if let Some(piece) = board_repr[left] {
-                        if piece.color() != side_to_move {
-                            moves.push(Move {
-                                from,
-                                to: left,
-                                which_half,
-                            });
-                        }
-                    }
-                }
+        Self {
+            king: Some(king),
+            checkers,
+            check_mask,
+            pins,
+            king_danger: without_king.attacked_squares(enemy),
+            en_passant_target,
+        }
+    }
 
-                // If the square down right is occupied by an enemy piece
-                if let Some(right) = down.right() {
-                    if let Some(piece) = board_repr[right] {
-                        if piece.color() != side_to_move {
-                            moves.push(Move {
-                                from,
-                                to: right,
-                                which_half,
-                            });
-                        }
-                    }
-                }
+    /// Checks whether a move generated by [`get_moves_from_square`] or [`drop_targets`] leaves the king safe.
+    fn is_legal(&self, board_repr: &BoardRepr, side_to_move: Color, move_: Move) -> bool {
+        if move_.is_drop() {
+            // A drop only adds to our pieces, so it can't expose the king.
+            // On an empty square it can block a check,
+            // but merging with a piece that is already there changes nothing for the king.
+            return match board_repr[move_.to] {
+                None => self.check_mask.contains(move_.to),
+                Some(_) => self.checkers.is_empty(),
+            };
+        }
 
-                // If this piece is unitary, and the piece down right or down left is also unitary,
-                // then the move is valid (it will merge)
-                if let Some(piece) = board_repr[from] {
-                    if let Piece::Unitary(UnitaryPiece::Pawn) = piece.piece() {
-                        if let Some(right) = down.right() {
-                            if let Some(ColorPiece::Black(Piece::Unitary(_))) = board_repr[right] {
-                                moves.push(Move {
-                                    from,
-                                    to: right,
-                                    which_half,
-                                });
-                            }
-                        }
-                        if let Some(left) = down.left() {
-                            if let Some(ColorPiece::Black(Piece::Unitary(_))) = board_repr[left] {
-                                moves.push(Move {
-                                    from,
-                                    to: left,
-                                    which_half,
-                                });
-                            }
-                        }
-                    }
-                }
+        if Some(move_.from) == self.king {
+            // Castling already checks every square the king passes through
+            return castling_side(board_repr, move_).is_some()
+                || !self.king_danger.contains(move_.to);
+        }
 
-                // If standing next to the en passant square,
-                // and the square down in the direction of the en passant square
-                // is empty
-                if let Some(en_passant_square) = board_repr.en_passant_square {
-                    if let Some(downleft) = down.left() {
-                        if let Some(left) = from.left() {
-                            if left == en_passant_square && board_repr[downleft].is_none() {
-                                moves.push(Move {
-                                    from,
-                                    to: downleft,
-                                    which_half,
-                                });
-                            }
-                        }
-                    }
+        if Some(move_.to) == self.en_passant_target
+            && board_repr[move_.from].is_some_and(|p| p.piece().contains(UnitaryPiece::Pawn))
+        {
+            return board_repr.is_safe_move(move_, side_to_move);
+        }
 
-                    if let Some(downright) = down.right() {
-                        if let Some(right) = from.right() {
-                            if right == en_passant_square && board_repr[downright].is_none() {
-                                moves.push(Move {
-                                    from,
-                                    to: downright,
-                                    which_half,
-                                });
-                            }
-                        }
-                    }
-                }
+        let mut allowed = self.check_mask;
+        let splits = move_.which_half.is_some()
+            && board_repr[move_.from].is_some_and(|p| !p.piece().is_unitary());
+        if !splits {
+            if let Some((_, line)) = self.pins.iter().find(|(pinned, _)| *pinned == move_.from) {
+                allowed &= *line;
             }
         }
+        allowed.contains(move_.to)
     }
 }
 
-fn try_add(
+/// Pushes the moves of the piece on the square, whole or split, that don't leave the king in check.
+fn push_legal_moves(
+    mut moves: MovesList,
     board_repr: &BoardRepr,
+    bitboards: &Bitboards,
+    legality: &Legality,
     side_to_move: Color,
-    from: Square,
     square: Square,
-    which_half: Option<PieceHalf>,
-    moves: &mut MovesList,
-) -> bool {
-    // If the given square is empty, then it's a valid move
-    if board_repr[square].is_none() {
-        moves.push(Move {
-            from,
-            to: square,
+) -> MovesList {
+    let Some(piece) = board_repr[square] else {
+        return moves;
+    };
+    if Some(square) != legality.king && legality.checkers.count() > 1 {
+        return moves;
+    }
+
+    for &which_half in ways_to_move(piece.piece()) {
+        let first_new = moves.len();
+        moves = get_moves_from_square_with_bitboards(
+            moves,
+            board_repr,
+            bitboards,
+            side_to_move,
+            square,
             which_half,
-        });
+        );
 
-        true
-    } else {
-        // If the square up is occupied by an enemy piece, the move is valid
-        if let Some(dst_piece) = board_repr[square] {
-            if dst_piece.color() != side_to_move {
-                moves.push(Move {
-                    from,
-                    to: square,
-                    which_half,
-                });
+        // Only check the moves that were just added, the others were already checked
+        let mut i = first_new;
+        while i < moves.len() {
+            if legality.is_legal(board_repr, side_to_move, moves[i]) {
+                i += 1;
             } else {
-                // If the square up is occupied by a friendly unitary piece,
-                // and the source piece is also unitary,
-                // the move is also valid
-                // (But not if that piece is the king)
-                let src_piece = board_repr[from].unwrap();
-                if dst_piece.is_unitary() && src_piece.is_unitary() {
-                    if let Piece::Unitary(UnitaryPiece::King) = dst_piece.piece() {
-                        return false;
-                    }
-                    moves.push(Move {
-                        from,
-                        to: square,
-                        which_half,
-                    });
-                }
+                moves.swap_remove(i);
             }
         }
-
-        // stop going in this direction
-        false
     }
+    moves
 }
-pub fn get_rook_moves_from_square(
+
+/// Pushes the drops of every piece the side has in hand that don't leave the king in check.
+fn push_legal_drops(
+    mut moves: MovesList,
     board_repr: &BoardRepr,
+    bitboards: &Bitboards,
+    legality: &Legality,
     side_to_move: Color,
-    from: Square,
-    which_half: Option<PieceHalf>,
-    moves: &mut MovesList,
-) {
-    // Try all 4 directions from the square
-
-    let mut current_square = from;
-
-    while let Some(left) = current_square.left() {
-        current_square = left;
-        if !try_add(
-            board_repr,
-            side_to_move,
-            from,
-            current_square,
-            which_half,
-            moves,
-        ) {
-            break;
+) -> MovesList {
+    let Some(pockets) = board_repr.pockets else {
+        return moves;
+    };
+    for (piece, _) in pockets.pieces(side_to_move) {
+        for to in drop_targets(bitboards, side_to_move, piece) {
+            let move_ = Move::drop(piece, to);
+            if legality.is_legal(board_repr, side_to_move, move_) {
+                moves.push(move_);
+            }
         }
     }
+    moves
+}
 
-    let mut current_square = from;
-
-    while let Some(right) = current_square.right() {
-        current_square = right;
-        if !try_add(
+/// Finds a legal move for the side, if it has any, including moves of a single half of a combination
+/// and drops.
+pub fn find_any_legal_move(board_repr: &BoardRepr, side_to_move: Color) -> Option<Move> {
+    let bitboards = board_repr.bitboards();
+    let legality = Legality::new(board_repr, bitboards, side_to_move);
+    for square in bitboards.by_color(side_to_move) {
+        let moves = push_legal_moves(
+            MovesList::new(),
             board_repr,
+            bitboards,
+            &legality,
             side_to_move,
-            from,
-            current_square,
-            which_half,
-            moves,
-        ) {
-            break;
+            square,
+        );
+        if let Some(move_) = moves.first() {
+            return Some(*move_);
         }
     }
 
-    let mut current_square = from;
+    push_legal_drops(
+        MovesList::new(),
+        board_repr,
+        bitboards,
+        &legality,
+        side_to_move,
+    )
+    .first()
+    .copied()
+}
 
-    while let Some(up) = current_square.up() {
-        current_square = up;
-        if !try_add(
+/// All the legal moves of the side: moves of whole pieces,
+/// moves of each half of a combination that leave the other half behind,
+/// and drops of the pieces in hand if the position has pockets.
+///
+/// A split move and a whole-piece move to the same square are both listed,
+/// since they leave different positions: the other half stays behind or not.
+/// Only moves with the same effect are left out, which are the splits of the second half
+/// of a combination of two identical pieces.
+pub fn get_all_legal_moves(board_repr: &BoardRepr, side_to_move: Color) -> MovesList {
+    let bitboards = board_repr.bitboards();
+    let legality = Legality::new(board_repr, bitboards, side_to_move);
+    let mut moves = MovesList::new();
+    for square in bitboards.by_color(side_to_move) {
+        moves = push_legal_moves(
+            moves,
             board_repr,
+            bitboards,
+            &legality,
             side_to_move,
-            from,
-            current_square,
-            which_half,
-            moves,
-        ) {
-            break;
-        }
+            square,
+        );
     }
+    push_legal_drops(moves, board_repr, bitboards, &legality, side_to_move)
+}
 
-    let mut current_square = from;
+pub fn get_legal_moves_from_square(
+    moves: MovesList,
+    board_repr: &BoardRepr,
+    side_to_move: Color,
+    from: Square,
+    which_half: Option<PieceHalf>,
+) -> MovesList {
+    let bitboards = board_repr.bitboards();
+    let legality = Legality::new(board_repr, bitboards, side_to_move);
+    let mut moves = get_moves_from_square_with_bitboards(
+        moves,
+        board_repr,
+        bitboards,
+        side_to_move,
+        from,
+        which_half,
+    );
+    moves.retain(|x| legality.is_legal(board_repr, side_to_move, *x));
+    moves
+}
 
-    while let Some(down) = current_square.down() {
-        current_square = down;
-        if !try_add(
-            board_repr,
-            side_to_move,
-            from,
-            current_square,
-            which_half,
-            moves,
-        ) {
-            break;
-        }
-    }
+pub fn get_moves_from_square(
+    moves: MovesList,
+    board_repr: &BoardRepr,
+    side_to_move: Color,
+    from: Square,
+    which_half: Option<PieceHalf>,
+) -> MovesList {
+    let bitboards = board_repr.bitboards();
+    get_moves_from_square_with_bitboards(
+        moves,
+        board_repr,
+        bitboards,
+        side_to_move,
+        from,
+        which_half,
+    )
 }
 
-pub fn get_bishop_moves_from_square(
+/// Same as [`get_moves_from_square`], but reuses bitboards that were already computed for this board.
+pub fn get_moves_from_square_with_bitboards(
+    mut moves: MovesList,
     board_repr: &BoardRepr,
+    bitboards: &Bitboards,
     side_to_move: Color,
     from: Square,
     which_half: Option<PieceHalf>,
-    moves: &mut MovesList,
-) {
-    let mut current_square = from;
-    while let Some(upright) = current_square.up().and_then(|x| x.right()) {
-        current_square = upright;
-        if !try_add(
-            board_repr,
-            side_to_move,
-            from,
-            current_square,
-            which_half,
-            moves,
-        ) {
-            break;
+) -> MovesList {
+    // If there is no piece of the current color on the square, there are no moves
+    let piece = if let Some(piece) = board_repr[from] {
+        if piece.color() == side_to_move {
+            piece
+        } else {
+            return moves;
         }
+    } else {
+        return moves;
+    };
+    let piece = piece.piece();
+
+    // If the piece is unitary, then it has no halves
+    // If a half is specified in such a case, then there are no moves
+    if which_half.is_some() && piece.is_unitary() {
+        return moves;
     }
 
-    let mut current_square = from;
-    while let Some(upleft) = current_square.up().and_then(|x| x.left()) {
-        current_square = upleft;
-        if !try_add(
-            board_repr,
-            side_to_move,
+    let targets_of = |unitary: UnitaryPiece| {
+        component_targets(board_repr, bitboards, side_to_move, from, piece, unitary)
+    };
+
+    let targets = match piece {
+        // If the piece is unitary, then we just return the list matching its state
+        Piece::Unitary(unitary) => targets_of(unitary),
+
+        // If the piece is not unitary, then we add the moves for the specified half
+        // (or for the piece together, going where either half can go,
+        // without listing twice the squares that both halves reach)
+        Piece::Combination(combo) => match which_half {
+            Some(half) => targets_of(combo[half]),
+            None => targets_of(combo[PieceHalf::Left]) | targets_of(combo[PieceHalf::Right]),
+        },
+    };
+    for to in targets {
+        moves.push(Move {
             from,
-            current_square,
+            to,
             which_half,
-            moves,
-        ) {
-            break;
-        }
+            drop: None,
+        });
     }
 
-    let mut current_square = from;
-    while let Some(downright) = current_square.down().and_then(|x| x.right()) {
-        current_square = downright;
-        if !try_add(
-            board_repr,
-            side_to_move,
-            from,
-            current_square,
-            which_half,
-            moves,
-        ) {
-            break;
+    moves
+}
+
+/// The squares that one component of a piece can move to by its own movement rule.
+/// `moving` is the whole piece that moves, which decides whether it may merge.
+fn component_targets(
+    board_repr: &BoardRepr,
+    bitboards: &Bitboards,
+    side_to_move: Color,
+    from: Square,
+    moving: Piece,
+    unitary: UnitaryPiece,
+) -> Bitboard {
+    match unitary {
+        UnitaryPiece::Pawn => get_pawn_targets(board_repr, bitboards, side_to_move, from, moving),
+        UnitaryPiece::King => get_king_targets(board_repr, bitboards, side_to_move, from),
+        UnitaryPiece::Queen => {
+            queen_attacks(from, bitboards.occupied())
+                & allowed_destinations(bitboards, side_to_move, moving)
+        }
+        UnitaryPiece::Rook => {
+            rook_attacks(from, bitboards.occupied())
+                & allowed_destinations(bitboards, side_to_move, moving)
+        }
+        UnitaryPiece::Bishop => {
+            bishop_attacks(from, bitboards.occupied())
+                & allowed_destinations(bitboards, side_to_move, moving)
+        }
+        UnitaryPiece::Knight => {
+            knight_attacks(from) & allowed_destinations(bitboards, side_to_move, moving)
         }
     }
+}
 
-    let mut current_square = from;
-    while let Some(downleft) = current_square.down().and_then(|x| x.left()) {
-        current_square = downleft;
-        if !try_add(
-            board_repr,
-            side_to_move,
-            from,
-            current_square,
-            which_half,
-            moves,
-        ) {
-            break;
+/// The squares that a non-pawn, non-king piece may land on, if its movement rule reaches them:
+/// - empty squares;
+/// - squares occupied by an enemy piece (which is captured);
+/// - squares occupied by a friendly unitary piece other than the king,
+///   if the moving piece is also unitary (the two will merge).
+fn allowed_destinations(bitboards: &Bitboards, side_to_move: Color, moving: Piece) -> Bitboard {
+    let mut allowed = !bitboards.by_color(side_to_move);
+    if moving.is_unitary() {
+        allowed |= mergeable_squares(bitboards, side_to_move);
+    }
+    allowed
+}
+
+/// The squares that the side can drop the piece on, if it has it in hand, see [`crate::pocket`]:
+/// empty squares, and squares with a friendly piece that it can merge with.
+/// Pawns can't be dropped on the first or last rank, and kings are never in hand.
+pub fn drop_targets(bitboards: &Bitboards, side_to_move: Color, piece: UnitaryPiece) -> Bitboard {
+    match piece {
+        UnitaryPiece::King => Bitboard::EMPTY,
+        UnitaryPiece::Pawn => {
+            (!bitboards.occupied() | mergeable_squares(bitboards, side_to_move))
+                & !Bitboard::from_rank(Rank::First)
+                & !Bitboard::from_rank(Rank::Eighth)
         }
+        _ => !bitboards.occupied() | mergeable_squares(bitboards, side_to_move),
     }
 }
 
-pub fn get_knight_moves_from_square(
+/// The squares with a friendly piece that a unitary piece could merge with.
+fn mergeable_squares(bitboards: &Bitboards, side_to_move: Color) -> Bitboard {
+    bitboards.by_color(side_to_move)
+        & bitboards.unitary()
+        & !bitboards.by_component(UnitaryPiece::King)
+}
+
+fn get_pawn_targets(
     board_repr: &BoardRepr,
+    bitboards: &Bitboards,
     side_to_move: Color,
     from: Square,
-    which_half: Option<PieceHalf>,
-    moves: &mut MovesList,
-) {
-    let squares = [
-        from.up().and_then(|x| x.left()).and_then(|x| x.left()),
-        from.up().and_then(|x| x.right()).and_then(|x| x.right()),
-        from.down().and_then(|x| x.left()).and_then(|x| x.left()),
-        from.down().and_then(|x| x.right()).and_then(|x| x.right()),
-        from.left().and_then(|x| x.up()).and_then(|x| x.up()),
-        from.left().and_then(|x| x.down()).and_then(|x| x.down()),
-        from.right().and_then(|x| x.up()).and_then(|x| x.up()),
-        from.right().and_then(|x| x.down()).and_then(|x| x.down()),
-    ];
-
-    for square in squares {
-        if let Some(square) = square {
-            try_add(board_repr, side_to_move, from, square, which_half, moves);
+    moving: Piece,
+) -> Bitboard {
+    let (forward, start_rank): (fn(Square) -> Option<Square>, Rank) = match side_to_move {
+        Color::White => (Square::up, Rank::Second),
+        Color::Black => (Square::down, Rank::Seventh),
+    };
+    let empty = !bitboards.occupied();
+    let mut targets = Bitboard::EMPTY;
+
+    // If the square forward is empty, then the move is valid.
+    // If the square after that is also empty, and we're on the starting rank,
+    // then the double step is valid too.
+    if let Some(one) = forward(from).filter(|s| empty.contains(*s)) {
+        targets.add(one);
+        if from.rank() == start_rank {
+            if let Some(two) = forward(one).filter(|s| empty.contains(*s)) {
+                targets.add(two);
+            }
         }
     }
+
+    // The squares forward diagonally can be captured if they contain an enemy piece
+    let attacks = pawn_attacks(side_to_move, from);
+    targets |= attacks & bitboards.by_color(side_to_move.opposite());
+
+    // If this piece is a unitary pawn, and the piece it can merge with
+    // (forward diagonally, or straight forward, depending on the rules) is also unitary,
+    // then the move is valid (it will merge)
+    if moving == Piece::Unitary(UnitaryPiece::Pawn) {
+        let merge_squares = match board_repr.rules.pawn_merge {
+            PawnMerge::Diagonal => attacks,
+            PawnMerge::Forward => forward(from).map_or(Bitboard::EMPTY, Bitboard::from_square),
+        };
+        targets |= merge_squares & mergeable_squares(bitboards, side_to_move);
+    }
+
+    // If standing next to the en passant square,
+    // and the square forward in the direction of the en passant square
+    // is empty, then it can be taken en passant
+    if let Some(en_passant_square) = board_repr
+        .en_passant_square
+        .filter(|_| board_repr.rules.en_passant)
+    {
+        if en_passant_square.rank() == from.rank()
+            && en_passant_square.file().distance(from.file()) == 1
+        {
+            if let Some(target) = forward(en_passant_square).filter(|s| empty.contains(*s)) {
+                targets.add(target);
+            }
+        }
+    }
+
+    targets
 }
 
-pub fn get_king_moves_from_square(
+fn get_king_targets(
     board_repr: &BoardRepr,
+    bitboards: &Bitboards,
     side_to_move: Color,
     from: Square,
-    which_half: Option<PieceHalf>,
-    moves: &mut MovesList,
-) {
-    // The king cannot merge with any other piece
-    let squares = [
-        from.up().and_then(|x| x.left()),
-        from.up(),
-        from.up().and_then(|x| x.right()),
-        from.left(),
-        from.right(),
-        from.down().and_then(|x| x.left()),
-        from.down(),
-        from.down().and_then(|x| x.right()),
-    ];
-
-    for square in squares {
-        if let Some(square) = square {
-            // If the square is occupied by a friendly piece, the move is invalid
-            // (king cannot merge)
-            // If it is empty, or it is occupied by an enemy piece, the move is valid
-            if let Some(piece) = board_repr[square] {
-                if piece.color() == side_to_move {
-                    continue;
-                }
-            }
-            moves.push(Move {
-                from,
-                to: square,
-                which_half,
-            });
+) -> Bitboard {
+    // The king cannot merge with any other piece,
+    // so it can only go to empty squares or capture enemies
+    let mut targets = king_attacks(from) & !bitboards.by_color(side_to_move);
+
+    for side in CastlingSide::ALL {
+        if from == side.king_from(board_repr, side_to_move)
+            && can_castle(board_repr, bitboards, side_to_move, side)
+        {
+            targets.add(side.castling_target(board_repr, side_to_move));
         }
     }
 
-    // If the king has castling rights, and it is in its starting position,
-    // and the squares between the king and the rook are empty,
-    // then the move is valid
-    // TODO: check that the king doesn't castle through check
-
-    match side_to_move {
-        Color::White => {
-            if from == Square::E1 {
-                if board_repr.castling_rights[0] {
-                    // can castle kingside
-                    if board_repr[Square::F1].is_none() && board_repr[Square::G1].is_none() {
-                        moves.push(Move {
-                            from,
-                            to: Square::G1,
-                            which_half,
-                        });
-                    }
-                }
+    targets
+}
 
-                if board_repr.castling_rights[1] {
-                    // can castle queenside
-                    if board_repr[Square::B1].is_none()
-                        && board_repr[Square::C1].is_none()
-                        && board_repr[Square::D1].is_none()
-                    {
-                        moves.push(Move {
-                            from,
-                            to: Square::C1,
-                            which_half,
-                        });
-                    }
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::RuleSet;
+
+    fn castling_moves(fen: &str) -> Vec<CastlingSide> {
+        let board = BoardRepr::from_fen(fen).unwrap();
+        let mut sides: Vec<CastlingSide> = get_all_legal_moves(&board, board.side_to_move)
+            .into_iter()
+            .filter_map(|m| castling_side(&board, m))
+            .collect();
+        sides.sort_by_key(|side| *side as usize);
+        sides
+    }
+
+    #[test]
+    fn test_castling() {
+        use CastlingSide::*;
+
+        let cases: &[(&str, &[CastlingSide])] = &[
+            (
+                "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+                &[KingSide, QueenSide],
+            ),
+            (
+                "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1",
+                &[KingSide, QueenSide],
+            ),
+            ("r3k2r/8/8/8/8/8/8/R3K2R w Qk - 0 1", &[QueenSide]),
+            // Out of check, through check and into check
+            ("4k3/4r3/8/8/8/8/8/R3K2R w KQ - 0 1", &[]),
+            ("4kr2/8/8/8/8/8/8/R3K2R w KQ - 0 1", &[QueenSide]),
+            ("4k1r1/8/8/8/8/8/8/R3K2R w KQ - 0 1", &[QueenSide]),
+            // The rook may pass over an attacked square
+            ("1r2k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", &[KingSide, QueenSide]),
+            // Blocked, and no rook in the corner
+            ("4k3/8/8/8/8/8/8/RN2K1NR w KQ - 0 1", &[]),
+            // A combined rook castles too
+            (
+                "4k3/8/8/8/8/8/8/(QR)3K2(NR) w KQ - 0 1",
+                &[KingSide, QueenSide],
+            ),
+            // Chess960: the king's whole path must be safe, but not the rook's
+            ("3rk3/8/8/8/8/8/8/RK5R w KQ - 0 1", &[QueenSide]),
+            ("4k3/8/8/8/8/8/8/6KR w K - 0 1", &[KingSide]),
+            ("4k3/8/8/8/8/8/8/1RK5 w Q - 0 1", &[QueenSide]),
+            // The rook can't shield the king from a rook behind it once it has moved
+            ("4k3/8/8/8/8/8/8/rRK5 w Q - 0 1", &[]),
+        ];
+
+        for (fen, expected) in cases {
+            assert_eq!(castling_moves(fen), *expected, "{fen}");
+        }
+
+        // FEN refuses a castling right with no rook in the corner,
+        // but the generator doesn't rely on that
+        let mut board = BoardRepr::from_fen("4k3/8/8/8/8/8/8/4K2B w - - 0 1").unwrap();
+        board.castling_rights[CastlingSide::KingSide.right_index(Color::White)] = true;
+        assert!(get_all_legal_moves(&board, Color::White)
+            .iter()
+            .all(|m| castling_side(&board, *m).is_none()));
+    }
+
+    #[test]
+    fn test_castling_with_combination() {
+        let mut board = BoardRepr::from_fen("4k3/8/8/8/8/8/8/4K2(NR) w K - 0 1").unwrap();
+        board
+            .play(Move {
+                from: Square::E1,
+                to: Square::G1,
+                which_half: None,
+                drop: None,
+            })
+            .unwrap();
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/5(NR)K1 b - - 1 1");
+    }
+
+    #[test]
+    fn test_chess960_castling() {
+        let cases = [
+            // The king moves one square, so the move is written as taking its own rook
+            (
+                "3rk3/8/8/8/8/8/8/RK5R w KQ - 0 1",
+                Square::A1,
+                "3rk3/8/8/8/8/8/8/2KR3R b - - 1 1",
+            ),
+            // The king stays where it is, and the rook jumps over it
+            (
+                "4k3/8/8/8/8/8/8/6KR w K - 0 1",
+                Square::H1,
+                "4k3/8/8/8/8/8/8/5RK1 b - - 1 1",
+            ),
+            // The rook is already where it castles to
+            (
+                "4k3/8/8/8/8/8/8/3RK3 w Q - 0 1",
+                Square::C1,
+                "4k3/8/8/8/8/8/8/2KR4 b - - 1 1",
+            ),
+        ];
+        for (fen, to, expected) in cases {
+            let mut board = BoardRepr::from_fen(fen).unwrap();
+            let before = board;
+            let move_ = Move {
+                from: board.king_square(Color::White),
+                to,
+                which_half: None,
+                drop: None,
+            };
+            assert!(castling_side(&board, move_).is_some(), "{fen}");
+            let undo = board.make_move(move_);
+            assert_eq!(board.to_fen(), expected);
+            assert_eq!(board.castling_files, [CastlingFiles::STANDARD; 2]);
+            board.unmake_move(undo);
+            assert_eq!(board, before, "{fen}");
+        }
+
+        // Without the right, the king captures on the castling target as usual
+        let board = BoardRepr::from_fen("4k3/8/8/8/8/8/8/6Kr w - - 0 1").unwrap();
+        let capture = Move {
+            from: Square::G1,
+            to: Square::H1,
+            which_half: None,
+            drop: None,
+        };
+        assert_eq!(castling_side(&board, capture), None);
+        assert!(board.validate_move(capture).is_ok());
+    }
+
+    #[test]
+    fn test_rights_lost_on_capture() {
+        let mut board = BoardRepr::from_fen("4k3/8/8/8/8/8/6b1/R3K2(NR) b KQ - 0 1").unwrap();
+        board
+            .play(Move {
+                from: Square::G2,
+                to: Square::H1,
+                which_half: None,
+                drop: None,
+            })
+            .unwrap();
+        assert_eq!(board.castling_rights, [false, true, false, false]);
+    }
+
+    #[test]
+    fn test_identical_halves_split_once() {
+        let board = BoardRepr::from_fen("4k3/8/8/8/8/8/8/(NN)3K3 w - - 0 1").unwrap();
+        let moves = get_all_legal_moves(&board, Color::White);
+        let splits = moves.iter().filter(|m| m.which_half.is_some()).count();
+        let whole = moves.iter().filter(|m| m.from == Square::A1).count() - splits;
+        assert_eq!((whole, splits), (2, 2));
+    }
+
+    #[test]
+    fn test_split_and_whole_moves_to_same_square() {
+        // The whole queen-knight and its queen half alone both reach d5,
+        // and the two moves leave different positions, so both are listed
+        let board = BoardRepr::from_fen("4k3/8/8/8/3(QN)4/8/8/4K3 w - - 0 1").unwrap();
+        let moves = get_all_legal_moves(&board, Color::White);
+        let halves_to = |to| {
+            moves
+                .iter()
+                .filter(|m| m.from == Square::D4 && m.to == to)
+                .map(|m| m.which_half)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(halves_to(Square::D5), [None, Some(PieceHalf::Left)]);
+        assert_eq!(halves_to(Square::E6), [None, Some(PieceHalf::Right)]);
+
+        let mut after_whole = board;
+        after_whole.make_move(Move {
+            from: Square::D4,
+            to: Square::D5,
+            which_half: None,
+            drop: None,
+        });
+        let mut after_split = board;
+        after_split.make_move(Move {
+            from: Square::D4,
+            to: Square::D5,
+            which_half: Some(PieceHalf::Left),
+            drop: None,
+        });
+        assert_ne!(after_whole, after_split);
+    }
+
+    #[test]
+    fn test_move_rule_decides_en_passant() {
+        let board = BoardRepr::from_fen("4k3/8/8/3(NP)p3/8/8/8/4K3 w - e6 0 1").unwrap();
+        let whole = |to| Move {
+            from: Square::D5,
+            to,
+            which_half: None,
+            drop: None,
+        };
+
+        // The pawn's capture takes the pawn that just moved
+        let info = move_info(&board, whole(Square::E6)).unwrap();
+        assert_eq!(
+            (info.rule, info.kind),
+            (UnitaryPiece::Pawn, MoveKind::EnPassant)
+        );
+        let mut after = board;
+        after.play(whole(Square::E6)).unwrap();
+        assert_eq!(after[Square::E5], None);
+
+        // The knight's jump to the same file doesn't
+        let info = move_info(&board, whole(Square::E7)).unwrap();
+        assert_eq!(
+            (info.rule, info.kind),
+            (UnitaryPiece::Knight, MoveKind::Quiet)
+        );
+        let mut after = board;
+        after.play(whole(Square::E7)).unwrap();
+        assert!(after[Square::E5].is_some());
+    }
+
+    #[test]
+    fn test_move_rule_decides_double_step() {
+        let board = BoardRepr::from_fen("k7/8/8/8/8/8/4(RP)3/4K3 w - - 0 1").unwrap();
+        let double_step = |which_half| Move {
+            from: Square::E2,
+            to: Square::E4,
+            which_half,
+            drop: None,
+        };
+
+        // Moving as a whole, the pawn's rule is preferred over the rook's
+        let mut after = board;
+        after.play(double_step(None)).unwrap();
+        assert_eq!(
+            move_rule(&board, double_step(None)),
+            Some(UnitaryPiece::Pawn)
+        );
+        assert_eq!(after.en_passant_square, Some(Square::E4));
+
+        // The rook splitting off can't be captured en passant
+        let rook = double_step(Some(PieceHalf::Left));
+        let mut after = board;
+        after.play(rook).unwrap();
+        assert_eq!(
+            move_info(&board, rook),
+            Some(MoveInfo {
+                rule: UnitaryPiece::Rook,
+                kind: MoveKind::Quiet,
+                split: true,
+                promotion: false,
+            })
+        );
+        assert_eq!(after.en_passant_square, None);
+
+        // So a whole piece that could also have moved like a rook is still captured en passant
+        let board = BoardRepr::from_fen("k7/8/8/8/3p4/8/4(RP)3/4K3 w - - 0 1").unwrap();
+        let mut after = board;
+        after.play(double_step(None)).unwrap();
+        let capture = after.parse_san("dxe3").unwrap();
+        assert_eq!(
+            move_info(&after, capture).map(|info| info.kind),
+            Some(MoveKind::EnPassant)
+        );
+        after.play(capture).unwrap();
+        assert_eq!(after[Square::E4], None);
+        assert_eq!(after[Square::E3], Some(UnitaryPiece::Pawn.black()));
+    }
+
+    #[test]
+    fn test_move_kinds() {
+        let board = BoardRepr::from_fen("1r2k3/2P5/8/8/8/7B/8/R3K1N1 w Q - 0 1").unwrap();
+        let kind = |from, to| {
+            move_info(
+                &board,
+                Move {
+                    from,
+                    to,
+                    which_half: None,
+                    drop: None,
+                },
+            )
+            .map(|info| (info.kind, info.promotion))
+        };
+        assert_eq!(
+            kind(Square::E1, Square::C1),
+            Some((MoveKind::Castle, false))
+        );
+        assert_eq!(kind(Square::G1, Square::H3), Some((MoveKind::Merge, false)));
+        assert_eq!(
+            kind(Square::C7, Square::B8),
+            Some((MoveKind::Capture, true))
+        );
+        assert_eq!(kind(Square::C7, Square::C8), Some((MoveKind::Quiet, true)));
+        assert_eq!(kind(Square::G1, Square::G2), None);
+    }
+
+    /// The legal moves found by trying out every pseudo-legal move on a copy of the board.
+    fn slow_legal_moves(board: &BoardRepr) -> Vec<Move> {
+        let side = board.side_to_move;
+        let mut moves = vec![];
+        for (square, piece) in board.iter_pieces() {
+            if piece.color() != side {
+                continue;
+            }
+            for &which_half in ways_to_move(piece.piece()) {
+                moves.extend(
+                    get_moves_from_square(MovesList::new(), board, side, square, which_half)
+                        .into_iter()
+                        .filter(|m| board.is_safe_move(*m, side)),
+                );
             }
         }
-        Color::Black => {
-            if from == Square::E8 {
-                if board_repr.castling_rights[2] {
-                    // can castle kingside
-                    if board_repr[Square::F8].is_none() && board_repr[Square::G8].is_none() {
-                        moves.push(Move {
-                            from,
-                            to: Square::G8,
-                            which_half,
-                        });
-                    }
-                }
+        moves
+    }
 
-                if board_repr.castling_rights[3] {
-                    // can castle queenside
-                    if board_repr[Square::B8].is_none()
-                        && board_repr[Square::C8].is_none()
-                        && board_repr[Square::D8].is_none()
-                    {
-                        moves.push(Move {
-                            from,
-                            to: Square::C8,
-                            which_half,
-                        });
-                    }
+    #[test]
+    fn test_legal_generator_matches_trying_moves() {
+        let starts = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "4k3/8/3(qn)4/8/8/3(BR)(NP)3/8/4K3 w - - 0 1",
+            // Pins, and an en passant capture that would expose the king along the rank
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "4k3/4r3/8/2b5/8/4(NP)3/3(BR)4/r3K2R w K - 0 1",
+        ];
+
+        // Play pseudo-random games, comparing both ways of finding legal moves in every position
+        let mut seed = 2024u64;
+        for (fen, rules) in starts.iter().flat_map(|fen| {
+            [RuleSet::THIS_PROJECT, RuleSet::ANDROID_APP]
+                .into_iter()
+                .map(move |rules| (fen, rules))
+        }) {
+            let mut board = BoardRepr::from_fen(fen).unwrap();
+            board.rules = rules;
+            for _ in 0..80 {
+                let mut fast: Vec<Move> = get_all_legal_moves(&board, board.side_to_move).to_vec();
+                let mut slow = slow_legal_moves(&board);
+                let key = |m: &Move| (m.from as u8, m.to as u8, m.which_half.map(|h| h as u8));
+                fast.sort_by_key(key);
+                slow.sort_by_key(key);
+                assert_eq!(fast, slow, "in {}", board.to_fen());
+                assert_eq!(
+                    find_any_legal_move(&board, board.side_to_move).is_some(),
+                    !slow.is_empty()
+                );
+
+                if fast.is_empty() {
+                    break;
                 }
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                board.make_move(fast[(seed >> 33) as usize % fast.len()]);
             }
         }
     }
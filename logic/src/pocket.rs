@@ -0,0 +1,157 @@
+//! The pieces that each side holds in hand, for variants with drops like Crazyhouse.
+//!
+//! A position has pockets only if the variant uses them, see [`BoardRepr::pockets`].
+//! When it does, a captured piece goes to the pocket of the side that captured it,
+//! and a combination is split into its two components on the way.
+//! Kings are never captured, so the pockets only hold the other five pieces.
+//!
+//! A piece in the pocket can be dropped instead of moving a piece, see [`Move::drop`]:
+//! onto an empty square, or onto a friendly unitary piece other than the king to merge with it.
+//! Pawns can't be dropped on the first or last rank.
+//!
+//! [`BoardRepr::pockets`]: crate::board_repr::BoardRepr::pockets
+//! [`Move::drop`]: crate::pieces::movement::Move::drop
+
+use crate::pieces::{Color, Piece, UnitaryPiece};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// How many of each piece both sides have in hand.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Pockets {
+    /// Indexed by color, then by piece in the order of [`Pockets::DROPPABLE`].
+    counts: [[u8; 5]; 2],
+}
+
+impl Pockets {
+    /// The pieces that can be held in hand, which is every piece but the king,
+    /// in the order of [`UnitaryPiece::ALL`].
+    pub const DROPPABLE: [UnitaryPiece; 5] = [
+        UnitaryPiece::Queen,
+        UnitaryPiece::Bishop,
+        UnitaryPiece::Knight,
+        UnitaryPiece::Rook,
+        UnitaryPiece::Pawn,
+    ];
+
+    /// How many of the piece the side has in hand. Always 0 for the king.
+    pub fn count(&self, color: Color, piece: UnitaryPiece) -> u8 {
+        match piece {
+            UnitaryPiece::King => 0,
+            _ => self.counts[color as usize][piece as usize],
+        }
+    }
+
+    /// Whether the side has nothing in hand.
+    pub fn is_empty(&self, color: Color) -> bool {
+        self.counts[color as usize].iter().all(|count| *count == 0)
+    }
+
+    /// The pieces the side has in hand, with how many of each, skipping the ones it has none of.
+    pub fn pieces(&self, color: Color) -> impl Iterator<Item = (UnitaryPiece, u8)> + '_ {
+        Pockets::DROPPABLE
+            .into_iter()
+            .map(move |piece| (piece, self.count(color, piece)))
+            .filter(|(_, count)| *count > 0)
+    }
+
+    /// Puts a piece in the side's pocket, or returns `false` if it already holds
+    /// as many of the piece as can be counted, which is [`u8::MAX`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the piece is a king.
+    #[must_use]
+    pub fn add(&mut self, color: Color, piece: UnitaryPiece) -> bool {
+        assert_ne!(piece, UnitaryPiece::King, "a king can't be held in hand");
+        let count = &mut self.counts[color as usize][piece as usize];
+        match count.checked_add(1) {
+            Some(added) => {
+                *count = added;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Puts both components of a captured piece in the side's pocket,
+    /// or returns `false` and leaves the pockets as they were if either doesn't fit, see [`Pockets::add`].
+    #[must_use]
+    pub fn add_captured(&mut self, color: Color, piece: Piece) -> bool {
+        let mut added = *self;
+        let mut fits = true;
+        piece.for_components(|component| fits &= added.add(color, component));
+        if fits {
+            *self = added;
+        }
+        fits
+    }
+
+    /// Sets how many of the piece the side has in hand.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the piece is a king.
+    pub fn set_count(&mut self, color: Color, piece: UnitaryPiece, count: u8) {
+        assert_ne!(piece, UnitaryPiece::King, "a king can't be held in hand");
+        self.counts[color as usize][piece as usize] = count;
+    }
+
+    /// Takes a piece out of the side's pocket, or returns `false` if it has none.
+    pub fn remove(&mut self, color: Color, piece: UnitaryPiece) -> bool {
+        if self.count(color, piece) == 0 {
+            return false;
+        }
+        self.counts[color as usize][piece as usize] -= 1;
+        true
+    }
+
+    /// The same pockets with the sides swapped.
+    #[must_use]
+    pub fn swap_colors(self) -> Pockets {
+        let [white, black] = self.counts;
+        Pockets {
+            counts: [black, white],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pieces::CombinationPiece;
+
+    #[test]
+    fn test_pockets() {
+        let mut pockets = Pockets::default();
+        assert!(pockets.is_empty(Color::White));
+
+        let knawn = CombinationPiece::new(UnitaryPiece::Knight, UnitaryPiece::Pawn).unwrap();
+        assert!(pockets.add_captured(Color::White, knawn.into()));
+        assert!(pockets.add(Color::White, UnitaryPiece::Pawn));
+        assert_eq!(
+            pockets.pieces(Color::White).collect::<Vec<_>>(),
+            [(UnitaryPiece::Knight, 1), (UnitaryPiece::Pawn, 2)]
+        );
+        assert!(pockets.is_empty(Color::Black));
+
+        assert!(pockets.remove(Color::White, UnitaryPiece::Knight));
+        assert!(!pockets.remove(Color::White, UnitaryPiece::Knight));
+        assert!(!pockets.remove(Color::White, UnitaryPiece::King));
+        assert_eq!(
+            pockets
+                .swap_colors()
+                .count(Color::Black, UnitaryPiece::Pawn),
+            2
+        );
+
+        // A count can't go past what a byte holds
+        pockets.set_count(Color::White, UnitaryPiece::Pawn, u8::MAX);
+        let full = pockets;
+        assert!(!pockets.add(Color::White, UnitaryPiece::Pawn));
+        assert!(!pockets.add_captured(Color::White, knawn.into()));
+        assert_eq!(pockets, full);
+    }
+}
@@ -0,0 +1,355 @@
+//! Text forms of squares, pieces and positions for serde, meant for clients not written in Rust.
+//!
+//! By default, the `serde` feature serializes these types structurally:
+//! a square is the name of its enum variant, and a piece is a nested object
+//! such as `{"White":{"Combination":{"first":"Queen","second":"Knight"}}}`.
+//! That format is kept for compatibility, and the modules here can be chosen per field instead,
+//! with `#[serde(with = "readable::square")]` and so on:
+//!
+//! - [`square`] writes a square by its name, such as `"e4"`, and [`option_square`] writes `null` for none;
+//! - [`piece`] writes a piece as its color, `w` or `b`, followed by its letters, such as `"wQN"`;
+//! - [`move_`] writes a move as its source and destination squares, such as `"e2e4"`,
+//!   followed by `L` or `R` if only the left or right half of a combination moves, such as `"d2f3L"`,
+//!   or a drop as the piece and its square, such as `"N@e4"`; [`option_move`] writes `null` for none;
+//! - [`board`] writes a position as an object with its FEN, its rule set and the previous move.
+
+use crate::{
+    fen::parse_piece_letters,
+    pieces::{movement::Move, Color, ColorPiece, Piece, PieceHalf, UnitaryPiece},
+    square::Square,
+};
+
+/// The short code of a piece, such as `"wQN"` for a white queen-knight.
+fn piece_code(piece: ColorPiece) -> String {
+    let mut code = String::from(match piece.color() {
+        Color::White => 'w',
+        Color::Black => 'b',
+    });
+    match piece.piece() {
+        Piece::Unitary(p) => code.push(p.char()),
+        Piece::Combination(combo) => {
+            code.push(combo.first().char());
+            code.push(combo.second().char());
+        }
+    }
+    code
+}
+
+fn parse_piece_code(code: &str) -> Option<ColorPiece> {
+    let (color, letters) = match code.split_at_checked(1)? {
+        ("w", letters) => (Color::White, letters),
+        ("b", letters) => (Color::Black, letters),
+        _ => return None,
+    };
+    // The letters are uppercase for both colors
+    if !letters.chars().all(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+    let piece = parse_piece_letters(letters)?.piece();
+    Some(match color {
+        Color::White => ColorPiece::White(piece),
+        Color::Black => ColorPiece::Black(piece),
+    })
+}
+
+/// The text form of a move, such as `"e2e4"`, `"d2f3L"` or `"N@e4"`.
+fn move_code(move_: Move) -> String {
+    if let Some(piece) = move_.drop {
+        return format!("{}@{}", piece.char(), move_.to);
+    }
+    let half = match move_.which_half {
+        None => "",
+        Some(PieceHalf::Left) => "L",
+        Some(PieceHalf::Right) => "R",
+    };
+    format!("{}{}{half}", move_.from, move_.to)
+}
+
+fn parse_move_code(code: &str) -> Option<Move> {
+    if let Some((piece, to)) = code.split_once('@') {
+        let mut letters = piece.chars();
+        let piece = match (letters.next(), letters.next()) {
+            (Some(letter), None) if letter.is_ascii_uppercase() => UnitaryPiece::from_char(letter)?,
+            _ => return None,
+        };
+        return Some(Move::drop(piece, to.parse().ok()?));
+    }
+
+    let (from, rest) = code.split_at_checked(2)?;
+    let (to, half) = rest.split_at_checked(2)?;
+    let which_half = match half {
+        "" => None,
+        "L" => Some(PieceHalf::Left),
+        "R" => Some(PieceHalf::Right),
+        _ => return None,
+    };
+    Some(Move {
+        from: from.parse::<Square>().ok()?,
+        to: to.parse().ok()?,
+        which_half,
+        drop: None,
+    })
+}
+
+/// Serializes a [`Square`](crate::square::Square) by its name, such as `"e4"`.
+pub mod square {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::square::Square;
+
+    pub fn serialize<S: Serializer>(square: &Square, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(square)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Square, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Serializes an optional [`Square`](crate::square::Square) by its name, or as `null` if there is none.
+pub mod option_square {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::square::Square;
+
+    pub fn serialize<S: Serializer>(
+        square: &Option<Square>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match square {
+            Some(square) => serializer.collect_str(square),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Square>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|name| name.parse().map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+/// Serializes a [`ColorPiece`] as its color and letters, such as `"wQN"` or `"bP"`.
+pub mod piece {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::pieces::ColorPiece;
+
+    pub fn serialize<S: Serializer>(piece: &ColorPiece, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::piece_code(*piece))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ColorPiece, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        super::parse_piece_code(&code)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid piece code: {code:?}")))
+    }
+}
+
+/// Serializes a [`Move`] as its squares, such as `"e2e4"`, see the [module documentation](self).
+pub mod move_ {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::pieces::movement::Move;
+
+    pub fn serialize<S: Serializer>(move_: &Move, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::move_code(*move_))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Move, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        super::parse_move_code(&code)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid move: {code:?}")))
+    }
+}
+
+/// Serializes an optional [`Move`] like [`move_`], or as `null` if there is none.
+pub mod option_move {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::pieces::movement::Move;
+
+    pub fn serialize<S: Serializer>(
+        move_: &Option<Move>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match move_ {
+            Some(move_) => serializer.serialize_str(&super::move_code(*move_)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Move>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|code| {
+                super::parse_move_code(&code)
+                    .ok_or_else(|| serde::de::Error::custom(format!("invalid move: {code:?}")))
+            })
+            .transpose()
+    }
+}
+
+/// Serializes a [`BoardRepr`](crate::board_repr::BoardRepr) as an object with its FEN,
+/// along with what FEN has no room for: the rule set, and the previous move in the form of [`move_`].
+///
+/// ```json
+/// {"fen":"rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1","rules":{...},"previous_move":"e2e4"}
+/// ```
+///
+/// The rules default to [`RuleSet::default`] and the previous move to none when they are left out.
+pub mod board {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::{board_repr::BoardRepr, pieces::movement::Move, rules::RuleSet};
+
+    #[derive(Serialize, Deserialize)]
+    struct Board {
+        fen: String,
+        #[serde(default)]
+        rules: RuleSet,
+        #[serde(default, with = "super::option_move")]
+        previous_move: Option<Move>,
+    }
+
+    pub fn serialize<S: Serializer>(board: &BoardRepr, serializer: S) -> Result<S::Ok, S::Error> {
+        Board {
+            fen: board.to_fen(),
+            rules: board.rules,
+            previous_move: board.previous_move,
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BoardRepr, D::Error> {
+        let Board {
+            fen,
+            rules,
+            previous_move,
+        } = Board::deserialize(deserializer)?;
+        let mut board = BoardRepr::from_fen(&fen).map_err(serde::de::Error::custom)?;
+        board.rules = rules;
+        board.previous_move = previous_move;
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::{
+        board_repr::BoardRepr,
+        pieces::{CombinationPiece, UnitaryPiece},
+        rules::RuleSet,
+    };
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message {
+        #[serde(with = "square")]
+        square: Square,
+        #[serde(with = "option_square")]
+        target: Option<Square>,
+        #[serde(with = "piece")]
+        piece: ColorPiece,
+        #[serde(with = "move_")]
+        move_: Move,
+        #[serde(with = "board")]
+        board: BoardRepr,
+    }
+
+    #[test]
+    fn test_readable_message() {
+        let message = Message {
+            square: Square::E4,
+            piece: CombinationPiece::new(UnitaryPiece::Queen, UnitaryPiece::Knight)
+                .unwrap()
+                .white(),
+            target: None,
+            move_: Move::drop(UnitaryPiece::Knight, Square::E4),
+            board: BoardRepr::default(),
+        };
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(
+            json,
+            concat!(
+                r#"{"square":"e4","target":null,"piece":"wQN","move_":"N@e4","board":{"#,
+                r#""fen":"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1","#,
+                r#""rules":{"pawn_merge":"Diagonal","en_passant":true,"promotion":"WholePieceToQueen","castle_with_combined_rook":true},"#,
+                r#""previous_move":null}}"#
+            )
+        );
+        assert_eq!(serde_json::from_str::<Message>(&json).unwrap(), message);
+    }
+
+    #[test]
+    fn test_board_keeps_rules_and_previous_move() {
+        let mut board = BoardRepr::default();
+        board.rules = RuleSet::ANDROID_APP;
+        board.make_move(Move {
+            from: Square::E2,
+            to: Square::E4,
+            which_half: None,
+            drop: None,
+        });
+        let message = Message {
+            square: Square::E4,
+            target: Some(Square::E2),
+            piece: UnitaryPiece::Pawn.white(),
+            move_: board.previous_move.unwrap(),
+            board,
+        };
+        let json = serde_json::to_string(&message).unwrap();
+        assert!(json.contains(r#""target":"e2""#), "{json}");
+        assert!(json.contains(r#""previous_move":"e2e4""#), "{json}");
+        assert_eq!(serde_json::from_str::<Message>(&json).unwrap(), message);
+
+        // The rules and the previous move can be left out
+        let message: Message = serde_json::from_str(
+            r#"{"square":"e4","target":null,"piece":"wP","move_":"e2e4","board":{"fen":"8/8/8/8/8/8/8/K6k w - - 0 1"}}"#,
+        )
+        .unwrap();
+        assert_eq!(message.board.rules, RuleSet::default());
+        assert_eq!(message.board.previous_move, None);
+    }
+
+    #[test]
+    fn test_move_codes() {
+        let split = Move {
+            from: Square::D2,
+            to: Square::F3,
+            which_half: Some(PieceHalf::Left),
+            drop: None,
+        };
+        assert_eq!(move_code(split), "d2f3L");
+        assert_eq!(
+            move_code(Move::drop(UnitaryPiece::Knight, Square::E4)),
+            "N@e4"
+        );
+        for move_ in [split, Move::drop(UnitaryPiece::Pawn, Square::A3)] {
+            assert_eq!(parse_move_code(&move_code(move_)), Some(move_));
+        }
+        for code in [
+            "", "e2", "e2e", "e2e4X", "e2e4l", "n@e4", "NB@e4", "N@e9", "@e4",
+        ] {
+            assert_eq!(parse_move_code(code), None, "{code}");
+        }
+    }
+
+    #[test]
+    fn test_piece_codes() {
+        assert_eq!(parse_piece_code("bP"), Some(UnitaryPiece::Pawn.black()));
+        assert_eq!(parse_piece_code("wNQ"), parse_piece_code("wQN"));
+        for code in ["", "w", "xQ", "wq", "wKQ", "wQNB"] {
+            assert_eq!(parse_piece_code(code), None, "{code}");
+        }
+        for piece in [UnitaryPiece::King.white(), UnitaryPiece::Rook.black()] {
+            assert_eq!(parse_piece_code(&piece_code(piece)), Some(piece));
+        }
+    }
+}
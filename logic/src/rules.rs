@@ -0,0 +1,198 @@
+//! The rules of merging chess that differ between implementations.
+//!
+//! This project was started before we knew about the 2016 rulebook and the official Android app,
+//! so it disagrees with them in a few places, and they also disagree with each other.
+//! A [`RuleSet`] stored on the board selects which behavior the move generator and [`crate::board_repr::BoardRepr::play`] use.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// How a unitary pawn may move onto a friendly piece to merge with it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PawnMerge {
+    /// Diagonally forward, as if capturing the piece.
+    Diagonal,
+    /// One square straight forward, as if moving to an empty square.
+    Forward,
+}
+
+/// What a piece containing a pawn becomes when it reaches the last rank.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Promotion {
+    /// The whole piece becomes a single queen, even if it was a combination.
+    WholePieceToQueen,
+    /// Only the pawn half becomes a queen, so a Knawn becomes a queen-knight.
+    PawnHalfToQueen,
+}
+
+/// A set of choices for the rules that are not agreed upon.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RuleSet {
+    pub pawn_merge: PawnMerge,
+
+    /// Whether a piece containing a pawn that just made a double step can be captured en passant.
+    pub en_passant: bool,
+
+    pub promotion: Promotion,
+
+    /// Whether the king can castle with a rook that is merged with another piece,
+    /// which then moves along with the rook.
+    pub castle_with_combined_rook: bool,
+}
+
+impl RuleSet {
+    /// The rules as this project originally implemented them.
+    pub const THIS_PROJECT: RuleSet = RuleSet {
+        pawn_merge: PawnMerge::Diagonal,
+        en_passant: true,
+        promotion: Promotion::WholePieceToQueen,
+        castle_with_combined_rook: true,
+    };
+
+    /// The rules of the official Android app, which has no en passant.
+    pub const ANDROID_APP: RuleSet = RuleSet {
+        pawn_merge: PawnMerge::Forward,
+        en_passant: false,
+        promotion: Promotion::PawnHalfToQueen,
+        castle_with_combined_rook: false,
+    };
+
+    /// The rules as written in the 2016 rulebook.
+    pub const RULEBOOK_2016: RuleSet = RuleSet {
+        pawn_merge: PawnMerge::Forward,
+        en_passant: true,
+        promotion: Promotion::PawnHalfToQueen,
+        castle_with_combined_rook: false,
+    };
+
+    /// The presets, with the names they are referred to by in game records.
+    pub const PRESETS: [(&'static str, RuleSet); 3] = [
+        ("this project", RuleSet::THIS_PROJECT),
+        ("Android app", RuleSet::ANDROID_APP),
+        ("2016 rulebook", RuleSet::RULEBOOK_2016),
+    ];
+
+    /// The name of the preset with these rules, if there is one.
+    pub fn name(&self) -> Option<&'static str> {
+        RuleSet::PRESETS
+            .iter()
+            .find(|(_, rules)| rules == self)
+            .map(|(name, _)| *name)
+    }
+
+    /// Finds a preset by its name.
+    pub fn from_name(name: &str) -> Option<RuleSet> {
+        RuleSet::PRESETS
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, rules)| *rules)
+    }
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        RuleSet::THIS_PROJECT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        board_repr::BoardRepr,
+        game_record::GameRecord,
+        pieces::{movement::Move, UnitaryPiece},
+        square::Square,
+    };
+
+    fn board(fen: &str, rules: RuleSet) -> BoardRepr {
+        let mut board = BoardRepr::from_fen(fen).unwrap();
+        board.rules = rules;
+        board
+    }
+
+    fn move_(from: Square, to: Square) -> Move {
+        Move {
+            from,
+            to,
+            which_half: None,
+            drop: None,
+        }
+    }
+
+    fn can_play(mut board: BoardRepr, move_: Move) -> bool {
+        board.play(move_).is_ok()
+    }
+
+    #[test]
+    fn test_pawn_merges() {
+        let fen = "4k3/8/8/5N2/4PN2/8/8/4K3 w - - 0 1";
+
+        let diagonal = board(fen, RuleSet::THIS_PROJECT);
+        assert!(can_play(diagonal, move_(Square::E4, Square::F5)));
+        assert!(can_play(diagonal, move_(Square::E4, Square::E5)));
+
+        let forward = board("4k3/8/8/4NN2/4P3/8/8/4K3 w - - 0 1", RuleSet::ANDROID_APP);
+        assert!(!can_play(forward, move_(Square::E4, Square::F5)));
+        let mut merged = forward;
+        merged.play(move_(Square::E4, Square::E5)).unwrap();
+        assert_eq!(merged.to_fen(), "4k3/8/8/4(NP)N2/8/8/8/4K3 b - - 0 1");
+
+        // Moving forward onto a piece isn't a merge under the diagonal rule
+        let mut diagonal = forward;
+        diagonal.rules = RuleSet::THIS_PROJECT;
+        assert!(!can_play(diagonal, move_(Square::E4, Square::E5)));
+    }
+
+    #[test]
+    fn test_en_passant() {
+        let fen = "4k3/4p3/8/3(NP)4/8/8/8/4K3 b - - 0 1";
+        for (rules, can_capture) in [(RuleSet::THIS_PROJECT, true), (RuleSet::ANDROID_APP, false)] {
+            let mut board = board(fen, rules);
+            board.play(move_(Square::E7, Square::E5)).unwrap();
+            assert_eq!(board.en_passant_square.is_some(), can_capture);
+            assert_eq!(can_play(board, move_(Square::D5, Square::E6)), can_capture);
+        }
+    }
+
+    #[test]
+    fn test_promotion() {
+        let fen = "4k3/1(NP)6/8/8/8/8/8/4K3 w - - 0 1";
+
+        let mut whole = board(fen, RuleSet::THIS_PROJECT);
+        whole.play(move_(Square::B7, Square::B8)).unwrap();
+        assert_eq!(whole[Square::B8], Some(UnitaryPiece::Queen.white()));
+
+        let mut half = board(fen, RuleSet::RULEBOOK_2016);
+        half.play(move_(Square::B7, Square::B8)).unwrap();
+        assert_eq!(half.to_fen(), "1(QN)2k3/8/8/8/8/8/8/4K3 b - - 0 1");
+    }
+
+    #[test]
+    fn test_castling_with_combined_rook() {
+        let fen = "4k3/8/8/8/8/8/8/4K2(NR) w K - 0 1";
+        let castle = move_(Square::E1, Square::G1);
+        assert!(can_play(board(fen, RuleSet::THIS_PROJECT), castle));
+        assert!(!can_play(board(fen, RuleSet::RULEBOOK_2016), castle));
+        let fen = "4k3/8/8/8/8/8/8/4K2R w K - 0 1";
+        assert!(can_play(board(fen, RuleSet::RULEBOOK_2016), castle));
+    }
+
+    #[test]
+    fn test_presets_in_game_records() {
+        for (name, rules) in RuleSet::PRESETS {
+            assert_eq!(rules.name(), Some(name));
+            assert_eq!(RuleSet::from_name(name), Some(rules));
+
+            let start = board(crate::fen::STARTING_FEN, rules);
+            let record = GameRecord::from_moves(start, &[], Default::default());
+            assert_eq!(record.start_position(), Ok(start));
+        }
+
+        let record = GameRecord::from_moves(BoardRepr::default(), &[], Default::default());
+        assert_eq!(record.tag(crate::game_record::TAG_RULES), None);
+    }
+}
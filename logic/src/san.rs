@@ -0,0 +1,447 @@
+//! Algebraic move notation, extended for merging chess.
+//!
+//! Moves are written like in regular SAN, with a few additions:
+//! - the piece that moves is named by its letter, or by both letters for a combination moving as a whole
+//!   (so a Knawn capturing en passant is `NPxe6`, while the pawn splitting off from it is `dxe6`);
+//! - a unitary piece moving onto a friendly piece to merge with it is marked with `+` before the destination,
+//!   like `N+e4` (a trailing `+` is still a check);
+//! - a drop from the pocket is written as the letter of the piece, `@` and the square, like `N@f3`,
+//!   also when the piece merges with a friendly piece there (a pawn drop can be written `@e4` or `P@e4`);
+//! - the optional ` e.p.` suffix after en passant captures is accepted, but never written.
+//!
+//! A split is written with the letter of the half that moves,
+//! and is disambiguated from other pieces with the same letter like in regular SAN.
+
+use std::fmt;
+
+use crate::{
+    board_repr::BoardRepr,
+    pieces::{
+        movement::{castling_side, get_all_legal_moves, move_info, CastlingSide, Move, MoveKind},
+        Piece, UnitaryPiece,
+    },
+    square::{File, Rank, Square},
+};
+
+/// Error when parsing a move in algebraic notation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseSanError {
+    /// The text is not a move in algebraic notation.
+    Syntax(String),
+    /// The text describes a move, but there is no such legal move in the position.
+    IllegalMove(String),
+    /// More than one legal move matches the text.
+    AmbiguousMove(String),
+}
+
+impl fmt::Display for ParseSanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseSanError::Syntax(san) => write!(f, "invalid move notation: {san:?}"),
+            ParseSanError::IllegalMove(san) => write!(f, "illegal move: {san:?}"),
+            ParseSanError::AmbiguousMove(san) => write!(f, "ambiguous move: {san:?}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseSanError {}
+
+/// All the legal moves in the position, including the moves of single halves of combinations
+/// and drops.
+/// If both halves of a combination are the same piece, only the left half's moves are included.
+fn legal_moves(board: &BoardRepr) -> Vec<Move> {
+    get_all_legal_moves(board, board.side_to_move).to_vec()
+}
+
+/// The part of the piece on the source square that actually moves, or the dropped piece.
+fn moving_piece(board: &BoardRepr, move_: Move) -> Option<Piece> {
+    if let Some(piece) = move_.drop {
+        return Some(piece.into());
+    }
+    let piece = board[move_.from]?.piece();
+    Some(match (piece, move_.which_half) {
+        (Piece::Combination(combo), Some(half)) => Piece::Unitary(combo[half]),
+        _ => piece,
+    })
+}
+
+/// The letters that name the moving piece: empty for a unitary pawn.
+fn piece_label(piece: Piece) -> String {
+    match piece {
+        Piece::Unitary(UnitaryPiece::Pawn) => String::new(),
+        Piece::Unitary(p) => p.char().to_string(),
+        Piece::Combination(combo) => format!("{}{}", combo.first().char(), combo.second().char()),
+    }
+}
+
+fn is_merge(board: &BoardRepr, move_: Move) -> bool {
+    move_info(board, move_).is_some_and(|info| info.kind == MoveKind::Merge)
+}
+
+fn is_capture(board: &BoardRepr, move_: Move) -> bool {
+    move_info(board, move_)
+        .is_some_and(|info| matches!(info.kind, MoveKind::Capture | MoveKind::EnPassant))
+}
+
+fn is_promotion(board: &BoardRepr, move_: Move) -> bool {
+    move_info(board, move_).is_some_and(|info| info.promotion)
+}
+
+/// Whether the two moves have the same effect on the board,
+/// which happens when they move different halves of a combination of two identical pieces.
+fn is_same_move(board: &BoardRepr, a: Move, b: Move) -> bool {
+    a.from == b.from
+        && a.to == b.to
+        && a.which_half.is_some() == b.which_half.is_some()
+        && moving_piece(board, a) == moving_piece(board, b)
+}
+
+impl Move {
+    /// Writes this move in algebraic notation.
+    /// The move must be legal in the given position.
+    pub fn to_san(&self, board: &BoardRepr) -> String {
+        let move_ = *self;
+        let mut san = if let Some(side) = castling_side(board, move_) {
+            match side {
+                CastlingSide::KingSide => "O-O".to_string(),
+                CastlingSide::QueenSide => "O-O-O".to_string(),
+            }
+        } else if let Some(piece) = move_.drop {
+            format!("{}@{}", piece.char(), move_.to)
+        } else {
+            let moving = moving_piece(board, move_).expect("no piece on the source square");
+            let label = piece_label(moving);
+            let marker = if is_capture(board, move_) {
+                "x"
+            } else if is_merge(board, move_) {
+                "+"
+            } else {
+                ""
+            };
+
+            // Find the other moves that would be written the same way
+            let others: Vec<Move> = legal_moves(board)
+                .into_iter()
+                .filter(|m| {
+                    m.to == move_.to
+                        && m.from != move_.from
+                        && !m.is_drop()
+                        && moving_piece(board, *m).map(piece_label) == Some(label.clone())
+                })
+                .collect();
+
+            let is_pawn = label.is_empty();
+            let mut disambiguation = String::new();
+            if is_pawn && !marker.is_empty() || !others.is_empty() {
+                let file_is_enough = others.iter().all(|m| m.from.file() != move_.from.file());
+                let rank_is_enough = others.iter().all(|m| m.from.rank() != move_.from.rank());
+                if file_is_enough || is_pawn && others.is_empty() {
+                    disambiguation.push(move_.from.file().char());
+                } else if rank_is_enough && !is_pawn {
+                    disambiguation.push(move_.from.rank().char());
+                } else {
+                    disambiguation.push_str(&move_.from.to_string());
+                }
+            }
+
+            let mut san = format!("{label}{disambiguation}{marker}{}", move_.to);
+            if is_promotion(board, move_) {
+                san.push_str("=Q");
+            }
+            san
+        };
+
+        let mut after = *board;
+        if after.play(move_).is_ok() {
+            let opponent = after.side_to_move;
+            if after.king_in_check(opponent) {
+                if legal_moves(&after).is_empty() {
+                    san.push('#');
+                } else {
+                    san.push('+');
+                }
+            }
+        }
+
+        san
+    }
+}
+
+impl BoardRepr {
+    /// Finds the legal move in this position described by the given algebraic notation.
+    pub fn parse_san(&self, san: &str) -> Result<Move, ParseSanError> {
+        let syntax_error = || ParseSanError::Syntax(san.to_string());
+
+        // Remove the suffixes that don't affect which move this is
+        let mut text = san.trim();
+        text = text.strip_suffix("e.p.").unwrap_or(text).trim_end();
+        text = text.trim_end_matches(['!', '?']);
+        text = text.trim_end_matches(['+', '#']);
+        if text.is_empty() {
+            return Err(syntax_error());
+        }
+
+        let candidates = legal_moves(self);
+
+        let matching: Vec<Move> = match text {
+            "O-O" | "0-0" | "O-O-O" | "0-0-0" => {
+                let side = if text.len() == 3 {
+                    CastlingSide::KingSide
+                } else {
+                    CastlingSide::QueenSide
+                };
+                candidates
+                    .into_iter()
+                    .filter(|m| castling_side(self, *m) == Some(side))
+                    .collect()
+            }
+            _ if text.contains('@') => {
+                let (letter, to) = text.split_once('@').ok_or_else(syntax_error)?;
+                let mut letters = letter.chars();
+                let piece = match (letters.next(), letters.next()) {
+                    (None, _) => Some(UnitaryPiece::Pawn),
+                    (Some(c), None) if c.is_ascii_uppercase() => UnitaryPiece::from_char(c),
+                    _ => None,
+                }
+                .ok_or_else(syntax_error)?;
+                let to: Square = to.parse().map_err(|_| syntax_error())?;
+                candidates
+                    .into_iter()
+                    .filter(|m| *m == Move::drop(piece, to))
+                    .collect()
+            }
+            _ => {
+                let (text, promotion) = match text.split_once('=') {
+                    Some((text, "Q")) => (text, true),
+                    Some(_) => return Err(syntax_error()),
+                    None => (text, false),
+                };
+
+                if text.len() < 2 || !text.is_char_boundary(text.len() - 2) {
+                    return Err(syntax_error());
+                }
+                let (text, to) = text.split_at(text.len() - 2);
+                let to: Square = to.parse().map_err(|_| syntax_error())?;
+
+                let (text, capture, merge) = if let Some(text) = text.strip_suffix('x') {
+                    (text, true, false)
+                } else if let Some(text) = text.strip_suffix('+') {
+                    (text, false, true)
+                } else {
+                    (text, false, false)
+                };
+
+                let label_len = text
+                    .find(|c: char| !c.is_ascii_uppercase())
+                    .unwrap_or(text.len());
+                let (label, hint) = text.split_at(label_len);
+                if label.len() > 2 || label.chars().any(|c| UnitaryPiece::from_char(c).is_none()) {
+                    return Err(syntax_error());
+                }
+                // A lone "P" is the same as no letter at all
+                let label = if label == "P" { "" } else { label };
+
+                let mut hint_chars = hint.chars();
+                let mut from_file = None;
+                let mut from_rank = None;
+                let mut next = hint_chars.next();
+                if let Some(file) = next.and_then(File::from_char) {
+                    from_file = Some(file);
+                    next = hint_chars.next();
+                }
+                if let Some(rank) = next.and_then(Rank::from_char) {
+                    from_rank = Some(rank);
+                    next = hint_chars.next();
+                }
+                if next.is_some() {
+                    return Err(syntax_error());
+                }
+
+                // The letters of a combination can be given in any order
+                let label_matches = |m: &Move| {
+                    let actual = moving_piece(self, *m).map(piece_label).unwrap_or_default();
+                    actual == label || actual.chars().rev().collect::<String>() == label
+                };
+
+                candidates
+                    .into_iter()
+                    .filter(|m| m.to == to && !m.is_drop() && castling_side(self, *m).is_none())
+                    .filter(label_matches)
+                    .filter(|m| from_file.is_none_or(|f| m.from.file() == f))
+                    .filter(|m| from_rank.is_none_or(|r| m.from.rank() == r))
+                    .filter(|m| !capture || is_capture(self, *m))
+                    .filter(|m| !merge || is_merge(self, *m))
+                    .filter(|m| !promotion || is_promotion(self, *m))
+                    .collect()
+            }
+        };
+
+        match matching.as_slice() {
+            [] => Err(ParseSanError::IllegalMove(san.to_string())),
+            [first, rest @ ..] => {
+                if rest.iter().all(|m| is_same_move(self, *first, *m)) {
+                    Ok(*first)
+                } else {
+                    Err(ParseSanError::AmbiguousMove(san.to_string()))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pieces::PieceHalf;
+
+    fn check(fen: &str, san: &str, from: Square, to: Square, which_half: Option<PieceHalf>) {
+        let board = BoardRepr::from_fen(fen).unwrap();
+        let move_ = Move {
+            from,
+            to,
+            which_half,
+            drop: None,
+        };
+        assert_eq!(board.parse_san(san), Ok(move_), "parsing {san} in {fen}");
+        assert_eq!(move_.to_san(&board), san, "writing {move_:?} in {fen}");
+    }
+
+    #[test]
+    fn test_normal_moves() {
+        let start = crate::fen::STARTING_FEN;
+        check(start, "e4", Square::E2, Square::E4, None);
+        check(start, "Nf3", Square::G1, Square::F3, None);
+
+        let fen = "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2";
+        check(fen, "exd5", Square::E4, Square::D5, None);
+    }
+
+    #[test]
+    fn test_merges_and_combinations() {
+        // Both knights can merge with the pawn on d2, forming a Knawn
+        let fen = "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 2";
+        check(fen, "Nf+d2", Square::F3, Square::D2, None);
+        // The pawn on d2 merges diagonally with the knight on c3
+        let fen = "rnbqkbnr/pppp1ppp/8/4p3/4P3/2N5/PPPP1PPP/R1BQKBNR w KQkq - 0 2";
+        check(fen, "d+c3", Square::D2, Square::C3, None);
+
+        // A Knawn moving as a whole, and its halves moving separately
+        let fen = "rnbqkbnr/pppp1ppp/8/4p3/8/2(NP)5/PPPP1PPP/R1BQKBNR w KQkq - 0 3";
+        check(fen, "NPb5", Square::C3, Square::B5, None);
+        check(fen, "Nb5", Square::C3, Square::B5, Some(PieceHalf::Left));
+        check(fen, "c4", Square::C3, Square::C4, Some(PieceHalf::Right));
+        assert_eq!(
+            BoardRepr::from_fen(fen).unwrap().parse_san("PNb5"),
+            Ok(Move {
+                from: Square::C3,
+                to: Square::B5,
+                which_half: None,
+                drop: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_en_passant() {
+        // Black just played e7-e5, and White has a Knawn on d5
+        let fen = "rnbqkbnr/pppp1ppp/8/3(NP)p3/8/8/PPPP1PPP/R1BQKBNR w KQkq e6 0 3";
+        check(fen, "NPxe6", Square::D5, Square::E6, None);
+        check(fen, "dxe6", Square::D5, Square::E6, Some(PieceHalf::Right));
+
+        let board = BoardRepr::from_fen(fen).unwrap();
+        assert_eq!(
+            board.parse_san("NPdxe6 e.p."),
+            Ok(Move {
+                from: Square::D5,
+                to: Square::E6,
+                which_half: None,
+                drop: None,
+            })
+        );
+        assert_eq!(
+            board.parse_san("dxe6 e.p."),
+            Ok(Move {
+                from: Square::D5,
+                to: Square::E6,
+                which_half: Some(PieceHalf::Right),
+                drop: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_castling_and_promotion() {
+        let fen = "r3k2r/pppq1ppp/8/8/8/8/PPPQ1PPP/R3K2R w KQkq - 0 1";
+        check(fen, "O-O", Square::E1, Square::G1, None);
+        check(fen, "O-O-O", Square::E1, Square::C1, None);
+        // In Chess960, a king that moves one square castles onto its own rook
+        let fen = "4k3/8/8/8/8/8/8/RK4R1 w KQ - 0 1";
+        check(fen, "O-O", Square::B1, Square::G1, None);
+        check(fen, "O-O-O", Square::B1, Square::A1, None);
+
+        let fen = "7k/1P6/8/8/8/8/8/K7 w - - 0 1";
+        check(fen, "b8=Q+", Square::B7, Square::B8, None);
+        let fen = "7k/1(RP)6/8/8/8/8/8/K7 w - - 0 1";
+        check(fen, "RPb8=Q+", Square::B7, Square::B8, None);
+    }
+
+    #[test]
+    fn test_drops() {
+        let board =
+            BoardRepr::from_fen("rnb1kbnr/ppp1pppp/8/3q4/8/8/PPPP1PPP/RNBQKBNR[NPp] w KQkq - 0 3")
+                .unwrap();
+        let cases = [
+            ("P@e4", Move::drop(UnitaryPiece::Pawn, Square::E4)),
+            // Merging with the pawn that is already there
+            ("N@d2", Move::drop(UnitaryPiece::Knight, Square::D2)),
+            ("N@f6+", Move::drop(UnitaryPiece::Knight, Square::F6)),
+        ];
+        for (san, move_) in cases {
+            assert_eq!(board.parse_san(san), Ok(move_), "{san}");
+            assert_eq!(move_.to_san(&board), san);
+        }
+
+        assert_eq!(
+            board.parse_san("@e4"),
+            Ok(Move::drop(UnitaryPiece::Pawn, Square::E4))
+        );
+        assert_eq!(
+            board.parse_san("Q@e4"),
+            Err(ParseSanError::IllegalMove("Q@e4".into()))
+        );
+        assert_eq!(
+            board.parse_san("P@e1"),
+            Err(ParseSanError::IllegalMove("P@e1".into()))
+        );
+        assert_eq!(
+            board.parse_san("NP@e4"),
+            Err(ParseSanError::Syntax("NP@e4".into()))
+        );
+    }
+
+    #[test]
+    fn test_disambiguation() {
+        // Two rooks on the same rank can reach d4
+        let fen = "4k3/8/8/8/R6R/8/8/4K3 w - - 0 1";
+        check(fen, "Rad4", Square::A4, Square::D4, None);
+        // A queen splitting off from a double queen, and a unitary queen
+        let fen = "6k1/8/8/8/Q7/8/8/3(QQ)K3 w - - 0 1";
+        check(fen, "Qdd4", Square::D1, Square::D4, Some(PieceHalf::Left));
+        check(fen, "Qad4", Square::A4, Square::D4, None);
+        check(fen, "QQd4", Square::D1, Square::D4, None);
+
+        let board = BoardRepr::from_fen(fen).unwrap();
+        assert_eq!(
+            board.parse_san("Qd4"),
+            Err(ParseSanError::AmbiguousMove("Qd4".into()))
+        );
+        assert_eq!(
+            board.parse_san("Qh8"),
+            Err(ParseSanError::IllegalMove("Qh8".into()))
+        );
+        assert_eq!(
+            board.parse_san("Xd4"),
+            Err(ParseSanError::Syntax("Xd4".into()))
+        );
+    }
+}
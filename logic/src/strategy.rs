@@ -4,26 +4,43 @@ use minimax_alpha_beta::strategy::game_strategy::GameStrategy;
 #[cfg(feature = "minimax")]
 use crate::{
     board_repr::BoardRepr,
-    pieces::{
-        movement::{get_all_legal_moves, Move},
-        Color,
-    },
+    game::{Game, GameOutcome},
+    pieces::{movement::Move, Color},
 };
 
 #[cfg(feature = "minimax")]
 pub struct MergingChessStrategy {
-    board: BoardRepr,
+    /// The game being searched, whose variant decides when it ends.
+    /// The moves played during the search are taken back from it.
+    game: Game,
 
-    prev_boards: Vec<(BoardRepr, Move)>,
+    /// The outcome of each position from the start of the search to the current one,
+    /// worked out once when the position is reached, since the search asks for it several times.
+    outcomes: Vec<Option<GameOutcome>>,
+}
+
+#[cfg(feature = "minimax")]
+impl MergingChessStrategy {
+    fn outcome(&self) -> Option<GameOutcome> {
+        *self
+            .outcomes
+            .last()
+            .expect("there is always a current position")
+    }
 }
 
 #[cfg(feature = "minimax")]
 impl From<BoardRepr> for MergingChessStrategy {
     fn from(board: BoardRepr) -> Self {
-        Self {
-            board,
-            prev_boards: Vec::new(),
-        }
+        Game::new(board).into()
+    }
+}
+
+#[cfg(feature = "minimax")]
+impl From<Game> for MergingChessStrategy {
+    fn from(game: Game) -> Self {
+        let outcomes = vec![game.outcome()];
+        Self { game, outcomes }
     }
 }
 
@@ -38,22 +55,33 @@ impl GameStrategy for MergingChessStrategy {
     fn evaluate(&self) -> f64 {
         // Return the amount of material advantage that the white player has.
         let mut material = 0.0;
-
-        for (_square, piece) in self.board.iter_pieces() {
+        let component_value = |component| match component {
+            crate::pieces::UnitaryPiece::Queen => 9.0,
+            crate::pieces::UnitaryPiece::Bishop => 3.0,
+            crate::pieces::UnitaryPiece::Knight => 3.0,
+            crate::pieces::UnitaryPiece::Rook => 5.0,
+            crate::pieces::UnitaryPiece::Pawn => 1.0,
+            crate::pieces::UnitaryPiece::King => 1000.0,
+        };
+        let sign = |color| match color {
+            Color::White => 1.0,
+            Color::Black => -1.0,
+        };
+
+        for (_square, piece) in self.game.board().iter_pieces() {
             let mut value: f64 = 0.0;
             piece.piece().for_components(|component| {
-                value += match component {
-                    crate::pieces::UnitaryPiece::Queen => 9.0,
-                    crate::pieces::UnitaryPiece::Bishop => 3.0,
-                    crate::pieces::UnitaryPiece::Knight => 3.0,
-                    crate::pieces::UnitaryPiece::Rook => 5.0,
-                    crate::pieces::UnitaryPiece::Pawn => 1.0,
-                    crate::pieces::UnitaryPiece::King => 1000.0,
-                }
+                value += component_value(component);
             });
-            match piece.color() {
-                Color::White => material += value,
-                Color::Black => material -= value,
+            material += sign(piece.color()) * value;
+        }
+
+        // Pieces in hand are worth as much as the ones on the board
+        if let Some(pockets) = self.game.board().pockets {
+            for color in [Color::White, Color::Black] {
+                for (piece, count) in pockets.pieces(color) {
+                    material += sign(color) * component_value(piece) * f64::from(count);
+                }
             }
         }
 
@@ -62,63 +90,39 @@ impl GameStrategy for MergingChessStrategy {
     }
 
     fn get_winner(&self) -> Option<Self::Player> {
-        // If a player has no legal moves, and is in check,
-        // the other player is the winner
-        for player in [Color::White, Color::Black] {
-            let legal_moves = get_all_legal_moves(&self.board, player);
-            if legal_moves.is_empty() && self.board.king_in_check(player) {
-                return Some(player.opposite());
-            }
-        }
-        None
+        // The variant decides how the game can be won
+        self.outcome().and_then(GameOutcome::winner)
     }
 
     fn is_game_tied(&self) -> bool {
-        // The game is a draw:
-        // - when the player to move has no legal moves, and is not in check (stalemate)
-        // - when both players have insufficient material
-        if self.get_available_moves().is_empty() {
-            return true;
-        }
-
-        if self
-            .board
-            .has_insufficient_material(self.board.side_to_move)
-        {
-            return true;
-        }
-
-        false
+        matches!(self.outcome(), Some(GameOutcome::Draw { .. }))
     }
 
     fn is_game_complete(&self) -> bool {
-        self.is_game_tied() || self.get_winner().is_some()
+        self.outcome().is_some()
     }
 
     fn get_available_moves(&self) -> Vec<Self::Move> {
-        let legal_moves = get_all_legal_moves(&self.board, self.board.side_to_move);
-        legal_moves.into_iter().collect()
+        self.game.legal_moves()
     }
 
     fn play(&mut self, mv: &Self::Move, _maximizer: bool) {
-        self.prev_boards.push((self.board, *mv));
-        self.board
-            .play(*mv)
-            .expect("alpha-beta algorithm produced illegal move");
+        // The alpha-beta algorithm only plays moves from `get_available_moves`,
+        // so they don't need to be checked again
+        self.game.make_move(*mv);
+        self.outcomes.push(self.game.outcome());
     }
 
     fn clear(&mut self, mv: &Self::Move) {
-        let last = self.prev_boards.last().unwrap();
-        if last.1 == *mv {
-            self.board = last.0;
-            self.prev_boards.pop();
-        } else {
-            panic!("Attempted to clear a move that was not played: {mv:?}");
+        match self.game.undo() {
+            Some(undone) if undone == *mv => {}
+            _ => panic!("Attempted to clear a move that was not played: {mv:?}"),
         }
+        self.outcomes.pop();
     }
 
     fn get_board(&self) -> &Self::Board {
-        &self.board
+        self.game.board()
     }
 
     fn is_a_valid_move(&self, mv: &Self::Move) -> bool {
@@ -134,6 +138,38 @@ impl GameStrategy for MergingChessStrategy {
             from: crate::square::Square::A1,
             to: crate::square::Square::H7,
             which_half: None,
+            drop: None,
         }
     }
 }
+
+#[cfg(all(test, feature = "minimax"))]
+mod tests {
+    use super::*;
+    use crate::game::WinReason;
+
+    #[test]
+    fn test_outcome_follows_moves() {
+        let mut strategy = MergingChessStrategy::from(
+            BoardRepr::from_fen("rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2")
+                .unwrap(),
+        );
+        assert!(!strategy.is_game_complete());
+
+        let mate = strategy.get_board().parse_san("Qh4").unwrap();
+        strategy.play(&mate, true);
+        assert_eq!(
+            strategy.outcome(),
+            Some(GameOutcome::Win {
+                winner: Color::Black,
+                reason: WinReason::Checkmate
+            })
+        );
+        assert_eq!(strategy.get_winner(), Some(Color::Black));
+        assert!(!strategy.is_game_tied());
+
+        strategy.clear(&mate);
+        assert!(!strategy.is_game_complete());
+        assert_eq!(strategy.get_winner(), None);
+    }
+}
@@ -0,0 +1,163 @@
+//! Transforming whole positions into equivalent ones.
+//!
+//! The rules are the same for both colors and on both wings, so a position
+//! with the colors swapped and the board turned upside down plays exactly like the original,
+//! and so does a position mirrored left to right, as long as no one can castle.
+//! The pieces in hand and the checks given change sides along with the colors.
+//! Comparing the two is a cheap way to catch asymmetry bugs in the move generator and the evaluation.
+
+use crate::{board_repr::BoardRepr, pieces::movement::Move, pocket::Pockets, square::Square};
+
+fn map_move(move_: Move, f: fn(Square) -> Square) -> Move {
+    Move {
+        from: f(move_.from),
+        to: f(move_.to),
+        which_half: move_.which_half,
+        drop: move_.drop,
+    }
+}
+
+impl BoardRepr {
+    /// The same position seen from the other side:
+    /// the ranks are mirrored and every piece changes color,
+    /// along with the side to move, the castling rights, the en passant square, the pockets
+    /// and the checks given.
+    #[must_use]
+    pub fn flip_colors(&self) -> BoardRepr {
+        let mut board = *self;
+        for square in Square::ALL {
+            board.set_piece(
+                square.flip_vertical(),
+                self[square].map(|piece| piece.flip_color()),
+            );
+        }
+        board.side_to_move = self.side_to_move.opposite();
+        let [white_king, white_queen, black_king, black_queen] = self.castling_rights;
+        board.castling_rights = [black_king, black_queen, white_king, white_queen];
+        let [white_files, black_files] = self.castling_files;
+        board.castling_files = [black_files, white_files];
+        board.en_passant_square = self.en_passant_square.map(Square::flip_vertical);
+        board.previous_move = self
+            .previous_move
+            .map(|move_| map_move(move_, Square::flip_vertical));
+        board.pockets = self.pockets.map(Pockets::swap_colors);
+        board.checks_given = self.checks_given.map(|[white, black]| [black, white]);
+        board.refresh_zobrist_key();
+        board
+    }
+
+    /// The same position with the files mirrored, so that the a-file becomes the h-file.
+    ///
+    /// The kings end up on the wrong file to castle,
+    /// so the castling rights are dropped.
+    #[must_use]
+    pub fn mirror_files(&self) -> BoardRepr {
+        let mut board = *self;
+        for square in Square::ALL {
+            board.set_piece(square.flip_horizontal(), self[square]);
+        }
+        board.castling_rights = [false; 4];
+        board.forget_lost_castling_files();
+        board.en_passant_square = self.en_passant_square.map(Square::flip_horizontal);
+        board.previous_move = self
+            .previous_move
+            .map(|move_| map_move(move_, Square::flip_horizontal));
+        board.refresh_zobrist_key();
+        board
+    }
+}
+
+/// Checks that the position plays the same after [`BoardRepr::flip_colors`]
+/// and [`BoardRepr::mirror_files`]: the legal moves are the transformed ones,
+/// and the evaluation changes sign when the colors are swapped.
+///
+/// # Panics
+///
+/// Panics if the position isn't symmetric.
+///
+/// Only available with the `test-utils` feature, for testing code that builds on this crate.
+#[cfg(any(test, feature = "test-utils"))]
+pub fn assert_symmetric(board: &BoardRepr) {
+    use std::collections::HashSet;
+
+    use crate::pieces::movement::get_all_legal_moves;
+
+    let moves = |board: &BoardRepr, f: fn(Square) -> Square| -> HashSet<Move> {
+        get_all_legal_moves(board, board.side_to_move)
+            .into_iter()
+            .map(|move_| map_move(move_, f))
+            .collect()
+    };
+    let fen = board.to_fen();
+
+    let flipped = board.flip_colors();
+    assert_eq!(flipped.validate(), Ok(()), "{fen}");
+    assert_eq!(flipped.flip_colors(), *board, "{fen}");
+    assert_eq!(
+        moves(board, Square::flip_vertical),
+        moves(&flipped, |square| square),
+        "{fen}"
+    );
+
+    let mut without_castling = *board;
+    without_castling.castling_rights = [false; 4];
+    without_castling.forget_lost_castling_files();
+    without_castling.refresh_zobrist_key();
+    let mirrored = board.mirror_files();
+    assert_eq!(mirrored.validate(), Ok(()), "{fen}");
+    assert_eq!(mirrored.mirror_files(), without_castling, "{fen}");
+    assert_eq!(
+        moves(&without_castling, Square::flip_horizontal),
+        moves(&mirrored, |square| square),
+        "{fen}"
+    );
+
+    #[cfg(feature = "minimax")]
+    {
+        use minimax_alpha_beta::strategy::game_strategy::GameStrategy;
+
+        use crate::strategy::MergingChessStrategy;
+
+        let evaluate = |board: BoardRepr| MergingChessStrategy::from(board).evaluate();
+        assert_eq!(evaluate(flipped), -evaluate(*board), "{fen}");
+        assert_eq!(evaluate(mirrored), evaluate(*board), "{fen}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        perft::parse_perft_suite,
+        pieces::{movement::get_all_legal_moves, UnitaryPiece},
+    };
+
+    #[test]
+    fn test_flip_colors() {
+        let board = BoardRepr::from_fen("r3k3/8/8/3(NP)p3/8/8/8/4K2(RQ) w Kq e6 0 1").unwrap();
+        assert_eq!(
+            board.flip_colors(),
+            BoardRepr::from_fen("4k2(rq)/8/8/8/3(np)P3/8/8/R3K3 b Qk e3 0 1").unwrap()
+        );
+        let mirrored = board.mirror_files();
+        assert_eq!(mirrored[Square::A1], board[Square::H1]);
+        assert_eq!(mirrored[Square::D1], Some(UnitaryPiece::King.white()));
+        assert_eq!(mirrored.en_passant_square, Some(Square::D5));
+        assert_eq!(mirrored.castling_rights, [false; 4]);
+    }
+
+    #[test]
+    fn test_suite_is_symmetric() {
+        for position in parse_perft_suite(include_str!("../tests/perft_suite.epd")) {
+            let board = BoardRepr::from_fen(&position.fen).unwrap();
+            assert_symmetric(&board);
+
+            // The positions after one move cover en passant and the previous move
+            for move_ in get_all_legal_moves(&board, board.side_to_move) {
+                let mut after = board;
+                after.make_move(move_);
+                assert_symmetric(&after);
+            }
+        }
+    }
+}
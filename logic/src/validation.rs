@@ -0,0 +1,266 @@
+//! Checking that a position could be reached in a game,
+//! as far as the rest of the crate relies on it.
+//!
+//! The move generator assumes that each side has at most one king,
+//! and that the castling rights and en passant square describe pieces that are really there.
+//! Positions that come from outside (FEN, a board editor, the server) should be validated
+//! before anything is done with them.
+//!
+//! A game needs both kings, but a board being set up may not have them yet:
+//! the `_lenient` constructors, like [`BoardRepr::from_fen_lenient`], accept a side without a king,
+//! and check everything else as usual.
+
+use std::fmt;
+
+use crate::{
+    board_repr::BoardRepr,
+    pieces::{movement::CastlingSide, Color, ColorPiece, UnitaryPiece},
+    square::{Rank, Square},
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A reason why a position can't be played from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PositionError {
+    /// The side has no king.
+    MissingKing(Color),
+    /// The side has more than one king.
+    TooManyKings(Color),
+    /// A piece containing a pawn is on the rank where it would have been promoted.
+    /// Unlike in regular chess, a pawn can stand on its own first rank,
+    /// by retreating there as part of a combination.
+    PawnOnLastRank(Square),
+    /// The side can castle, but its king or the rook isn't on its starting square.
+    InvalidCastlingRight { color: Color, side: CastlingSide },
+    /// The en passant square doesn't hold a piece containing a pawn that just made a double step.
+    InvalidEnPassantSquare(Square),
+    /// The side that just moved has left its king in check.
+    OpponentInCheck,
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PositionError::MissingKing(color) => write!(f, "{color:?} has no king"),
+            PositionError::TooManyKings(color) => write!(f, "{color:?} has more than one king"),
+            PositionError::PawnOnLastRank(square) => {
+                write!(f, "there is an unpromoted pawn on {square}")
+            }
+            PositionError::InvalidCastlingRight { color, side } => {
+                let side = match side {
+                    CastlingSide::KingSide => "king side",
+                    CastlingSide::QueenSide => "queen side",
+                };
+                write!(
+                    f,
+                    "{color:?} can castle on the {side}, but the king or rook has moved"
+                )
+            }
+            PositionError::InvalidEnPassantSquare(square) => {
+                write!(f, "there is no pawn to capture en passant on {square}")
+            }
+            PositionError::OpponentInCheck => {
+                f.write_str("the side that is not to move is in check")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+impl BoardRepr {
+    /// Checks that the position is one that the game can be played from,
+    /// and lists everything that is wrong with it if not.
+    pub fn validate(&self) -> Result<(), Vec<PositionError>> {
+        self.check_position(true)
+    }
+
+    /// Like [`BoardRepr::validate`], but a side may have no king,
+    /// as on a board that is still being set up.
+    /// Such a side is never in check, see [`BoardRepr::try_king_square`].
+    pub fn validate_lenient(&self) -> Result<(), Vec<PositionError>> {
+        self.check_position(false)
+    }
+
+    fn check_position(&self, require_kings: bool) -> Result<(), Vec<PositionError>> {
+        let mut errors = vec![];
+        let bitboards = self.bitboards();
+
+        let mut kings_ok = true;
+        for color in [Color::White, Color::Black] {
+            let kings = bitboards.by_component(UnitaryPiece::King) & bitboards.by_color(color);
+            match kings.count() {
+                0 if require_kings => errors.push(PositionError::MissingKing(color)),
+                0 | 1 => {}
+                _ => {
+                    errors.push(PositionError::TooManyKings(color));
+                    kings_ok = false;
+                }
+            }
+        }
+
+        for (square, piece) in self.iter_pieces() {
+            let last_rank = match piece.color() {
+                Color::White => Rank::Eighth,
+                Color::Black => Rank::First,
+            };
+            if piece.piece().contains(UnitaryPiece::Pawn) && square.rank() == last_rank {
+                errors.push(PositionError::PawnOnLastRank(square));
+            }
+        }
+
+        for color in [Color::White, Color::Black] {
+            for side in CastlingSide::ALL {
+                if self.castling_rights[side.right_index(color)]
+                    && !self.could_castle_later(color, side)
+                {
+                    errors.push(PositionError::InvalidCastlingRight { color, side });
+                }
+            }
+        }
+
+        if let Some(square) = self.en_passant_square {
+            if !self.is_valid_en_passant_square(square) {
+                errors.push(PositionError::InvalidEnPassantSquare(square));
+            }
+        }
+
+        if kings_ok && self.king_in_check(self.side_to_move.opposite()) {
+            errors.push(PositionError::OpponentInCheck);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Checks that the king and a piece containing the rook are on their starting squares,
+    /// which is needed to have the castling right,
+    /// and that the rook starts on the side of the king that it castles to.
+    fn could_castle_later(&self, color: Color, side: CastlingSide) -> bool {
+        let files = self.castling_files[color as usize];
+        let rook_side = match side {
+            CastlingSide::KingSide => files.rook(side) > files.king,
+            CastlingSide::QueenSide => files.rook(side) < files.king,
+        };
+        rook_side
+            && self[side.king_from(self, color)]
+                .is_some_and(|p| p.color() == color && p.piece() == UnitaryPiece::King.into())
+            && self[side.rook_from(self, color)]
+                .is_some_and(|p| p.color() == color && p.piece().contains(UnitaryPiece::Rook))
+    }
+
+    /// Checks that the piece on the square could have just made a double step:
+    /// it contains a pawn of the side that just moved,
+    /// and the square it passed through is empty.
+    /// The square it started from may still hold the other half of a combination
+    /// that the pawn split from.
+    fn is_valid_en_passant_square(&self, square: Square) -> bool {
+        let moved = self.side_to_move.opposite();
+        let (rank, behind): (Rank, fn(Square) -> Option<Square>) = match moved {
+            Color::White => (Rank::Fourth, Square::down),
+            Color::Black => (Rank::Fifth, Square::up),
+        };
+        square.rank() == rank
+            && self[square]
+                .is_some_and(|p| p.color() == moved && p.piece().contains(UnitaryPiece::Pawn))
+            && behind(square).is_some_and(|passed| self[passed].is_none())
+    }
+}
+
+/// Sets up a position from the pieces alone, with White to move,
+/// and with every castling right whose king and rook are on their starting squares.
+impl TryFrom<[Option<ColorPiece>; 64]> for BoardRepr {
+    type Error = Vec<PositionError>;
+
+    fn try_from(pieces: [Option<ColorPiece>; 64]) -> Result<Self, Self::Error> {
+        let mut board = BoardRepr::empty();
+        board.set_pieces(&pieces);
+        for color in [Color::White, Color::Black] {
+            for side in CastlingSide::ALL {
+                board.castling_rights[side.right_index(color)] =
+                    board.could_castle_later(color, side);
+            }
+        }
+        board.refresh_zobrist_key();
+        board.validate()?;
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(BoardRepr::default().validate(), Ok(()));
+
+        use PositionError as E;
+        let cases = [
+            (
+                "4k3/8/8/8/8/8/8/8 w - - 0 1",
+                vec![E::MissingKing(Color::White)],
+            ),
+            (
+                "4k3/8/8/8/8/8/8/3KK3 w - - 0 1",
+                vec![E::TooManyKings(Color::White)],
+            ),
+            // A pawn can retreat to its own first rank, but not stay on the last one
+            (
+                "1P2k3/8/8/8/8/8/8/(RP)3K3 w - - 0 1",
+                vec![E::PawnOnLastRank(Square::B8)],
+            ),
+            // As in X-FEN, K stands for the outermost rook, wherever it is
+            ("4k3/8/8/8/8/8/8/4K1R1 w K - 0 1", vec![]),
+            (
+                "4k3/8/8/8/8/8/8/R3K3 w K - 0 1",
+                vec![E::InvalidCastlingRight {
+                    color: Color::White,
+                    side: CastlingSide::KingSide,
+                }],
+            ),
+            ("4k3/8/8/8/8/8/8/4K2(NR) w K - 0 1", vec![]),
+            ("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1", vec![]),
+            // The pawn may have split from a combination, leaving its other half behind
+            ("4k3/8/8/8/4P3/8/4N3/4K3 b - e3 0 1", vec![]),
+            (
+                "4k3/8/8/8/4P3/4N3/8/4K3 b - e3 0 1",
+                vec![E::InvalidEnPassantSquare(Square::E4)],
+            ),
+            ("4k3/8/8/8/8/8/4R3/4K3 b - - 0 1", vec![]),
+            ("4k3/8/8/8/8/8/4R3/4K3 w - - 0 1", vec![E::OpponentInCheck]),
+        ];
+        for (fen, expected) in cases {
+            let result = BoardRepr::from_fen(fen);
+            if expected.is_empty() {
+                assert!(result.is_ok(), "{fen}");
+            } else {
+                assert_eq!(
+                    result,
+                    Err(crate::fen::ParseFenError::InvalidPosition(expected)),
+                    "{fen}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_from_pieces() {
+        let board = BoardRepr::try_from(BoardRepr::default().pieces()).unwrap();
+        assert_eq!(board, BoardRepr::default());
+
+        let mut pieces = BoardRepr::default().pieces();
+        pieces[Square::E1 as usize] = None;
+        pieces[Square::H1 as usize] = None;
+        assert_eq!(
+            BoardRepr::try_from(pieces),
+            Err(vec![PositionError::MissingKing(Color::White)])
+        );
+    }
+}
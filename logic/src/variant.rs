@@ -0,0 +1,427 @@
+//! Variants of the game with their own starting positions, endings and restrictions on moves.
+//!
+//! A [`Variant`] only changes what is built on top of the move generator:
+//! the pieces still move and merge as usual, and a variant can forbid some of the legal moves,
+//! end the game early, or say when a side can no longer win.
+//! A [`Game`] is played under a variant, and the engine and the board component take one too.
+//!
+//! Variants are passed around as `&'static dyn Variant`,
+//! and are compared, serialized and looked up by their [name](Variant::name).
+
+use std::fmt;
+
+use crate::{
+    board_repr::BoardRepr,
+    game::{DrawReason, Game, GameOutcome, WinReason},
+    pieces::{movement::Move, Color},
+    pocket::Pockets,
+    square::{Rank, Square},
+};
+
+/// The rules that a variant changes. Every method defaults to the standard rules.
+pub trait Variant: fmt::Debug + Send + Sync {
+    /// The name that the variant is referred to by in game records and in the API.
+    fn name(&self) -> &'static str;
+
+    fn start_position(&self) -> BoardRepr {
+        BoardRepr::default()
+    }
+
+    /// Whether a legal move may be played in this variant.
+    fn allows_move(&self, _board: &BoardRepr, _move: Move) -> bool {
+        true
+    }
+
+    /// Ends the game for a reason specific to the variant.
+    /// This is checked before checkmate, stalemate and the usual draws.
+    fn outcome(&self, _game: &Game) -> Option<GameOutcome> {
+        None
+    }
+
+    /// Whether the side can no longer win, so that the game is drawn if neither side can.
+    fn has_insufficient_material(&self, board: &BoardRepr, color: Color) -> bool {
+        board.has_insufficient_material(color)
+    }
+}
+
+impl PartialEq for dyn Variant {
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name()
+    }
+}
+
+impl Eq for dyn Variant {}
+
+impl Default for &'static dyn Variant {
+    fn default() -> Self {
+        &Standard
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for dyn Variant {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for &'static dyn Variant {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        from_name(&name)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown variant: {name:?}")))
+    }
+}
+
+/// The variants that come with this crate.
+pub const ALL: [&dyn Variant; 5] = [
+    &Standard,
+    &KingOfTheHill,
+    &ThreeCheck,
+    &RacingKings,
+    &Crazyhouse,
+];
+
+/// Finds one of the variants in [`ALL`] by its name.
+pub fn from_name(name: &str) -> Option<&'static dyn Variant> {
+    ALL.into_iter()
+        .find(|variant| variant.name().eq_ignore_ascii_case(name))
+}
+
+/// The usual game, won by checkmate.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Standard;
+
+impl Variant for Standard {
+    fn name(&self) -> &'static str {
+        "Standard"
+    }
+}
+
+/// A side also wins by bringing its king to one of the four center squares.
+/// Since the king can't merge, it can only get there alone.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KingOfTheHill;
+
+impl KingOfTheHill {
+    pub const HILL: [Square; 4] = [Square::D4, Square::E4, Square::D5, Square::E5];
+}
+
+impl Variant for KingOfTheHill {
+    fn name(&self) -> &'static str {
+        "King of the Hill"
+    }
+
+    fn outcome(&self, game: &Game) -> Option<GameOutcome> {
+        let board = game.board();
+        [Color::White, Color::Black].into_iter().find_map(|color| {
+            let king = board.try_king_square(color)?;
+            KingOfTheHill::HILL
+                .contains(&king)
+                .then_some(GameOutcome::Win {
+                    winner: color,
+                    reason: WinReason::KingOfTheHill,
+                })
+        })
+    }
+
+    fn has_insufficient_material(&self, _board: &BoardRepr, _color: Color) -> bool {
+        // A lone king can still walk to the center
+        false
+    }
+}
+
+/// A side also wins by giving check for the third time.
+/// A combination gives one check, even if both of its halves attack the king.
+///
+/// The checks are counted by the position, see [`BoardRepr::checks_given`],
+/// so a game started from a position that doesn't count them can't be won this way.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ThreeCheck;
+
+impl ThreeCheck {
+    pub const CHECKS_TO_WIN: u8 = 3;
+
+    /// How many times the side has given check so far.
+    pub fn checks_given(board: &BoardRepr, color: Color) -> u8 {
+        board
+            .checks_given
+            .map_or(0, |checks_given| checks_given[color as usize])
+    }
+}
+
+impl Variant for ThreeCheck {
+    fn name(&self) -> &'static str {
+        "Three-check"
+    }
+
+    fn start_position(&self) -> BoardRepr {
+        let mut board = BoardRepr::default();
+        board.checks_given = Some([0; 2]);
+        board.refresh_zobrist_key();
+        board
+    }
+
+    fn outcome(&self, game: &Game) -> Option<GameOutcome> {
+        // Only the side that just moved can have given another check
+        let board = game.board();
+        let color = board.side_to_move.opposite();
+        (ThreeCheck::checks_given(board, color) >= ThreeCheck::CHECKS_TO_WIN).then_some(
+            GameOutcome::Win {
+                winner: color,
+                reason: WinReason::ThreeChecks,
+            },
+        )
+    }
+}
+
+/// Both kings race to the eighth rank, and giving check is not allowed.
+///
+/// The pieces start side by side on the first two ranks, and there are no pawns,
+/// but they can merge as usual.
+/// If White gets there first, Black has one more move to also get there and draw.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RacingKings;
+
+impl RacingKings {
+    pub const START_FEN: &'static str = "8/8/8/8/8/8/krbnNBRK/qrbnNBRQ w - - 0 1";
+
+    fn reached_goal(board: &BoardRepr, color: Color) -> bool {
+        board
+            .try_king_square(color)
+            .is_some_and(|king| king.rank() == Rank::Eighth)
+    }
+}
+
+impl Variant for RacingKings {
+    fn name(&self) -> &'static str {
+        "Racing Kings"
+    }
+
+    fn start_position(&self) -> BoardRepr {
+        BoardRepr::from_fen(RacingKings::START_FEN).expect("Racing Kings start position is valid")
+    }
+
+    fn allows_move(&self, board: &BoardRepr, move_: Move) -> bool {
+        let mut after = *board;
+        after.make_move(move_);
+        !after.king_in_check(board.side_to_move.opposite())
+    }
+
+    fn outcome(&self, game: &Game) -> Option<GameOutcome> {
+        let board = game.board();
+        let win = |winner| GameOutcome::Win {
+            winner,
+            reason: WinReason::RaceWon,
+        };
+        match (
+            RacingKings::reached_goal(board, Color::White),
+            RacingKings::reached_goal(board, Color::Black),
+        ) {
+            (true, true) => Some(GameOutcome::Draw {
+                reason: DrawReason::BothKingsReachedGoal,
+            }),
+            (false, true) => Some(win(Color::Black)),
+            (true, false) => {
+                // Black can still draw if its king can reach the goal right away
+                let catches_up = board.side_to_move == Color::Black
+                    && game.legal_moves().into_iter().any(|move_| {
+                        Some(move_.from) == board.try_king_square(Color::Black)
+                            && move_.to.rank() == Rank::Eighth
+                    });
+                (!catches_up).then_some(win(Color::White))
+            }
+            (false, false) => None,
+        }
+    }
+
+    fn has_insufficient_material(&self, _board: &BoardRepr, _color: Color) -> bool {
+        // The kings alone are enough to race
+        false
+    }
+}
+
+/// Captured pieces go to the capturer's hand, and can be dropped back on the board instead of moving,
+/// see [`crate::pocket`]. A captured combination is split, so both of its halves come back.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Crazyhouse;
+
+impl Variant for Crazyhouse {
+    fn name(&self) -> &'static str {
+        "Crazyhouse"
+    }
+
+    fn start_position(&self) -> BoardRepr {
+        let mut board = BoardRepr::default();
+        board.pockets = Some(Pockets::default());
+        board.refresh_zobrist_key();
+        board
+    }
+
+    fn has_insufficient_material(&self, _board: &BoardRepr, _color: Color) -> bool {
+        // Captured pieces come back, so material never runs out
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        board_repr::IllegalMoveError,
+        game_record::GameRecord,
+        pieces::{CombinationPiece, UnitaryPiece},
+    };
+
+    fn play_san(game: &mut Game, moves: &[&str]) {
+        for san in moves {
+            let move_ = game.board().parse_san(san).unwrap();
+            game.play(move_).unwrap();
+        }
+    }
+
+    fn game(fen: &str, variant: &'static dyn Variant) -> Game {
+        Game::from_position(BoardRepr::from_fen(fen).unwrap(), variant)
+    }
+
+    #[test]
+    fn test_names() {
+        for variant in ALL {
+            assert_eq!(from_name(variant.name()), Some(variant));
+        }
+        assert_eq!(
+            from_name("racing kings"),
+            Some(&RacingKings as &dyn Variant)
+        );
+        assert_eq!(from_name("Atomic"), None);
+    }
+
+    #[test]
+    fn test_king_of_the_hill() {
+        // In the standard game, two lone kings are a draw
+        let mut game = game("8/8/8/8/8/5K2/8/k7 w - - 0 1", &KingOfTheHill);
+        assert_eq!(game.outcome(), None);
+        play_san(&mut game, &["Ke4"]);
+        assert_eq!(
+            game.outcome(),
+            Some(GameOutcome::Win {
+                winner: Color::White,
+                reason: WinReason::KingOfTheHill
+            })
+        );
+    }
+
+    #[test]
+    fn test_three_check() {
+        let mut game = Game::from_variant(&ThreeCheck);
+        play_san(
+            &mut game,
+            &["e4", "f6", "Qh5+", "g6", "Qxg6+", "hxg6", "Bc4", "a6"],
+        );
+        assert_eq!(ThreeCheck::checks_given(game.board(), Color::White), 2);
+        assert_eq!(game.outcome(), None);
+        assert_eq!(
+            game.board().to_fen(),
+            "rnbqkbnr/1pppp3/p4pp1/8/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 0 5 +2+0"
+        );
+
+        // The same position with a different number of checks given is a different one
+        let fewer_checks = BoardRepr::from_fen(
+            "rnbqkbnr/1pppp3/p4pp1/8/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 0 5 +1+0",
+        )
+        .unwrap();
+        assert_ne!(fewer_checks.zobrist_key(), game.board().zobrist_key());
+        assert_eq!(
+            game.board().zobrist_key(),
+            game.board().compute_zobrist_key()
+        );
+        assert_eq!(
+            BoardRepr::from_fen(&game.board().to_fen())
+                .unwrap()
+                .zobrist_key(),
+            game.board().zobrist_key()
+        );
+
+        play_san(&mut game, &["Bf7+"]);
+        assert_eq!(
+            game.outcome(),
+            Some(GameOutcome::Win {
+                winner: Color::White,
+                reason: WinReason::ThreeChecks
+            })
+        );
+        game.undo();
+        assert_eq!(ThreeCheck::checks_given(game.board(), Color::White), 2);
+    }
+
+    #[test]
+    fn test_racing_kings() {
+        let game = Game::from_variant(&RacingKings);
+        assert_eq!(game.legal_moves().len(), 34);
+
+        // Giving check is not allowed
+        let mut game = self::game("8/8/8/8/8/8/k6K/7R w - - 0 1", &RacingKings);
+        let check = game.board().parse_san("Ra1").unwrap();
+        assert!(!game.legal_moves().contains(&check));
+        assert_eq!(
+            game.play(check),
+            Err(IllegalMoveError::ForbiddenByVariant {
+                from: Square::H1,
+                to: Square::A1
+            })
+        );
+
+        // Black is too far behind to catch up
+        let mut game = self::game("8/6K1/8/k7/8/8/8/8 w - - 0 1", &RacingKings);
+        play_san(&mut game, &["Kg8"]);
+        assert_eq!(
+            game.outcome(),
+            Some(GameOutcome::Win {
+                winner: Color::White,
+                reason: WinReason::RaceWon
+            })
+        );
+
+        // Black gets one more move to draw
+        let mut game = self::game("8/k5K1/8/8/8/8/8/8 w - - 0 1", &RacingKings);
+        play_san(&mut game, &["Kg8"]);
+        assert_eq!(game.outcome(), None);
+        play_san(&mut game, &["Ka8"]);
+        assert_eq!(
+            game.outcome(),
+            Some(GameOutcome::Draw {
+                reason: DrawReason::BothKingsReachedGoal
+            })
+        );
+    }
+
+    #[test]
+    fn test_crazyhouse() {
+        let mut game = Game::from_variant(&Crazyhouse);
+        play_san(&mut game, &["e4", "d5", "exd5", "Qxd5", "Nc3", "Qd8"]);
+        let pockets = game.board().pockets.unwrap();
+        assert_eq!(pockets.count(Color::White, UnitaryPiece::Pawn), 1);
+        assert_eq!(pockets.count(Color::Black, UnitaryPiece::Pawn), 1);
+
+        // The pawn in hand merges with the knight, and comes back whole when captured
+        play_san(&mut game, &["P@c3", "e5", "NPd5", "Bd6", "Bb5+", "c6"]);
+        assert_eq!(
+            game.board()[Square::D5],
+            CombinationPiece::new(UnitaryPiece::Knight, UnitaryPiece::Pawn)
+                .map(CombinationPiece::white)
+        );
+        play_san(&mut game, &["Bd3", "cxd5"]);
+        let pockets = game.board().pockets.unwrap();
+        assert_eq!(pockets.count(Color::Black, UnitaryPiece::Knight), 1);
+        assert_eq!(pockets.count(Color::Black, UnitaryPiece::Pawn), 2);
+        assert!(pockets.is_empty(Color::White));
+        assert_eq!(game.to_record().tag("Variant"), Some("Crazyhouse"));
+
+        // Reading the record back gives the same game, with drops and all
+        let record = GameRecord::from_pgn(&game.to_record().to_pgn().unwrap()).unwrap();
+        assert_eq!(record.variant(), Ok(&Crazyhouse as &dyn Variant));
+        assert_eq!(record.start_position(), Ok(*game.start_position()));
+        assert_eq!(record.mainline_moves(), game.moves().collect::<Vec<_>>());
+    }
+}
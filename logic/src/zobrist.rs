@@ -0,0 +1,362 @@
+//! Zobrist hashing of positions.
+//!
+//! Every colored piece on every square, the side to move, each castling right,
+//! each starting file of a king or castling rook other than the usual one, each en passant file, each count of each piece in hand
+//! and each number of checks given in Three-check has a random 64-bit key.
+//! The key of a position is the XOR of the keys of everything in it,
+//! so it can be updated incrementally as pieces move.
+//!
+//! [`BoardRepr`] keeps its key up to date in [`BoardRepr::make_move`] and [`BoardRepr::unmake_move`].
+//! After editing a board's fields directly, call [`BoardRepr::refresh_zobrist_key`].
+
+use crate::{
+    board_repr::BoardRepr,
+    pieces::{movement::CastlingFiles, Color, ColorPiece, Piece},
+    pocket::Pockets,
+    square::Square,
+};
+
+/// Generates the next number of the SplitMix64 sequence,
+/// which is good enough to fill the tables at compile time.
+pub(crate) const fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+struct Keys {
+    pieces: [[[u64; 64]; Piece::COUNT]; 2],
+    black_to_move: u64,
+    castling: [u64; 4],
+    /// One key per file of the king, the king side rook and the queen side rook of each side,
+    /// of which only the files other than the usual ones are used.
+    castling_files: [[[u64; 8]; 3]; 2],
+    en_passant_file: [u64; 8],
+    /// One key per count of each piece in hand, from 1 up, since holding none contributes nothing.
+    pockets: [[[u64; u8::MAX as usize]; Pockets::DROPPABLE.len()]; 2],
+    /// One key per number of checks given by each side, from 1 up, like the pockets.
+    checks: [[u64; u8::MAX as usize]; 2],
+}
+
+const fn generate_keys() -> Keys {
+    let mut state = 0x4d45_5247_494e_4721;
+    let mut keys = Keys {
+        pieces: [[[0; 64]; Piece::COUNT]; 2],
+        black_to_move: 0,
+        castling: [0; 4],
+        castling_files: [[[0; 8]; 3]; 2],
+        en_passant_file: [0; 8],
+        pockets: [[[0; u8::MAX as usize]; Pockets::DROPPABLE.len()]; 2],
+        checks: [[0; u8::MAX as usize]; 2],
+    };
+
+    let mut color = 0;
+    while color < 2 {
+        let mut piece = 0;
+        while piece < Piece::COUNT {
+            let mut square = 0;
+            while square < 64 {
+                keys.pieces[color][piece][square] = splitmix64(&mut state);
+                square += 1;
+            }
+            piece += 1;
+        }
+        color += 1;
+    }
+
+    keys.black_to_move = splitmix64(&mut state);
+
+    let mut i = 0;
+    while i < 4 {
+        keys.castling[i] = splitmix64(&mut state);
+        i += 1;
+    }
+
+    let mut i = 0;
+    while i < 8 {
+        keys.en_passant_file[i] = splitmix64(&mut state);
+        i += 1;
+    }
+
+    let mut color = 0;
+    while color < 2 {
+        let mut piece = 0;
+        while piece < Pockets::DROPPABLE.len() {
+            let mut count = 0;
+            while count < u8::MAX as usize {
+                keys.pockets[color][piece][count] = splitmix64(&mut state);
+                count += 1;
+            }
+            piece += 1;
+        }
+        color += 1;
+    }
+
+    let mut color = 0;
+    while color < 2 {
+        let mut count = 0;
+        while count < u8::MAX as usize {
+            keys.checks[color][count] = splitmix64(&mut state);
+            count += 1;
+        }
+        color += 1;
+    }
+
+    let mut color = 0;
+    while color < 2 {
+        let mut role = 0;
+        while role < 3 {
+            let mut file = 0;
+            while file < 8 {
+                keys.castling_files[color][role][file] = splitmix64(&mut state);
+                file += 1;
+            }
+            role += 1;
+        }
+        color += 1;
+    }
+
+    keys
+}
+
+static KEYS: Keys = generate_keys();
+
+/// The key for a piece standing on a square.
+pub fn piece_key(piece: ColorPiece, square: Square) -> u64 {
+    KEYS.pieces[piece.color() as usize][piece.piece().index()][square as usize]
+}
+
+/// The key for the side to move, which is zero for White.
+pub fn side_key(side: Color) -> u64 {
+    match side {
+        Color::White => 0,
+        Color::Black => KEYS.black_to_move,
+    }
+}
+
+/// The combined key of the castling rights that are available,
+/// in the order of [`BoardRepr::castling_rights`].
+pub fn castling_key(castling_rights: [bool; 4]) -> u64 {
+    castling_rights
+        .iter()
+        .zip(KEYS.castling)
+        .filter(|(right, _)| **right)
+        .fold(0, |key, (_, right_key)| key ^ right_key)
+}
+
+/// The key for the starting files of the kings and castling rooks,
+/// which is zero when they are the usual ones, see [`BoardRepr::castling_files`].
+pub fn castling_files_key(castling_files: [CastlingFiles; 2]) -> u64 {
+    let mut key = 0;
+    for (color, files) in castling_files.into_iter().enumerate() {
+        let [king_side, queen_side] = files.rooks;
+        let [standard_king_side, standard_queen_side] = CastlingFiles::STANDARD.rooks;
+        for (role, file, standard) in [
+            (0, files.king, CastlingFiles::STANDARD.king),
+            (1, king_side, standard_king_side),
+            (2, queen_side, standard_queen_side),
+        ] {
+            if file != standard {
+                key ^= KEYS.castling_files[color][role][file as usize];
+            }
+        }
+    }
+    key
+}
+
+/// The key for the en passant square, which only depends on its file.
+pub fn en_passant_key(en_passant_square: Option<Square>) -> u64 {
+    en_passant_square.map_or(0, |square| KEYS.en_passant_file[square.file() as usize])
+}
+
+/// The key for the pieces in hand, which is zero when there are none, or no pockets at all.
+/// Each count of each piece has a key of its own.
+pub fn pockets_key(pockets: Option<Pockets>) -> u64 {
+    let Some(pockets) = pockets else {
+        return 0;
+    };
+    let mut key = 0;
+    for color in [Color::White, Color::Black] {
+        for piece in Pockets::DROPPABLE {
+            let count = pockets.count(color, piece);
+            if count > 0 {
+                key ^= KEYS.pockets[color as usize][piece as usize][usize::from(count) - 1];
+            }
+        }
+    }
+    key
+}
+
+/// The key for the number of checks each side has given,
+/// which is zero when neither has given any, or checks aren't counted.
+pub fn checks_key(checks_given: Option<[u8; 2]>) -> u64 {
+    let Some(checks_given) = checks_given else {
+        return 0;
+    };
+    let mut key = 0;
+    for color in [Color::White, Color::Black] {
+        let count = checks_given[color as usize];
+        if count > 0 {
+            key ^= KEYS.checks[color as usize][usize::from(count) - 1];
+        }
+    }
+    key
+}
+
+impl BoardRepr {
+    /// Computes the Zobrist key of this position from scratch.
+    pub fn compute_zobrist_key(&self) -> u64 {
+        self.iter_pieces()
+            .fold(0, |key, (square, piece)| key ^ piece_key(piece, square))
+            ^ side_key(self.side_to_move)
+            ^ castling_key(self.castling_rights)
+            ^ castling_files_key(self.castling_files)
+            ^ en_passant_key(self.en_passant_square)
+            ^ pockets_key(self.pockets)
+            ^ checks_key(self.checks_given)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pieces::{movement::get_all_legal_moves, movement::Move, UnitaryPiece};
+
+    #[test]
+    fn test_keys_are_distinct() {
+        let mut all_keys: Vec<u64> = KEYS.pieces.iter().flatten().flatten().copied().collect();
+        all_keys.push(KEYS.black_to_move);
+        all_keys.extend(KEYS.castling);
+        all_keys.extend(KEYS.castling_files.iter().flatten().flatten());
+        all_keys.extend(KEYS.en_passant_file);
+        all_keys.extend(KEYS.pockets.iter().flatten().flatten());
+        all_keys.extend(KEYS.checks.iter().flatten());
+
+        let count = all_keys.len();
+        all_keys.sort_unstable();
+        all_keys.dedup();
+        assert_eq!(all_keys.len(), count);
+        assert!(!all_keys.contains(&0));
+    }
+
+    #[test]
+    fn test_incremental_key() {
+        let mut board =
+            BoardRepr::from_fen("r3k2r/1p4P1/1(QN)6/2Pp4/8/8/P3(BP)1p1/R3K2R w KQkq d6 0 20")
+                .unwrap();
+        let start = board;
+        assert_eq!(board.zobrist_key(), board.compute_zobrist_key());
+
+        // Check every move from a sequence of positions,
+        // following the last move each time
+        let mut undos = vec![];
+        for _ in 0..20 {
+            for move_ in get_all_legal_moves(&board, board.side_to_move) {
+                let mut after = board;
+                let undo = after.make_move(move_);
+                assert_eq!(
+                    after.zobrist_key(),
+                    after.compute_zobrist_key(),
+                    "{move_:?} in {}",
+                    board.to_fen()
+                );
+                after.unmake_move(undo);
+                assert_eq!(after.zobrist_key(), board.zobrist_key());
+            }
+
+            let moves = get_all_legal_moves(&board, board.side_to_move);
+            let Some(move_) = moves.last() else {
+                break;
+            };
+            undos.push(board.make_move(*move_));
+        }
+
+        while let Some(undo) = undos.pop() {
+            board.unmake_move(undo);
+        }
+        assert_eq!(board, start);
+    }
+
+    #[test]
+    fn test_pocket_counts() {
+        let key = |knights: u8, pawns: u8| {
+            let mut pockets = Pockets::default();
+            pockets.set_count(Color::White, UnitaryPiece::Knight, knights);
+            pockets.set_count(Color::White, UnitaryPiece::Pawn, pawns);
+            pockets_key(Some(pockets))
+        };
+
+        assert_eq!(key(0, 0), 0);
+        let mut keys = vec![];
+        for knights in 0..4 {
+            for pawns in 0..4 {
+                keys.push(key(knights, pawns));
+            }
+        }
+        let count = keys.len();
+        keys.sort_unstable();
+        keys.dedup();
+        assert_eq!(keys.len(), count);
+    }
+
+    #[test]
+    fn test_castling_files() {
+        // The same right, but to castle with the rook on c1 or the one on a1
+        let c_rook = BoardRepr::from_fen("4k3/8/8/8/8/8/8/R1R1K3 w C - 0 1").unwrap();
+        let a_rook = BoardRepr::from_fen("4k3/8/8/8/8/8/8/R1R1K3 w Q - 0 1").unwrap();
+        assert_ne!(c_rook, a_rook);
+        assert_ne!(c_rook.zobrist_key(), a_rook.zobrist_key());
+
+        // Moving the rooks and the king forgets the files, which the key follows
+        let board = BoardRepr::from_fen("1r4kr/p7/8/8/8/8/P7/1R4KR w HBhb - 0 1").unwrap();
+        assert_ne!(castling_files_key(board.castling_files), 0);
+        for move_ in get_all_legal_moves(&board, board.side_to_move) {
+            let mut after = board;
+            after.make_move(move_);
+            for move_ in get_all_legal_moves(&after, after.side_to_move) {
+                let mut after = after;
+                after.make_move(move_);
+                assert_eq!(
+                    after.zobrist_key(),
+                    after.compute_zobrist_key(),
+                    "{move_:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_transposition() {
+        let play = |moves: &[(Square, Square)]| {
+            let mut board = BoardRepr::default();
+            for &(from, to) in moves {
+                board
+                    .play(Move {
+                        from,
+                        to,
+                        which_half: None,
+                        drop: None,
+                    })
+                    .unwrap();
+            }
+            board.zobrist_key()
+        };
+
+        let knights_first = play(&[
+            (Square::G1, Square::F3),
+            (Square::G8, Square::F6),
+            (Square::B1, Square::C3),
+            (Square::B8, Square::C6),
+        ]);
+        let knights_swapped = play(&[
+            (Square::B1, Square::C3),
+            (Square::B8, Square::C6),
+            (Square::G1, Square::F3),
+            (Square::G8, Square::F6),
+        ]);
+        assert_eq!(knights_first, knights_swapped);
+        assert_ne!(knights_first, BoardRepr::default().zobrist_key());
+    }
+}
@@ -0,0 +1,35 @@
+use merging_board_logic::{
+    board_repr::BoardRepr,
+    perft::{parse_perft_suite, perft},
+};
+
+const SUITE: &str = include_str!("perft_suite.epd");
+
+/// Runs every entry of the suite that has at most this many nodes.
+fn run_suite(max_nodes: u64) {
+    for position in parse_perft_suite(SUITE) {
+        let board = BoardRepr::from_fen(&position.fen).unwrap();
+        for (depth, expected) in position.expected {
+            if expected <= max_nodes {
+                assert_eq!(
+                    perft(&board, depth),
+                    expected,
+                    "{} at depth {depth}",
+                    position.fen
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn perft_suite_shallow() {
+    run_suite(500_000);
+}
+
+/// Takes a while without optimizations; run with `cargo test --release -- --ignored`.
+#[test]
+#[ignore]
+fn perft_suite_full() {
+    run_suite(u64::MAX);
+}
@@ -1,6 +1,9 @@
+#[cfg(feature = "compact")]
+use merging_board_logic::encoding::{compact_board, compact_moves};
 use merging_board_logic::{
     board_repr::BoardRepr,
     pieces::{movement::Move, Color},
+    variant::Variant,
 };
 use serde::{Deserialize, Serialize};
 
@@ -10,7 +13,10 @@ use crate::GameId;
 pub struct RunningGameState {
     pub id: GameId,
     pub your_color: Color,
+    pub variant: &'static dyn Variant,
+    #[cfg_attr(feature = "compact", serde(with = "compact_board"))]
     pub current_game_state: BoardRepr,
+    #[cfg_attr(feature = "compact", serde(with = "compact_moves"))]
     pub move_history: Vec<Move>,
     pub your_clock: std::time::Duration,
     pub their_clock: std::time::Duration,
@@ -25,7 +31,10 @@ pub enum GameStatus {
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct CompletedGameState {
     pub id: GameId,
+    pub variant: &'static dyn Variant,
+    #[cfg_attr(feature = "compact", serde(with = "compact_moves"))]
     pub move_history: Vec<Move>,
+    #[cfg_attr(feature = "compact", serde(with = "compact_board"))]
     pub final_game_state: BoardRepr,
     pub winner: Color,
 }
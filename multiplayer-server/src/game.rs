@@ -4,7 +4,10 @@ use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
 };
-use merging_board_logic::pieces::{movement::Move, Color};
+use merging_board_logic::{
+    pieces::{movement::Move, Color},
+    variant::Variant,
+};
 
 use crate::managers::AppState;
 
@@ -28,6 +31,7 @@ impl Handle {
 pub enum GameCommand {
     Create {
         id: GameId,
+        variant: &'static dyn Variant,
         white_token: String,
         black_token: String,
     },
@@ -50,6 +54,8 @@ pub enum SingleGameCommand {
 pub struct SingleGameHandle {
     pub tx: tokio::sync::mpsc::Sender<SingleGameCommand>,
     pub side: Color,
+    /// The variant the game is played in, to tell the players in `api::RunningGameState`.
+    pub variant: &'static dyn Variant,
 }
 
 pub async fn handle_game_request(
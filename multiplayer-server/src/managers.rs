@@ -1,5 +1,5 @@
 use api::{GameId, GameTermination, MatchmakingCounts, MatchmakingFoundGame};
-use merging_board_logic::pieces::Color;
+use merging_board_logic::{pieces::Color, variant::Variant};
 use tokio::sync::oneshot;
 
 use crate::game::SingleGameHandle;
@@ -126,6 +126,7 @@ async fn game_manager(
     let mut game_pool = std::collections::HashMap::new();
 
     struct Game {
+        variant: &'static dyn Variant,
         white_token: String,
         black_token: String,
         tx: tokio::sync::mpsc::Sender<crate::game::SingleGameCommand>,
@@ -142,14 +143,21 @@ async fn game_manager(
         match msg {
             crate::game::GameCommand::Create {
                 id,
+                variant,
                 white_token,
                 black_token,
             } => {
                 let (single_tx, single_rx) = tokio::sync::mpsc::channel(100);
-                tokio::spawn(single_game_manager(single_rx, id.clone(), tx.clone()));
+                tokio::spawn(single_game_manager(
+                    single_rx,
+                    id.clone(),
+                    variant,
+                    tx.clone(),
+                ));
                 game_pool.insert(
                     id,
                     Game {
+                        variant,
                         white_token,
                         black_token,
                         tx: single_tx.clone(),
@@ -169,6 +177,7 @@ async fn game_manager(
                             } else {
                                 Color::Black
                             },
+                            variant: game.variant,
                         })
                     } else {
                         None
@@ -193,13 +202,17 @@ async fn game_manager(
 async fn single_game_manager(
     mut rx: tokio::sync::mpsc::Receiver<crate::game::SingleGameCommand>,
     id: GameId,
+    variant: &'static dyn Variant,
     tx: tokio::sync::mpsc::Sender<crate::game::GameCommand>,
 ) {
+    // The game is played by the rules of its variant, from the variant's starting position
+    let game = merging_board_logic::game::Game::from_variant(variant);
+
     // Immediately close the game
 
     tx.send(crate::game::GameCommand::Terminate {
         id,
-        move_history: vec![],
+        move_history: game.moves().collect(),
         termination: GameTermination::Aborted,
     })
     .await